@@ -0,0 +1,77 @@
+#![feature(test)]
+
+extern crate test;
+
+use test::Bencher;
+use tokio::runtime::Runtime;
+
+const FIB: &str = r#"
+(defn fib
+  ([0] 0)
+  ([1] 1)
+  ([n] (+ (fib (- n 1)) (fib (- n 2)))))
+"#;
+
+const SUM: &str = r#"
+(defn sum
+  ([0 acc] acc)
+  ([n acc] (sum (- n 1) (+ acc n))))
+"#;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+#[bench]
+fn fib_tree_walking(b: &mut Bencher) {
+    let runtime = runtime();
+    let (env, _) = runtime
+        .block_on(compiler::evaluate_source(base::environment(), FIB))
+        .unwrap();
+    b.iter(|| {
+        runtime
+            .block_on(compiler::evaluate_source(env.clone(), "(fib 15)"))
+            .unwrap()
+    });
+}
+
+#[bench]
+fn fib_bytecode(b: &mut Bencher) {
+    let runtime = runtime();
+    let (env, _) = runtime
+        .block_on(compiler::vm::evaluate_source(base::environment(), FIB))
+        .unwrap();
+    b.iter(|| {
+        runtime
+            .block_on(compiler::vm::evaluate_source(env.clone(), "(fib 15)"))
+            .unwrap()
+    });
+}
+
+#[bench]
+fn sum_tree_walking(b: &mut Bencher) {
+    let runtime = runtime();
+    let (env, _) = runtime
+        .block_on(compiler::evaluate_source(base::environment(), SUM))
+        .unwrap();
+    b.iter(|| {
+        runtime
+            .block_on(compiler::evaluate_source(env.clone(), "(sum 1000 0)"))
+            .unwrap()
+    });
+}
+
+#[bench]
+fn sum_bytecode(b: &mut Bencher) {
+    let runtime = runtime();
+    let (env, _) = runtime
+        .block_on(compiler::vm::evaluate_source(base::environment(), SUM))
+        .unwrap();
+    b.iter(|| {
+        runtime
+            .block_on(compiler::vm::evaluate_source(env.clone(), "(sum 1000 0)"))
+            .unwrap()
+    });
+}
@@ -11,18 +11,16 @@ use alloc::vec;
 use alloc::vec::Vec;
 use compiler::effect::{error, Effect};
 use compiler::expression::{Call, Environment, Function, Pattern};
-use compiler::Expression::{Integer, NativeFunction, Ratio};
-use compiler::{array, evaluate_expressions, extract, map, pattern_match, ratio, Expression};
+use compiler::task::{self, Outcome, Task};
+use compiler::Expression::{Integer, NativeFunction, SpecialForm};
+use compiler::{
+    agent, arithmetic_with_precision, array, binary, channel, evaluate_expressions, extract, map,
+    pattern_match, stm, Expression, Operation, Rounding, RoundingMode,
+};
+use core::cmp::Ordering;
 use im::{ordmap, vector, Vector};
-use rug;
 
-pub fn truthy(expression: &Expression) -> bool {
-    match expression {
-        Expression::Nil => false,
-        Expression::Bool(false) => false,
-        _ => true,
-    }
-}
+pub use compiler::truthy;
 
 pub type Result = core::result::Result<(Environment, Expression), Effect>;
 
@@ -48,6 +46,105 @@ fn function(env: Environment, args: Vector<Expression>) -> core::result::Result<
     }
 }
 
+const DEFAULT_CONCURRENCY: usize = 16;
+
+fn concurrency(
+    env: &Environment,
+    limit: Option<&Expression>,
+) -> core::result::Result<usize, Effect> {
+    let limit = match limit.or(env.get("*concurrency*")) {
+        Some(limit) => extract::integer(limit.clone())?
+            .to_usize()
+            .filter(|limit| *limit > 0)
+            .ok_or(error("Expected positive integer"))?,
+        None => DEFAULT_CONCURRENCY,
+    };
+    Ok(limit)
+}
+
+async fn comparison(
+    env: Environment,
+    args: Vector<Expression>,
+    accept: fn(Ordering) -> bool,
+) -> Result {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    for (lhs, rhs) in args.iter().zip(args.iter().skip(1)) {
+        if !accept(compiler::compare(lhs, rhs)?) {
+            return Ok((env, Expression::Bool(false)));
+        }
+    }
+    Ok((env, Expression::Bool(true)))
+}
+
+async fn extremum(env: Environment, args: Vector<Expression>, keep: Ordering) -> Result {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    if args.is_empty() {
+        let name = if keep == Ordering::Less { "min" } else { "max" };
+        return Err(error(&format!("{} expects at least one argument", name)));
+    }
+    let (initial, args) = args.split_at(1);
+    let result = args
+        .iter()
+        .try_fold(initial[0].clone(), |best, candidate| {
+            let ordering = compiler::compare(candidate, &best)?;
+            Ok::<_, Effect>(if ordering == keep {
+                candidate.clone()
+            } else {
+                best
+            })
+        })?;
+    Ok((env, result))
+}
+
+async fn rounding(env: Environment, args: Vector<Expression>, rounding: Rounding) -> Result {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    if args.len() != 1 {
+        let name = match rounding {
+            Rounding::Floor => "floor",
+            Rounding::Ceil => "ceil",
+            Rounding::Round => "round",
+            Rounding::Truncate => "truncate",
+        };
+        return Err(error(&format!("{} expects one argument", name)));
+    }
+    Ok((env, compiler::round(rounding, args[0].clone())?))
+}
+
+async fn division(
+    env: Environment,
+    args: Vector<Expression>,
+    rounding: Rounding,
+    remainder: bool,
+) -> Result {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    if args.len() != 2 {
+        let name = match (rounding, remainder) {
+            (_, false) => "quot",
+            (Rounding::Floor, true) => "mod",
+            (_, true) => "rem",
+        };
+        return Err(error(&format!("{} expects a dividend and a divisor", name)));
+    }
+    let (dividend, divisor) = (args[0].clone(), args[1].clone());
+    if compiler::compare(&divisor, &Integer(0.into()))? == Ordering::Equal {
+        return Err(error("Division by zero"));
+    }
+    let precision = compiler::precision_from(&env)?;
+    let quotient = arithmetic_with_precision(
+        Operation::Divide,
+        dividend.clone(),
+        divisor.clone(),
+        precision,
+    )?;
+    let quotient = compiler::round(rounding, quotient)?;
+    if !remainder {
+        return Ok((env, quotient));
+    }
+    let product = arithmetic_with_precision(Operation::Multiply, divisor, quotient, precision)?;
+    let result = arithmetic_with_precision(Operation::Subtract, dividend, product, precision)?;
+    Ok((env, result))
+}
+
 pub fn environment() -> Environment {
     ordmap! {
         "=".to_string() => NativeFunction(
@@ -65,12 +162,10 @@ pub fn environment() -> Environment {
                 return Ok((env, Integer(0.into())));
             }
             let (env, args) = evaluate_expressions(env, args).await?;
+            let precision = compiler::precision_from(&env)?;
             let (initial, args) = args.split_at(1);
             let result = args.iter().try_fold(initial[0].clone(), |lhs, rhs| {
-                match (lhs, rhs) {
-                    (Integer(lhs), Integer(rhs)) => Ok(Integer((lhs + rhs).into())),
-                    _ => Err(error("Expected integer argument")),
-                }
+                arithmetic_with_precision(Operation::Add, lhs, rhs.clone(), precision)
             })?;
             Ok((env, result))
               })
@@ -80,12 +175,10 @@ pub fn environment() -> Environment {
           |env, args| {
               Box::pin(async move {
             let (env, args) = evaluate_expressions(env, args).await?;
+            let precision = compiler::precision_from(&env)?;
             let (initial, args) = args.split_at(1);
             let result = args.iter().try_fold(initial[0].clone(), |lhs, rhs| {
-                match (lhs, rhs) {
-                  (Integer(lhs), Integer(rhs)) => Ok(Integer((lhs - rhs).into())),
-                  _ => Err(error("Expected integer argument")),
-                }
+                arithmetic_with_precision(Operation::Subtract, lhs, rhs.clone(), precision)
             })?;
             Ok((env, result))
               })
@@ -98,14 +191,10 @@ pub fn environment() -> Environment {
                 return Ok((env, Integer(1.into())));
             }
             let (env, args) = evaluate_expressions(env, args).await?;
+            let precision = compiler::precision_from(&env)?;
             let (initial, args) = args.split_at(1);
             let result = args.iter().try_fold(initial[0].clone(), |lhs, rhs| {
-                match (lhs, rhs) {
-                  (Integer(lhs), Integer(rhs)) => Ok(Integer((lhs * rhs).into())),
-                  (Integer(lhs), Ratio(rhs)) => Ok(ratio((lhs * rhs).into())),
-                  (Ratio(lhs), Integer(rhs)) => Ok(ratio((lhs * rhs).into())),
-                  (lhs, rhs) => Err(error(&format!("Cannot multiply {} and {}", lhs, rhs))),
-                }
+                arithmetic_with_precision(Operation::Multiply, lhs, rhs.clone(), precision)
             })?;
             Ok((env, result))
               })
@@ -115,27 +204,123 @@ pub fn environment() -> Environment {
           |env, args| {
               Box::pin(async move {
             let (env, args) = evaluate_expressions(env, args).await?;
+            let precision = compiler::precision_from(&env)?;
             let (initial, args) = args.split_at(1);
             let result = args.iter().try_fold(initial[0].clone(), |lhs, rhs| {
-                match (lhs, rhs) {
-                  (Integer(lhs), Integer(rhs)) => Ok(ratio(rug::Rational::from((lhs, rhs)))),
-                  _ => Err(error("Expected integer argument")),
-                }
+                arithmetic_with_precision(Operation::Divide, lhs, rhs.clone(), precision)
             })?;
             Ok((env, result))
               })
           }
         ),
-        "if".to_string() => NativeFunction(
+        "<".to_string() => NativeFunction(|env, args| Box::pin(comparison(env, args, |o| o == Ordering::Less))),
+        "<=".to_string() => NativeFunction(|env, args| Box::pin(comparison(env, args, |o| o != Ordering::Greater))),
+        ">".to_string() => NativeFunction(|env, args| Box::pin(comparison(env, args, |o| o == Ordering::Greater))),
+        ">=".to_string() => NativeFunction(|env, args| Box::pin(comparison(env, args, |o| o != Ordering::Less))),
+        "min".to_string() => NativeFunction(|env, args| Box::pin(extremum(env, args, Ordering::Less))),
+        "max".to_string() => NativeFunction(|env, args| Box::pin(extremum(env, args, Ordering::Greater))),
+        "abs".to_string() => NativeFunction(
+          |env, args| {
+              Box::pin(async move {
+            let (env, args) = evaluate_expressions(env, args).await?;
+            if args.len() != 1 {
+                return Err(error("abs expects one argument"));
+            }
+            Ok((env, compiler::abs(args[0].clone())?))
+              })
+          }
+        ),
+        "floor".to_string() => NativeFunction(|env, args| Box::pin(rounding(env, args, Rounding::Floor))),
+        "ceil".to_string() => NativeFunction(|env, args| Box::pin(rounding(env, args, Rounding::Ceil))),
+        "round".to_string() => NativeFunction(|env, args| Box::pin(rounding(env, args, Rounding::Round))),
+        "truncate".to_string() => NativeFunction(|env, args| Box::pin(rounding(env, args, Rounding::Truncate))),
+        "quot".to_string() => NativeFunction(|env, args| Box::pin(division(env, args, Rounding::Truncate, false))),
+        "rem".to_string() => NativeFunction(|env, args| Box::pin(division(env, args, Rounding::Truncate, true))),
+        "mod".to_string() => NativeFunction(|env, args| Box::pin(division(env, args, Rounding::Floor, true))),
+        "integer".to_string() => NativeFunction(
           |env, args| {
               Box::pin(async move {
+            let (env, args) = evaluate_expressions(env, args).await?;
+            Ok((env, compiler::to_integer(args[0].clone())?))
+              })
+          }
+        ),
+        "rational".to_string() => NativeFunction(
+          |env, args| {
+              Box::pin(async move {
+            let (env, args) = evaluate_expressions(env, args).await?;
+            Ok((env, compiler::to_rational(args[0].clone())?))
+              })
+          }
+        ),
+        "float".to_string() => NativeFunction(
+          |env, args| {
+              Box::pin(async move {
+            let (env, args) = evaluate_expressions(env, args).await?;
+            let precision = match args.get(1) {
+                Some(bits) => {
+                    let mut scope = env.clone();
+                    scope.insert("*precision*".to_string(), bits.clone());
+                    compiler::precision_from(&scope)?
+                }
+                None => compiler::precision_from(&env)?,
+            };
+            Ok((env, compiler::to_float(args[0].clone(), precision)?))
+              })
+          }
+        ),
+        "decimal".to_string() => NativeFunction(
+          |env, args| {
+              Box::pin(async move {
+            let (env, args) = evaluate_expressions(env, args).await?;
+            if args.len() == 1 {
+                return Ok((env, compiler::to_decimal(args[0].clone())?));
+            }
+            let scale = extract::integer(args[1].clone())?
+                .to_u32()
+                .ok_or(error("Expected non negative scale"))?;
+            let mode = match args.get(2) {
+                Some(mode) => {
+                    let keyword = extract::keyword(mode.clone())?;
+                    RoundingMode::from_keyword(&keyword)
+                        .ok_or_else(|| error(&format!("Unknown rounding mode {}", keyword)))?
+                }
+                None => RoundingMode::HalfEven,
+            };
+            Ok((env, compiler::round_decimal(args[0].clone(), scale, mode)?))
+              })
+          }
+        ),
+        "with-precision".to_string() => SpecialForm(
+          |env, args| {
+              Box::pin(async move {
+            let original_env = env.clone();
+            let (env, bits) = compiler::evaluate(env, args[0].clone()).await?;
+            let mut scope = env;
+            scope.insert("*precision*".to_string(), bits);
+            compiler::precision_from(&scope)?;
+            let body = Expression::Call(Call {
+                function: Box::new(Expression::Symbol("do".to_string())),
+                arguments: args.skip(1),
+            });
+            let (_, value) = compiler::evaluate(scope, body).await?;
+            Ok((original_env, value))
+              })
+          }
+        ),
+        "if".to_string() => SpecialForm(
+          |env, args| {
+              Box::pin(async move {
+            if args.len() != 3 {
+                return Err(error("if expects a condition, a then branch and an else branch"));
+            }
             let (condition, then, otherwise) = (args[0].clone(), args[1].clone(), args[2].clone());
             let (env, condition) = compiler::evaluate(env, condition).await?;
             compiler::evaluate(env, if truthy(&condition) { then } else { otherwise }).await
               })
           }
         ),
-        "def".to_string() => NativeFunction(
+        "def".to_string() => SpecialForm(
           |env, args| {
             Box::pin(async move {
               let (name, value) = (args[0].clone(), args[1].clone());
@@ -147,7 +332,7 @@ pub fn environment() -> Environment {
             })
           }
         ),
-        "fn".to_string() => NativeFunction(
+        "fn".to_string() => SpecialForm(
             |env, args| {
               Box::pin(async move {
                 let f = function(env.clone(), args)?;
@@ -155,7 +340,7 @@ pub fn environment() -> Environment {
               })
             }
         ),
-        "defn".to_string() => NativeFunction(
+        "defn".to_string() => SpecialForm(
           |env, args| {
               Box::pin(async move {
                 let (name, args) = args.split_at(1);
@@ -174,6 +359,7 @@ pub fn environment() -> Environment {
           |env, args| {
               Box::pin(async move {
                 let (env, arg) = compiler::evaluate(env, args[0].clone()).await?;
+                let (arg, _) = compiler::optimize(&env, arg);
                 compiler::evaluate(env, arg).await
               })
           }
@@ -183,7 +369,7 @@ pub fn environment() -> Environment {
               Box::pin(async move {
                   let (env, arg) = compiler::evaluate(env, args[0].clone()).await?;
                   let s = extract::string(arg)?;
-                  let tokens = compiler::tokenize(&s);
+                  let tokens = compiler::tokenize(&s)?;
                   let (tokens, expression) = compiler::parse(&tokens);
                   if tokens.len() > 0 {
                       return Err(error("Could not parse expression"));
@@ -204,7 +390,7 @@ pub fn environment() -> Environment {
               })
           }
         ),
-        "let".to_string() => NativeFunction(
+        "let".to_string() => SpecialForm(
           |env, args| {
               Box::pin(async move {
                 let original_env = env.clone();
@@ -220,7 +406,7 @@ pub fn environment() -> Environment {
               })
           }
         ),
-        "for".to_string() => NativeFunction(
+        "for".to_string() => SpecialForm(
           |env, args| {
               Box::pin(async move {
                 let (bindings, body) = args.split_at(1);
@@ -228,28 +414,75 @@ pub fn environment() -> Environment {
                 let pattern = bindings[0].clone();
                 let (env, values) = compiler::evaluate(env, bindings[1].clone()).await?;
                 let values = extract::array(values)?;
-                let futures: Vec<_> = values
-                    .iter()
+                let mut results = vector![];
+                for value in values {
+                    let env = pattern_match(env.clone(), pattern.clone(), value)?;
+                    let (_, value) = compiler::evaluate_expressions(env, body.clone()).await?;
+                    results.push_back(value.last().unwrap_or(&Expression::Nil).clone());
+                }
+                Ok((env, Expression::Array(results)))
+              })
+          }
+        ),
+        "pfor".to_string() => SpecialForm(
+          |env, args| {
+              Box::pin(async move {
+                let (bindings, body) = args.split_at(1);
+                let bindings = extract::array(bindings[0].clone())?;
+                let pattern = bindings[0].clone();
+                let (env, values) = compiler::evaluate(env, bindings[1].clone()).await?;
+                let values = extract::array(values)?;
+                let limit = match (bindings.len(), bindings.get(2)) {
+                    (2, _) => None,
+                    (4, Some(Expression::Keyword(k))) if k == ":limit" => {
+                        Some(compiler::evaluate(env.clone(), bindings[3].clone()).await?.1)
+                    }
+                    _ => {
+                        return Err(error("pfor expects [pattern values] or [pattern values :limit n]"))
+                    }
+                };
+                let limit = concurrency(&env, limit.as_ref())?;
+                let bodies = values
+                    .into_iter()
                     .map(|value| {
                         let body = body.clone();
                         let pattern = pattern.clone();
-                        let env = env.clone();
+                        let env = env.without(stm::TRANSACTION);
                         async move {
-                            let env = pattern_match(env, pattern, value.clone())?;
-                            let (_, value) = compiler::evaluate_expressions(env.clone(), body).await?;
-                            Ok(value.last().unwrap_or(&Expression::Nil).clone())
+                            let env = pattern_match(env, pattern, value)?;
+                            let (_, value) = compiler::evaluate_expressions(env, body).await?;
+                            Ok::<_, Effect>(value.last().unwrap_or(&Expression::Nil).clone())
                         }
                     })
                     .collect();
-                let results = futures::future::join_all(futures).await;
-                let mut values = vector![];
-                for result in results {
-                    match result {
-                        Ok(v) => values.push_back(v),
-                        Err(e) => return Err(e),
-                    }
-                }
-                Ok((env, Expression::Array(values)))
+                let results = task::parallel(bodies, limit).await?;
+                Ok((env, Expression::Array(results)))
+              })
+          }
+        ),
+        "pmap".to_string() => NativeFunction(
+          |env, args| {
+              Box::pin(async move {
+                let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                let f = args[0].clone();
+                let values = extract::array(args[1].clone())?;
+                let limit = concurrency(&env, args.get(2))?;
+                let calls = values
+                    .into_iter()
+                    .map(|value| {
+                        let env = env.without(stm::TRANSACTION);
+                        let call = Expression::Call(Call {
+                            function: Box::new(f.clone()),
+                            arguments: vector![Expression::Quote(Box::new(value))],
+                        });
+                        async move {
+                            let (_, value) = compiler::evaluate(env, call).await?;
+                            Ok::<_, Effect>(value)
+                        }
+                    })
+                    .collect();
+                let results = task::parallel(calls, limit).await?;
+                Ok((env, Expression::Array(results)))
               })
           }
         ),
@@ -260,6 +493,8 @@ pub fn environment() -> Environment {
             let result = args.iter().fold(String::new(), |mut result, arg| {
                 if let Expression::String(s) = arg {
                     result.push_str(&s);
+                } else if let Expression::Decimal(d) = arg {
+                    result.push_str(&d.to_string());
                 } else {
                     result.push_str(&format!("{}", arg));
                 }
@@ -269,7 +504,7 @@ pub fn environment() -> Environment {
               })
           }
         ),
-        "bound?".to_string() => NativeFunction(
+        "bound?".to_string() => SpecialForm(
             |env, args| {
               Box::pin(async move {
                 let name = extract::symbol(args[0].clone())?;
@@ -278,7 +513,7 @@ pub fn environment() -> Environment {
               })
             }
         ),
-        "do".to_string() => NativeFunction(
+        "do".to_string() => SpecialForm(
             |env, args| {
               Box::pin(async move {
                 let (env, args) = evaluate_expressions(env, args).await?;
@@ -286,7 +521,7 @@ pub fn environment() -> Environment {
               })
             }
         ),
-        "->".to_string() => NativeFunction(
+        "->".to_string() => SpecialForm(
             |env, args| {
               Box::pin(async move {
                 let (initial, args) = args.split_at(1);
@@ -318,7 +553,7 @@ pub fn environment() -> Environment {
               })
             }
         ),
-        "when".to_string() => NativeFunction(
+        "when".to_string() => SpecialForm(
             |env, args| {
               Box::pin(async move {
                 let (condition, body) = args.split_at(1);
@@ -334,24 +569,45 @@ pub fn environment() -> Environment {
               })
             }
         ),
-        "import".to_string() => NativeFunction(
+        "import".to_string() => SpecialForm(
             |env, args| {
               Box::pin(async move {
                 let name = extract::symbol(args[0].clone())?;
-                let path = format!("{}.yeti", name);
-                let (mut env, source) = compiler::evaluate(env, Expression::Call(Call{
-                    function: Box::new(Expression::NamespacedSymbol(vec![
-                        "io".to_string(),
-                        "read-file".to_string()
-                    ])),
-                    arguments: vector![Expression::String(path)],
-                })).await?;
+                let bundled = match env.get("*modules*") {
+                    Some(Expression::Map(modules)) => modules.get(&Expression::String(name.clone())).cloned(),
+                    _ => None,
+                };
+                let (mut env, source) = match bundled {
+                    Some(source) => (env, source),
+                    None => {
+                        let path = format!("{}.yeti", name);
+                        compiler::evaluate(env, Expression::Call(Call{
+                            function: Box::new(Expression::NamespacedSymbol(vec![
+                                "io".to_string(),
+                                "read-file".to_string()
+                            ])),
+                            arguments: vector![Expression::String(path)],
+                        })).await?
+                    }
+                };
                 let source = extract::string(source)?;
-                let tokens = compiler::tokenize(&source);
+                let tokens = compiler::tokenize(&source)?;
                 let expressions = compiler::parse_all(&tokens);
                 let mut module = environment();
                 module.insert("*name*".to_string(), Expression::String(name.clone()));
-                module.insert("io".to_string(), env.get("io").unwrap().clone());
+                if let Some(io) = env.get("io") {
+                    module.insert("io".to_string(), io.clone());
+                }
+                if let Some(modules) = env.get("*modules*") {
+                    module.insert("*modules*".to_string(), modules.clone());
+                }
+                if let Some(sandbox) = env.get(compiler::sandbox::KEY) {
+                    module = module
+                        .into_iter()
+                        .filter(|(key, _)| env.contains_key(key) || key.starts_with('*'))
+                        .collect();
+                    module.insert(compiler::sandbox::KEY.to_string(), sandbox.clone());
+                }
                 for expression in expressions.iter() {
                     let (env, _) = compiler::evaluate(module, expression.clone()).await?;
                     module = env;
@@ -379,30 +635,16 @@ pub fn environment() -> Environment {
                 })
             }
         ),
-        "chan".to_string() => NativeFunction(
-            |env, args| {
-                Box::pin(async move {
-                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
-                    if args.len() == 0 {
-                        Ok((env, Expression::Channel(compiler::channel::Channel::new(1))))
-                    } else {
-                        let buffer_size = extract::integer(args[0].clone())?;
-                        let buffer_size = buffer_size.to_usize().ok_or(error("Expected positive integer"))?;
-                        Ok((env, Expression::Channel(compiler::channel::Channel::new(buffer_size))))
-                    }
-                })
-            }
-        ),
+        "chan".to_string() => NativeFunction(|env, args| Box::pin(channel::chan(env, args))),
        "put!".to_string() => NativeFunction(
             |env, args| {
                 Box::pin(async move {
                     let (env, args) = compiler::evaluate_expressions(env, args).await?;
                     let chan = extract::channel(args[0].clone())?;
                     let value = args[1].clone();
-                    if Expression::Nil == value {
-                        chan.sender.close();
-                    } else {
-                        chan.sender.send(value).await.map_err(|_| error("Channel closed"))?;
+                    channel::sendable(&value)?;
+                    if !channel::put(&chan, value).await {
+                        return Err(error("Channel closed"));
                     }
                     Ok((env, Expression::Nil))
                 })
@@ -413,7 +655,7 @@ pub fn environment() -> Environment {
                 Box::pin(async move {
                     let (env, args) = compiler::evaluate_expressions(env, args).await?;
                     let chan = extract::channel(args[0].clone())?;
-                    let value = compiler::channel::take(chan).await;
+                    let value = channel::take(chan).await.unwrap_or_else(channel::closed);
                     Ok((env, value))
                 })
             }
@@ -443,10 +685,11 @@ pub fn environment() -> Environment {
                 Box::pin(async move {
                     let (env, args) = compiler::evaluate_expressions(env, args).await?;
                     let atom = extract::atom(args[0].clone())?;
-                    let value_to_swap = args[1].clone();
-                    let mut value = atom.0.lock().await;
-                    *value = value_to_swap;
-                    Ok((env, Expression::Nil))
+                    let new_value = args[1].clone();
+                    atom.validate(&env, &new_value).await?;
+                    let old_value = core::mem::replace(&mut *atom.0.lock().await, new_value.clone());
+                    atom.notify(&env, &old_value, &new_value).await?;
+                    Ok((env, new_value))
                 })
             }
         ),
@@ -456,22 +699,98 @@ pub fn environment() -> Environment {
                     let (env, args) = compiler::evaluate_expressions(env, args).await?;
                     let atom = extract::atom(args[0].clone())?;
                     let f = args[1].clone();
-                    let mut value = atom.0.lock().await;
-                    let (env, new_value) = compiler::evaluate(env, Expression::Call(Call{
-                        function: Box::new(f),
-                        arguments: vector![value.clone()],
-                    })).await?;
-                    *value = new_value;
+                    loop {
+                        let value = atom.0.lock().await.clone();
+                        let mut arguments: Vector<Expression> = args
+                            .skip(2)
+                            .into_iter()
+                            .map(|argument| Expression::Quote(Box::new(argument)))
+                            .collect();
+                        arguments.push_front(Expression::Quote(Box::new(value.clone())));
+                        let (_, new_value) = compiler::evaluate(env.clone(), Expression::Call(Call{
+                            function: Box::new(f.clone()),
+                            arguments,
+                        })).await?;
+                        atom.validate(&env, &new_value).await?;
+                        if atom.compare_and_set(&value, new_value.clone()).await {
+                            atom.notify(&env, &value, &new_value).await?;
+                            return Ok((env, new_value));
+                        }
+                    }
+                })
+            }
+        ),
+        "add-watch".to_string() => NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    let atom = extract::atom(args[0].clone())?;
+                    atom.1.lock().await.watches.insert(args[1].clone(), args[2].clone());
+                    Ok((env, args[0].clone()))
+                })
+            }
+        ),
+        "remove-watch".to_string() => NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    let atom = extract::atom(args[0].clone())?;
+                    atom.1.lock().await.watches.remove(&args[1]);
+                    Ok((env, args[0].clone()))
+                })
+            }
+        ),
+        "set-validator!".to_string() => NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    let atom = extract::atom(args[0].clone())?;
+                    let validator = match args[1].clone() {
+                        Expression::Nil => None,
+                        validator => Some(validator),
+                    };
+                    let previous = core::mem::replace(&mut atom.1.lock().await.validator, validator);
+                    let value = atom.0.lock().await.clone();
+                    if let Err(effect) = atom.validate(&env, &value).await {
+                        atom.1.lock().await.validator = previous;
+                        return Err(effect);
+                    }
                     Ok((env, Expression::Nil))
                 })
             }
         ),
+        "compare-and-set!".to_string() => NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    let atom = extract::atom(args[0].clone())?;
+                    let (old_value, new_value) = (&args[1], &args[2]);
+                    atom.validate(&env, new_value).await?;
+                    let swapped = atom.compare_and_set(old_value, new_value.clone()).await;
+                    if swapped {
+                        atom.notify(&env, old_value, new_value).await?;
+                    }
+                    Ok((env, Expression::Bool(swapped)))
+                })
+            }
+        ),
+        "agent".to_string() => NativeFunction(|env, args| Box::pin(agent::agent(env, args))),
+        "send".to_string() => NativeFunction(|env, args| Box::pin(agent::send(env, args))),
+        "agent-error".to_string() => NativeFunction(|env, args| Box::pin(agent::agent_error(env, args))),
+        "restart-agent".to_string() => NativeFunction(|env, args| Box::pin(agent::restart_agent(env, args))),
+        "ref".to_string() => NativeFunction(|env, args| Box::pin(stm::reference(env, args))),
+        "dosync".to_string() => SpecialForm(|env, args| Box::pin(stm::dosync(env, args))),
+        "alter".to_string() => NativeFunction(|env, args| Box::pin(stm::alter(env, args))),
+        "ref-set".to_string() => NativeFunction(|env, args| Box::pin(stm::ref_set(env, args))),
+        "commute".to_string() => NativeFunction(|env, args| Box::pin(stm::commute(env, args))),
+        "ensure".to_string() => NativeFunction(|env, args| Box::pin(stm::ensure(env, args))),
         "range".to_string() => NativeFunction(
             |env, args| {
                 Box::pin(async move {
                     let (env, args) = compiler::evaluate_expressions(env, args).await?;
                     let mut start = rug::Integer::from(0);
                     let stop = extract::integer(args[0].clone())?;
+                    compiler::sandbox::allocate(&env, stop.to_usize().unwrap_or(if stop > 0 { usize::MAX } else { 0 })).await?;
                     let step = 1;
                     let mut range = vector![];
                     while start < stop {
@@ -482,23 +801,147 @@ pub fn environment() -> Environment {
                 })
             }
         ),
-        "spawn".to_string() => NativeFunction(
+        "spawn".to_string() => SpecialForm(
             |env, args| {
                 Box::pin(async move {
                     let env_cloned = env.clone();
-                    tokio::spawn(async move {
-                        let _ = compiler::evaluate_expressions(env, args).await?;
-                        Ok::<(), Effect>(())
+                    let env = env.without(stm::TRANSACTION);
+                    let task = Task::spawn(async move {
+                        let (_, values) = compiler::evaluate_expressions(env, args).await?;
+                        Ok(values.last().cloned().unwrap_or(Expression::Nil))
                     });
-                    Ok((env_cloned, Expression::Nil))
+                    task::track(&env_cloned, &task).await;
+                    Ok((env_cloned, Expression::Task(task)))
+                })
+            }
+        ),
+        "spawn-blocking".to_string() => SpecialForm(
+            |env, args| {
+                Box::pin(async move {
+                    let env_cloned = env.clone();
+                    let env = env.without(stm::TRANSACTION);
+                    let task = Task::spawn_blocking(async move {
+                        let (_, values) = compiler::evaluate_expressions(env, args).await?;
+                        Ok(values.last().cloned().unwrap_or(Expression::Nil))
+                    });
+                    task::track(&env_cloned, &task).await;
+                    Ok((env_cloned, Expression::Task(task)))
+                })
+            }
+        ),
+        "with-tasks".to_string() => SpecialForm(|env, args| Box::pin(task::with_tasks(env, args))),
+        "cancel!".to_string() => NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    let task = extract::task(args[0].clone())?;
+                    task.cancel();
+                    Ok((env, Expression::Nil))
+                })
+            }
+        ),
+        "await".to_string() => NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    if let Expression::Agent(_) = args[0] {
+                        for agent in args {
+                            extract::agent(agent)?.wait().await;
+                        }
+                        return Ok((env, Expression::Nil));
+                    }
+                    let task = extract::task(args[0].clone())?;
+                    match task.wait().await {
+                        Outcome::Value(value) => Ok((env, value)),
+                        Outcome::Failed(effect) => Err(effect),
+                        Outcome::Cancelled => Err(error("Task cancelled")),
+                    }
+                })
+            }
+        ),
+        "done?".to_string() => NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    let task = extract::task(args[0].clone())?;
+                    Ok((env, Expression::Bool(task.poll().is_some())))
+                })
+            }
+        ),
+        "result".to_string() => NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    let task = extract::task(args[0].clone())?;
+                    let result = match task.poll() {
+                        None => Expression::Nil,
+                        Some(Outcome::Value(value)) => Expression::Map(ordmap! {
+                            Expression::Keyword(":status".to_string()) => Expression::Keyword(":ok".to_string()),
+                            Expression::Keyword(":value".to_string()) => value
+                        }),
+                        Some(Outcome::Failed(effect)) => Expression::Map(ordmap! {
+                            Expression::Keyword(":status".to_string()) => Expression::Keyword(":error".to_string()),
+                            Expression::Keyword(":error".to_string()) => Expression::String(format!("{}", effect))
+                        }),
+                        Some(Outcome::Cancelled) => Expression::Map(ordmap! {
+                            Expression::Keyword(":status".to_string()) => Expression::Keyword(":cancelled".to_string())
+                        }),
+                    };
+                    Ok((env, result))
+                })
+            }
+        ),
+        "timeout".to_string() => SpecialForm(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, ms) = compiler::evaluate(env, args[0].clone()).await?;
+                    let ms = extract::integer(ms)?;
+                    let ms = ms.to_u64().ok_or(error("Expected positive integer"))?;
+                    let duration = core::time::Duration::from_millis(ms);
+                    if args.len() == 1 {
+                        return Ok((env, Expression::Channel(channel::timeout(duration))));
+                    }
+                    let body = Expression::Call(Call {
+                        function: Box::new(Expression::Symbol("do".to_string())),
+                        arguments: args.skip(1),
+                    });
+                    let (scoped, tasks) = task::tracked(env.clone());
+                    match tokio::time::timeout(duration, compiler::evaluate(scoped, body)).await {
+                        Ok(result) => result.map(|(_, value)| (env, value)),
+                        Err(_) => {
+                            task::cancel_tracked(&tasks).await;
+                            Err(error(&format!("Timed out after {}ms", ms)))
+                        }
+                    }
                 })
             }
         ),
+        "sliding-buffer".to_string() => NativeFunction(|env, args| Box::pin(channel::sliding_buffer(env, args))),
+        "dropping-buffer".to_string() => NativeFunction(|env, args| Box::pin(channel::dropping_buffer(env, args))),
+        "offer!".to_string() => NativeFunction(|env, args| Box::pin(channel::offer(env, args))),
+        "poll!".to_string() => NativeFunction(|env, args| Box::pin(channel::poll(env, args))),
+        "alts!".to_string() => NativeFunction(|env, args| Box::pin(channel::alts(env, args))),
+        "pipe".to_string() => NativeFunction(|env, args| Box::pin(channel::pipe(env, args))),
+        "merge-channels".to_string() => NativeFunction(|env, args| Box::pin(channel::merge(env, args))),
+        "mult".to_string() => NativeFunction(|env, args| Box::pin(channel::mult(env, args))),
+        "tap".to_string() => NativeFunction(|env, args| Box::pin(channel::tap(env, args))),
+        "untap".to_string() => NativeFunction(|env, args| Box::pin(channel::untap(env, args))),
+        "pub".to_string() => NativeFunction(|env, args| Box::pin(channel::publish(env, args))),
+        "sub".to_string() => NativeFunction(|env, args| Box::pin(channel::sub(env, args))),
+        "unsub".to_string() => NativeFunction(|env, args| Box::pin(channel::unsub(env, args))),
         "assoc".to_string() => NativeFunction(|env, args| Box::pin(map::assoc(env, args))),
         "dissoc".to_string() => NativeFunction(|env, args| Box::pin(map::dissoc(env, args))),
         "merge".to_string() => NativeFunction(|env, args| Box::pin(map::merge(env, args))),
         "get".to_string() => NativeFunction(|env, args| Box::pin(map::get(env, args))),
         "nth".to_string() => NativeFunction(|env, args| Box::pin(array::nth(env, args))),
-        "count".to_string() => NativeFunction(|env, args| Box::pin(array::count(env, args)))
+        "count".to_string() => NativeFunction(|env, args| Box::pin(array::count(env, args))),
+        "bytes".to_string() => NativeFunction(|env, args| Box::pin(binary::bytes(env, args))),
+        "bytes->string".to_string() => NativeFunction(|env, args| Box::pin(binary::bytes_to_string(env, args))),
+        "hex-encode".to_string() => NativeFunction(|env, args| Box::pin(binary::hex_encode(env, args))),
+        "hex-decode".to_string() => NativeFunction(|env, args| Box::pin(binary::hex_decode(env, args))),
+        "base64-encode".to_string() => NativeFunction(|env, args| Box::pin(binary::base64_encode(env, args))),
+        "base64-decode".to_string() => NativeFunction(|env, args| Box::pin(binary::base64_decode(env, args))),
+        "slice".to_string() => NativeFunction(|env, args| Box::pin(binary::slice(env, args))),
+        "concat".to_string() => NativeFunction(|env, args| Box::pin(binary::concat(env, args)))
     }
 }
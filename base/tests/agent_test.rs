@@ -0,0 +1,82 @@
+use compiler::Expression;
+use rug::Integer;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+#[tokio::test]
+async fn send_and_await_agent() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (agent 0))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(send a + 5)").await?;
+    assert!(matches!(actual, Expression::Agent(_)));
+    let (env, _) = compiler::evaluate_source(env, "(send a * 2) (await a)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "@a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(10)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn agent_actions_run_in_order() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (def log (agent ""))
+        (send log str "a")
+        (send log str "b")
+        (send log str "c")
+        (await log)
+        "#,
+    )
+    .await?;
+    let (_, actual) = compiler::evaluate_source(env, "@log").await?;
+    assert_eq!(actual, Expression::String("abc".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_agent_reports_error_until_restarted() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (agent 1))").await?;
+    let (env, _) =
+        compiler::evaluate_source(env, "(send a (fn [_] (assert false))) (await a)").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(agent-error a)").await?;
+    assert!(matches!(actual, Expression::String(_)));
+    assert!(compiler::evaluate_source(env.clone(), "(send a inc)")
+        .await
+        .is_err());
+    let (env, _) = compiler::evaluate_source(env, "(restart-agent a 10)").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(agent-error a)").await?;
+    assert_eq!(actual, Expression::Nil);
+    let (env, _) = compiler::evaluate_source(env, "(send a inc) (await a)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "@a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(11)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn sends_in_a_transaction_wait_for_the_commit() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (agent 0))").await?;
+    let (env, actual) =
+        compiler::evaluate_source(env, "(dosync (send a + 1) (await a) @a)").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(0)));
+    let (env, actual) = compiler::evaluate_source(env, "(await a) @a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(1)));
+    let result =
+        compiler::evaluate_source(env.clone(), "(dosync (send a + 1) (assert false))").await;
+    assert!(result.is_err());
+    let (_, actual) = compiler::evaluate_source(env, "(await a) @a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(1)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn agent_actions_run_outside_the_transaction() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (agent 0)) (def r (ref 0))").await?;
+    let source = "(dosync (send a (fn [_] (alter r inc)))) (await a) (agent-error a)";
+    let (_, actual) = compiler::evaluate_source(env, source).await?;
+    assert!(matches!(actual, Expression::String(_)));
+    Ok(())
+}
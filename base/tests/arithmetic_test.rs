@@ -1,7 +1,5 @@
-use base;
-use compiler;
+use im::{ordmap, vector};
 use rug::{Integer, Rational};
-use im::{vector, ordmap};
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
@@ -92,3 +90,88 @@ async fn evaluate_equality_of_floats() -> Result {
     Ok(())
 }
 
+#[tokio::test]
+async fn add_ratio_and_integer() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(env, "(+ (/ 1 2) 1)").await?;
+    let expected =
+        compiler::Expression::Ratio(Rational::from((Integer::from(3), Integer::from(2))));
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn multiply_integer_and_float() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(env, "(* 2 1.5)").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "3.0").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_precision_controls_float_bits() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, "(with-precision 100 (/ 1.0 3))").await?;
+    let compiler::Expression::Float(third) = actual else {
+        panic!("Expected float");
+    };
+    assert_eq!(third.as_float().prec(), 100);
+    let (_, actual) = compiler::evaluate_source(env, "(let [*precision* 64] (float 1/3))").await?;
+    let compiler::Expression::Float(third) = actual else {
+        panic!("Expected float");
+    };
+    assert_eq!(third.as_float().prec(), 64);
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_precision_reaches_called_functions() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(defn third [] (/ 1.0 3))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(with-precision 200 (third))").await?;
+    let compiler::Expression::Float(third) = actual else {
+        panic!("Expected float");
+    };
+    assert_eq!(third.as_float().prec(), 200);
+    let (_, actual) = compiler::evaluate_source(env, "(third)").await?;
+    let compiler::Expression::Float(third) = actual else {
+        panic!("Expected float");
+    };
+    assert_eq!(third.as_float().prec(), compiler::DEFAULT_PRECISION);
+    Ok(())
+}
+
+#[tokio::test]
+async fn numbers_and_concurrency_reach_called_functions() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(defn modes [] [*numbers* *concurrency*])").await?;
+    let (env, actual) =
+        compiler::evaluate_source(env, "(let [*numbers* :string *concurrency* 2] (modes))").await?;
+    let (_, expected) = compiler::evaluate_source(env, "[:string 2]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn numeric_functions_check_their_arity() -> Result {
+    let env = base::environment();
+    for source in [
+        "(min)",
+        "(max)",
+        "(abs)",
+        "(round)",
+        "(floor 1 2)",
+        "(quot 1)",
+        "(mod)",
+    ] {
+        let result = compiler::evaluate_source(env.clone(), source).await;
+        assert!(
+            matches!(result, Err(compiler::effect::Effect::Error(_))),
+            "{}",
+            source
+        );
+    }
+    Ok(())
+}
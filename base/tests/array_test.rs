@@ -1,5 +1,3 @@
-use compiler;
-use base;
 use rug::Integer;
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
@@ -1,5 +1,3 @@
-use base;
-use compiler;
 use rug::Integer;
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
@@ -41,3 +39,108 @@ async fn swap_an_atom() -> Result {
     assert_eq!(previous, expected);
     Ok(())
 }
+
+#[tokio::test]
+async fn reset_and_swap_return_new_value() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def x (atom 5))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(reset! x 10)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(10)));
+    let (_, actual) = compiler::evaluate_source(env, "(swap! x inc)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(11)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_an_atom() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (def x (atom 5))
+        (def changes (atom []))
+        (add-watch x :log (fn [key a old new] (reset! changes [key (= a x) old new])))
+        "#,
+    )
+    .await?;
+    let (env, _) = compiler::evaluate_source(env, "(swap! x inc)").await?;
+    let (env, actual) = compiler::evaluate_source(env, "@changes").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[:log true 5 6]").await?;
+    assert_eq!(actual, expected);
+    let (env, _) = compiler::evaluate_source(env, "(remove-watch x :log) (reset! x 0)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "@changes").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn validator_rejects_invalid_update() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def x (atom 5)) (set-validator! x (fn [v] (= v 5)))")
+            .await?;
+    assert!(compiler::evaluate_source(env.clone(), "(reset! x 6)")
+        .await
+        .is_err());
+    assert!(compiler::evaluate_source(env.clone(), "(swap! x inc)")
+        .await
+        .is_err());
+    let (env, actual) = compiler::evaluate_source(env, "@x").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(5)));
+    let (env, _) = compiler::evaluate_source(env, "(set-validator! x nil)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "(reset! x 6)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(6)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn validator_must_accept_current_value() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def x (atom 5))").await?;
+    let result = compiler::evaluate_source(env, "(set-validator! x (fn [v] (= v 1)))").await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejected_validator_keeps_the_previous_one() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def x (atom 5)) (set-validator! x (fn [v] (< v 10)))")
+            .await?;
+    let result =
+        compiler::evaluate_source(env.clone(), "(set-validator! x (fn [v] (= v 1)))").await;
+    assert!(result.is_err());
+    assert!(compiler::evaluate_source(env.clone(), "(reset! x 20)")
+        .await
+        .is_err());
+    let (_, actual) = compiler::evaluate_source(env, "(reset! x 7)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(7)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn compare_and_set_validates_and_notifies() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (def x (atom 5))
+        (def changes (atom []))
+        (set-validator! x (fn [v] (< v 10)))
+        (add-watch x :log (fn [key a old new] (reset! changes [old new])))
+        "#,
+    )
+    .await?;
+    assert!(
+        compiler::evaluate_source(env.clone(), "(compare-and-set! x 5 20)")
+            .await
+            .is_err()
+    );
+    let (env, actual) = compiler::evaluate_source(env, "(compare-and-set! x 5 6)").await?;
+    assert_eq!(actual, compiler::Expression::Bool(true));
+    let (_, actual) = compiler::evaluate_source(env, "@changes").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[5 6]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use compiler::Expression;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+#[tokio::test]
+async fn bytes_literal() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, r#"#x"DEADbeef""#).await?;
+    assert!(matches!(actual, Expression::Bytes(_)));
+    assert_eq!(format!("{}", actual), r#"#x"deadbeef""#);
+    let (env, actual) = compiler::evaluate_source(env, r#"(count #x"deadbeef")"#).await?;
+    assert_eq!(actual, Expression::Integer(4.into()));
+    let (_, actual) = compiler::evaluate_source(env, r#"(nth #x"deadbeef" 1)"#).await?;
+    assert_eq!(actual, Expression::Integer(0xad.into()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn bytes_from_strings_and_arrays() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, r#"(bytes "hé")"#).await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"#x"68c3a9""#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, r#"(bytes "hé" :latin-1)"#).await?;
+    let (env, expected) = compiler::evaluate_source(env, "(bytes [104 233])").await?;
+    assert_eq!(actual, expected);
+    let (env, actual) =
+        compiler::evaluate_source(env, r#"(bytes->string #x"68e9" :latin-1)"#).await?;
+    assert_eq!(actual, Expression::String("hé".to_string()));
+    let (env, actual) =
+        compiler::evaluate_source(env, r#"(bytes->string (bytes "hi" :utf-16le) :utf-16le)"#)
+            .await?;
+    assert_eq!(actual, Expression::String("hi".to_string()));
+    assert!(
+        compiler::evaluate_source(env.clone(), r#"(bytes->string #x"ff")"#)
+            .await
+            .is_err()
+    );
+    assert!(compiler::evaluate_source(env, r#"(bytes "é" :ascii)"#)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn hex_and_base64() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, r#"(hex-encode #x"00ff10")"#).await?;
+    assert_eq!(actual, Expression::String("00ff10".to_string()));
+    let (env, actual) = compiler::evaluate_source(env, r#"(hex-decode "00ff10")"#).await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"#x"00ff10""#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, r#"(base64-encode "hello")"#).await?;
+    assert_eq!(actual, Expression::String("aGVsbG8=".to_string()));
+    let (env, actual) =
+        compiler::evaluate_source(env, r#"(bytes->string (base64-decode "aGVsbG8="))"#).await?;
+    assert_eq!(actual, Expression::String("hello".to_string()));
+    assert!(compiler::evaluate_source(env, r#"(hex-decode "abc")"#)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn slice_and_concat() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, r#"(slice #x"0102030405" 1 3)"#).await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"#x"0203""#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, r#"(slice #x"0102030405" 3)"#).await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"#x"0405""#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, r#"(concat #x"01" #x"0203" #x"")"#).await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"#x"010203""#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, "(concat [1] [2 3])").await?;
+    let (env, expected) = compiler::evaluate_source(env, "[1 2 3]").await?;
+    assert_eq!(actual, expected);
+    assert!(compiler::evaluate_source(env, r#"(slice #x"01" 0 2)"#)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn malformed_bytes_literal() -> Result {
+    assert!(compiler::tokenize(r#"#x"0102"#).is_err());
+    assert!(compiler::tokenize(r#"#x"012""#).is_err());
+    assert!(compiler::tokenize(r#"#x"zz""#).is_err());
+    let env = base::environment();
+    let sources = [
+        r##"(read-string "#x\"0102")"##,
+        r##"(read-string "#x\"012\"")"##,
+        r##"(read-string "#x\"zz\"")"##,
+    ];
+    for source in sources {
+        assert!(compiler::evaluate_source(env.clone(), source)
+            .await
+            .is_err());
+    }
+    Ok(())
+}
@@ -1,5 +1,4 @@
-use base;
-use compiler;
+use rug::Integer;
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
@@ -53,14 +52,35 @@ async fn channel_with_specified_buffer_size() -> Result {
 }
 
 #[tokio::test]
-async fn closing_a_channel_using_nil() -> Result {
+async fn put_nil_on_a_channel() -> Result {
     let env = base::environment();
     let (env, _) = compiler::evaluate_source(env, "(def c (chan 3))").await?;
     let (env, _) = compiler::evaluate_source(env, "(put! c nil)").await?;
     let (env, actual) = compiler::evaluate_source(env, "(take! c)").await?;
     assert_eq!(actual, compiler::Expression::Nil);
-    let (_, actual) = compiler::evaluate_source(env, "(take! c)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "(closed? c)").await?;
+    assert_eq!(actual, compiler::Expression::Bool(false));
+    Ok(())
+}
+
+#[tokio::test]
+async fn take_from_a_closed_channel() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def c (chan 3))").await?;
+    let (env, _) = compiler::evaluate_source(env, "(put! c nil)").await?;
+    let (env, _) = compiler::evaluate_source(env, "(close! c)").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(take! c)").await?;
     assert_eq!(actual, compiler::Expression::Nil);
+    let (env, actual) = compiler::evaluate_source(env, "(take! c)").await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::Keyword(":channel/closed".to_string())
+    );
+    let (_, actual) = compiler::evaluate_source(env, "(poll! c)").await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::Keyword(":channel/closed".to_string())
+    );
     Ok(())
 }
 
@@ -76,3 +96,177 @@ async fn closing_a_channel_using_close() -> Result {
     assert_eq!(actual, compiler::Expression::Bool(true));
     Ok(())
 }
+
+#[tokio::test]
+async fn sliding_buffer_keeps_newest_values() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def c (chan (sliding-buffer 2)))").await?;
+    let (env, _) = compiler::evaluate_source(env, "(put! c 1) (put! c 2) (put! c 3)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "[(take! c) (take! c)]").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[2 3]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn dropping_buffer_keeps_oldest_values() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def c (chan (dropping-buffer 2)))").await?;
+    let (env, _) = compiler::evaluate_source(env, "(put! c 1) (put! c 2) (put! c 3)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "[(take! c) (take! c)]").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[1 2]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn offer_and_poll_do_not_block() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def c (chan 1))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(poll! c)").await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::Keyword(":channel/empty".to_string())
+    );
+    let (env, actual) = compiler::evaluate_source(env, "(offer! c 1)").await?;
+    assert_eq!(actual, compiler::Expression::Bool(true));
+    let (env, actual) = compiler::evaluate_source(env, "(offer! c 2)").await?;
+    assert_eq!(actual, compiler::Expression::Bool(false));
+    let (_, actual) = compiler::evaluate_source(env, "(poll! c)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(1)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn alts_takes_from_ready_channel() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def a (chan)) (def b (chan)) (put! b 5)").await?;
+    let (_, actual) =
+        compiler::evaluate_source(env, "(let [[v c] (alts! [a b])] [v (= c b)])").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[5 true]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn alts_with_timeout_channel() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (chan)) (def t (timeout 10))").await?;
+    let (_, actual) =
+        compiler::evaluate_source(env, "(let [[v c] (alts! [a t])] [v (= c t)])").await?;
+    let (_, expected) =
+        compiler::evaluate_source(base::environment(), "[:channel/closed true]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn alts_puts_value() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (chan))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(nth (alts! [[a 7]]) 0)").await?;
+    assert_eq!(actual, compiler::Expression::Bool(true));
+    let (_, actual) = compiler::evaluate_source(env, "(poll! a)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(7)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn pipe_forwards_values_and_closes() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def a (chan 2)) (def b (pipe a (chan 2)))").await?;
+    let (env, _) = compiler::evaluate_source(env, "(put! a 1) (close! a)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "[(take! b) (take! b)]").await?;
+    let (_, expected) =
+        compiler::evaluate_source(base::environment(), "[1 :channel/closed]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn merge_channels() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(
+        env,
+        "(def a (chan)) (def b (chan)) (def m (merge-channels [a b]))",
+    )
+    .await?;
+    let (env, _) = compiler::evaluate_source(env, "(put! a 1) (close! a) (close! b)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "[(take! m) (take! m)]").await?;
+    let (_, expected) =
+        compiler::evaluate_source(base::environment(), "[1 :channel/closed]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn mult_copies_to_every_tap() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def source (chan)) (def m (mult source))").await?;
+    let (env, _) =
+        compiler::evaluate_source(env, "(def a (tap m (chan))) (def b (tap m (chan)))").await?;
+    let (env, _) = compiler::evaluate_source(env, "(put! source 1)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "[(take! a) (take! b)]").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[1 1]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pub_sub_routes_by_topic() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def source (chan)) (def p (pub source :topic))").await?;
+    let (env, _) =
+        compiler::evaluate_source(env, "(def a (sub p :a (chan))) (def b (sub p :b (chan)))")
+            .await?;
+    let (env, _) = compiler::evaluate_source(env, "(put! source {:topic :b :value 2})").await?;
+    let (env, _) = compiler::evaluate_source(env, "(put! source {:topic :a :value 1})").await?;
+    let (_, actual) =
+        compiler::evaluate_source(env, "[(:value (take! a)) (:value (take! b))]").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[1 2]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn channel_markers_cannot_be_sent() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def c (chan 1))").await?;
+    assert!(
+        compiler::evaluate_source(env.clone(), "(put! c :channel/closed)")
+            .await
+            .is_err()
+    );
+    assert!(
+        compiler::evaluate_source(env.clone(), "(offer! c :channel/empty)")
+            .await
+            .is_err()
+    );
+    assert!(
+        compiler::evaluate_source(env.clone(), "(alts! [[c :channel/closed]])")
+            .await
+            .is_err()
+    );
+    let (env, _) = compiler::evaluate_source(env, "(put! c nil)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "[(poll! c) (poll! c)]").await?;
+    let (_, expected) =
+        compiler::evaluate_source(base::environment(), "[nil :channel/empty]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn buffers_expect_a_size() -> Result {
+    let env = base::environment();
+    assert!(compiler::evaluate_source(env.clone(), "(sliding-buffer)")
+        .await
+        .is_err());
+    assert!(compiler::evaluate_source(env, "(dropping-buffer 1 2)")
+        .await
+        .is_err());
+    Ok(())
+}
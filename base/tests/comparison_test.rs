@@ -0,0 +1,89 @@
+use compiler::Expression;
+use rug::Integer;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+#[tokio::test]
+async fn less_than_across_numeric_types() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, "(< 1 3/2 2.5 3)").await?;
+    assert_eq!(actual, Expression::Bool(true));
+    let (_, actual) = compiler::evaluate_source(env, "(< 1 1)").await?;
+    assert_eq!(actual, Expression::Bool(false));
+    Ok(())
+}
+
+#[tokio::test]
+async fn greater_than_or_equal() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, "(>= 3 3 2.0 1/2)").await?;
+    assert_eq!(actual, Expression::Bool(true));
+    let (_, actual) = compiler::evaluate_source(env, "(> 2 3)").await?;
+    assert_eq!(actual, Expression::Bool(false));
+    Ok(())
+}
+
+#[tokio::test]
+async fn compare_non_numbers_is_an_error() -> Result {
+    let env = base::environment();
+    assert!(compiler::evaluate_source(env, r#"(< 1 "two")"#)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn min_and_max() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, "(min 3 1/2 2)").await?;
+    let (env, expected) = compiler::evaluate_source(env, "1/2").await?;
+    assert_eq!(actual, expected);
+    let (_, actual) = compiler::evaluate_source(env, "(max 3 1/2 2)").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn rounding_ratios_and_floats() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        "[(floor -7/2) (ceil -7/2) (round 7/2) (truncate -7/2)]",
+    )
+    .await?;
+    let (env, expected) = compiler::evaluate_source(env, "[-4 -3 4 -3]").await?;
+    assert_eq!(actual, expected);
+    let (env, actual) =
+        compiler::evaluate_source(env, "[(floor 2.5) (ceil 2.5) (round 2.5) (truncate -2.5)]")
+            .await?;
+    let (_, expected) = compiler::evaluate_source(env, "[2 3 3 -2]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn quot_rem_and_mod() -> Result {
+    let env = base::environment();
+    let (env, actual) =
+        compiler::evaluate_source(env, "[(quot -7 2) (rem -7 2) (mod -7 2) (abs -7)]").await?;
+    let (env, expected) = compiler::evaluate_source(env, "[-3 -1 1 7]").await?;
+    assert_eq!(actual, expected);
+    assert!(compiler::evaluate_source(env, "(mod 1 0)").await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn exact_conversions() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, "(rational 0.5)").await?;
+    let (env, expected) = compiler::evaluate_source(env, "1/2").await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, "(integer 4.0)").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(4)));
+    assert!(compiler::evaluate_source(env.clone(), "(integer 1/2)")
+        .await
+        .is_err());
+    let (_, actual) = compiler::evaluate_source(env, "(float 1/4)").await?;
+    assert_eq!(actual, Expression::Float(compiler::Float::from_f64(0.25)));
+    Ok(())
+}
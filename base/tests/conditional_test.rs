@@ -1,5 +1,3 @@
-use base;
-use compiler;
 use rug::Integer;
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
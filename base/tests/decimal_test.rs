@@ -0,0 +1,77 @@
+use compiler::Expression;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+#[tokio::test]
+async fn decimal_literal() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, "12.34M").await?;
+    assert!(matches!(actual, Expression::Decimal(_)));
+    assert_eq!(format!("{}", actual), "12.34M");
+    let (_, actual) = compiler::evaluate_source(env, "(str -0.05M)").await?;
+    assert_eq!(actual, Expression::String("-0.05".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn display_never_uses_exponent() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(
+        env,
+        "(str (* 100000000000000000000M 0.000000000000000000001M))",
+    )
+    .await?;
+    assert_eq!(
+        actual,
+        Expression::String("0.100000000000000000000".to_string())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn decimal_arithmetic_is_exact() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, "(+ 0.1M 0.2M)").await?;
+    let (env, expected) = compiler::evaluate_source(env, "0.3M").await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, "(str (* 19.99M 3))").await?;
+    assert_eq!(actual, Expression::String("59.97".to_string()));
+    let (env, actual) = compiler::evaluate_source(env, "(/ 1M 4)").await?;
+    let (env, expected) = compiler::evaluate_source(env, "0.25M").await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, "(/ 1M 3)").await?;
+    let (_, expected) = compiler::evaluate_source(env, "1/3").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn decimal_rounding_modes() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, "[(str (decimal 2.345M 2 :half-even)) (str (decimal 2.355M 2 :half-even)) (str (decimal 2.345M 2 :half-up)) (str (decimal -2.341M 2 :floor)) (str (decimal -2.349M 2 :down)) (str (decimal 2.341M 2 :up))]").await?;
+    let (_, expected) =
+        compiler::evaluate_source(env, r#"["2.34" "2.36" "2.35" "-2.35" "-2.34" "2.35"]"#).await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn decimal_conversions() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::evaluate_source(env, r#"(decimal "12.50")"#).await?;
+    let (env, expected) = compiler::evaluate_source(env, "12.5M").await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(env, "(decimal 3/8)").await?;
+    let (env, expected) = compiler::evaluate_source(env, "0.375M").await?;
+    assert_eq!(actual, expected);
+    assert!(compiler::evaluate_source(env.clone(), "(decimal 1/3)")
+        .await
+        .is_err());
+    let (env, actual) = compiler::evaluate_source(env, "(str (decimal 1/3 4))").await?;
+    assert_eq!(actual, Expression::String("0.3333".to_string()));
+    let (_, actual) =
+        compiler::evaluate_source(env, "[(< 1.5M 2) (rational 1.5M) (floor 1.5M)]").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[true 3/2 1]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
@@ -1,6 +1,3 @@
-use base;
-use compiler;
-
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
 #[tokio::test]
@@ -1,8 +1,6 @@
-use base;
-use compiler;
 use compiler::expression::Call;
-use rug::Integer;
 use im::vector;
+use rug::Integer;
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
@@ -36,7 +34,6 @@ async fn evaluate_call_fn() -> Result {
     Ok(())
 }
 
-
 #[tokio::test]
 async fn function_definition_and_call() -> Result {
     let env = base::environment();
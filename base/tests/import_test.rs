@@ -1,5 +1,3 @@
-use base;
-use compiler;
 use im::ordmap;
 use rug::Integer;
 
@@ -101,3 +99,22 @@ async fn import_multiple_modules() -> Result {
     assert_eq!(actual, expected);
     Ok(())
 }
+
+#[tokio::test]
+async fn import_module_from_bundled_sources() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "*modules*".to_string(),
+        compiler::Expression::Map(ordmap! {
+            compiler::Expression::String("foo".to_string()) =>
+                compiler::Expression::String("(import bar) (def baz (bar/double 4))".to_string()),
+            compiler::Expression::String("bar".to_string()) =>
+                compiler::Expression::String("(defn double [x] (* x 2))".to_string())
+        }),
+    );
+    let (env, _) = compiler::evaluate_source(env, "(import foo)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "foo/baz").await?;
+    let expected = compiler::Expression::Integer(Integer::from(8));
+    assert_eq!(actual, expected);
+    Ok(())
+}
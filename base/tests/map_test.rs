@@ -1,4 +1,3 @@
-use compiler;
 use im::ordmap;
 use rug::Integer;
 
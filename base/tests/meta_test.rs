@@ -1,5 +1,3 @@
-use base;
-use compiler;
 use compiler::expression::Call;
 use im::vector;
 use rug::Integer;
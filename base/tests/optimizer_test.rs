@@ -0,0 +1,184 @@
+use rug::{Integer, Rational};
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+fn optimize(source: &str) -> (compiler::Expression, Vec<String>) {
+    let env = base::environment();
+    let tokens = compiler::tokenize(source).unwrap();
+    let (_, expression) = compiler::parse(&tokens);
+    compiler::optimize(&env, expression)
+}
+
+fn parse(source: &str) -> compiler::Expression {
+    let tokens = compiler::tokenize(source).unwrap();
+    let (_, expression) = compiler::parse(&tokens);
+    expression
+}
+
+#[tokio::test]
+async fn fold_nested_integer_arithmetic() -> Result {
+    let (actual, changes) = optimize("(+ 1 (* 2 3))");
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(7)));
+    assert_eq!(
+        changes,
+        vec!["folded (* 2 3) into 6", "folded (+ 1 6) into 7"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn fold_ratio_arithmetic() -> Result {
+    let (actual, _) = optimize("(/ 1 3)");
+    let expected = compiler::Expression::Ratio(Rational::from((1, 3)));
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_fold_division_by_zero() -> Result {
+    let (actual, changes) = optimize("(/ 1 0)");
+    assert_eq!(actual, parse("(/ 1 0)"));
+    assert!(changes.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_fold_symbols() -> Result {
+    let (actual, _) = optimize("(+ x (+ 1 2))");
+    assert_eq!(actual, parse("(+ x 3)"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_fold_shadowed_operator() -> Result {
+    let (actual, _) = optimize("(let [+ -] (+ 1 2))");
+    assert_eq!(actual, parse("(let [+ -] (+ 1 2))"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_fold_redefined_operator() -> Result {
+    let (actual, changes) = optimize("(do (def + -) (+ 1 2))");
+    assert_eq!(actual, parse("(do (def + -) (+ 1 2))"));
+    assert!(changes.is_empty());
+    let env = base::environment();
+    let (_, actual) = compiler::vm::evaluate_source(env, "(do (def + -) (+ 1 2))").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(-1)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_fold_operator_bound_by_pfor() -> Result {
+    let (actual, _) = optimize("(pfor [+ [-]] (+ 1 2))");
+    assert_eq!(actual, parse("(pfor [+ [-]] (+ 1 2))"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_fold_operator_bound_by_with_tasks() -> Result {
+    let (actual, _) = optimize("(with-tasks [+ -] (+ 1 2))");
+    assert_eq!(actual, parse("(with-tasks [+ -] (+ 1 2))"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_fold_function_parameter() -> Result {
+    let (actual, _) = optimize("(fn [* x] (* x 2))");
+    assert_eq!(actual, parse("(fn [* x] (* x 2))"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn simplify_if_with_literal_condition() -> Result {
+    let (actual, changes) = optimize("(if true (+ 1 2) (foo 1))");
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(3)));
+    assert_eq!(
+        changes.last().unwrap(),
+        "simplified (if true 3 (foo 1)) into 3"
+    );
+    let (actual, _) = optimize("(if nil (foo) :else)");
+    assert_eq!(actual, compiler::Expression::Keyword(":else".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn simplify_when_with_literal_condition() -> Result {
+    let (actual, _) = optimize("(when false (foo))");
+    assert_eq!(actual, compiler::Expression::Nil);
+    let (actual, _) = optimize("(when true 1 (foo))");
+    assert_eq!(actual, parse("(do (foo))"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn inline_thread_first() -> Result {
+    let (actual, _) = optimize("(-> x (f 1) g)");
+    assert_eq!(actual, parse("(g (f x 1))"));
+    let (actual, _) = optimize("(-> 2 (+ 3) (* 4))");
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(20)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn evaluate_source_runs_optimized_program() -> Result {
+    let env = base::environment();
+    let (_, actual) =
+        compiler::evaluate_source(env, "(defn f [x] (if true (+ x (* 2 3)) (boom))) (f 1)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(7)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn fold_float_arithmetic_at_current_precision() -> Result {
+    let (actual, _) = optimize("(+ 1.5 (* 2 0.25))");
+    assert_eq!(actual, parse("2.0"));
+    let mut env = base::environment();
+    env.insert(
+        "*precision*".to_string(),
+        compiler::Expression::Integer(Integer::from(100)),
+    );
+    let (actual, _) = compiler::optimize(&env, parse("(/ 1.0 3)"));
+    let (one, three) = (parse("1.0"), parse("3"));
+    let expected =
+        compiler::arithmetic_with_precision(compiler::Operation::Divide, one, three, Some(100))?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_fold_floats_whose_precision_depends_on_the_caller() -> Result {
+    let (actual, _) = optimize("(with-precision 200 (/ 1.0 3))");
+    assert_eq!(actual, parse("(with-precision 200 (/ 1.0 3))"));
+    let (actual, _) = optimize("(fn [x] (+ 1.5 (+ 1 2)))");
+    assert_eq!(actual, parse("(fn [x] (+ 1.5 3))"));
+    let (actual, _) = optimize("(let [*precision* 200] (/ 1.0 3))");
+    assert_eq!(actual, parse("(let [*precision* 200] (/ 1.0 3))"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn optimize_within_sandbox_limits() -> Result {
+    let limits = compiler::sandbox::Limits {
+        integer_bits: Some(64),
+        ..Default::default()
+    };
+    let env = compiler::sandbox::limited(base::environment(), limits);
+    let (actual, _) = compiler::optimize(&env, parse("(+ 1 2)"));
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(3)));
+    let (actual, _) = compiler::optimize(&env, parse("(* 1000000000000 1000000000000)"));
+    assert_eq!(actual, parse("(* 1000000000000 1000000000000)"));
+    let result = compiler::evaluate_source(env, "(* 1000000000000 1000000000000)").await;
+    assert!(matches!(result, Err(compiler::effect::Effect::Limit(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_and_sandbox_run_optimized_programs() -> Result {
+    let source = "(if true (+ 1 (* 2 3)) (boom))";
+    let (_, actual) = compiler::vm::evaluate_source(base::environment(), source).await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(7)));
+    let env = compiler::sandbox::limited(base::environment(), Default::default());
+    let (_, actual) = compiler::sandbox::evaluate(env, parse(source)).await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(7)));
+    Ok(())
+}
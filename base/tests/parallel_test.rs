@@ -0,0 +1,124 @@
+use compiler::Expression;
+use rug::Integer;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+#[tokio::test]
+async fn do_evaluates_left_to_right() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (def log (atom ""))
+        (do (swap! log str "a") (swap! log str "b") (swap! log str "c"))
+        "#,
+    )
+    .await?;
+    let (_, actual) = compiler::evaluate_source(env, "@log").await?;
+    assert_eq!(actual, Expression::String("abc".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn arguments_evaluate_left_to_right() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def c (chan 1))").await?;
+    let (_, actual) =
+        compiler::evaluate_source(env, "[(put! c 1) (take! c) (put! c 2) (take! c)]").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[nil 1 nil 2]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pmap_preserves_order() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(env, "(pmap inc [1 2 3] 2)").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[2 3 4]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pmap_propagates_errors() -> Result {
+    let env = base::environment();
+    let result = compiler::evaluate_source(env, "(pmap (fn [x] (assert (= x 1))) [1 2 3])").await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn pfor_with_concurrency_limit() -> Result {
+    let env = base::environment();
+    let (_, actual) =
+        compiler::evaluate_source(env, "(let [*concurrency* 1] (pfor [x [1 2 3]] (* x x)))")
+            .await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[1 4 9]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pfor_runs_bodies_concurrently() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def c (chan 1))").await?;
+    let (_, actual) = compiler::evaluate_source(
+        env,
+        "(pfor [x [:take :put]] (if (= x :take) (take! c) (put! c 5)))",
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[5 nil]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pfor_with_explicit_limit() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(env, "(pfor [x [1 2 3] :limit 1] (* x x))").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[1 4 9]").await?;
+    assert_eq!(actual, expected);
+    assert!(
+        compiler::evaluate_source(base::environment(), "(pfor [x [1] :size 1] x)")
+            .await
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn pfor_failure_cancels_remaining_bodies() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def log (atom []))").await?;
+    let result = compiler::evaluate_source(
+        env.clone(),
+        r#"
+        (pfor [x [:fail :slow]]
+          (if (= x :fail)
+            (assert false)
+            (do (take! (timeout 100)) (reset! log [x]))))
+        "#,
+    )
+    .await;
+    assert!(result.is_err());
+    let (_, actual) = compiler::evaluate_source(env, "(take! (timeout 200)) @log").await?;
+    assert_eq!(actual, Expression::Array(im::Vector::new()));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spawn_blocking_returns_task() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(env, "(await (spawn-blocking (+ 1 2)))").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn readme_concurrency_examples() -> Result {
+    let readme = include_str!("../../README.md");
+    let start = readme.find(";; Refs are updated together").unwrap();
+    let end = start + readme[start..].find("```").unwrap();
+    compiler::evaluate_source(base::environment(), &readme[start..end]).await?;
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use compiler::effect::Effect;
+use compiler::sandbox::{limited, restrict, Limits};
+use compiler::Expression;
+use core::time::Duration;
+use im::ordmap;
+use rug::Integer;
+
+type Result = std::result::Result<(), Effect>;
+
+fn is_limit(result: std::result::Result<(compiler::Environment, Expression), Effect>) -> bool {
+    matches!(result, Err(Effect::Limit(_)))
+}
+
+#[tokio::test]
+async fn restricted_environment_only_contains_capabilities() -> Result {
+    let mut env = base::environment();
+    let io = ordmap! {
+        "read-file".to_string() => env.get("str").unwrap().clone(),
+        "write-file".to_string() => env.get("str").unwrap().clone()
+    };
+    env.insert("io".to_string(), Expression::Module(io));
+    let env = restrict(&env, &["+", "io/read-file"]);
+    let (env, actual) = compiler::evaluate_source(env, "(+ 1 2)").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    let (env, actual) = compiler::evaluate_source(env, "(io/read-file \"a\")").await?;
+    assert_eq!(actual, Expression::String("a".to_string()));
+    assert!(
+        compiler::evaluate_source(env.clone(), "(io/write-file \"a\")")
+            .await
+            .is_err()
+    );
+    assert!(compiler::evaluate_source(env, "(str \"a\")").await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn step_limit() -> Result {
+    let limits = Limits {
+        steps: Some(100),
+        ..Limits::default()
+    };
+    let env = limited(base::environment(), limits);
+    let (env, _) =
+        compiler::evaluate_source(env, "(defn f [n] (if (= n 0) 0 (f (- n 1))))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(f 5)").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(0)));
+    assert!(is_limit(compiler::evaluate_source(env, "(f 1000)").await));
+    Ok(())
+}
+
+#[tokio::test]
+async fn time_limit() -> Result {
+    let limits = Limits {
+        time: Some(Duration::from_millis(50)),
+        ..Limits::default()
+    };
+    let env = limited(base::environment(), limits);
+    let result = compiler::sandbox::evaluate_source(env, "(take! (chan))").await;
+    assert!(is_limit(result));
+    Ok(())
+}
+
+#[tokio::test]
+async fn collection_size_limit() -> Result {
+    let limits = Limits {
+        collection_size: Some(10),
+        ..Limits::default()
+    };
+    let env = limited(base::environment(), limits);
+    let (env, _) = compiler::evaluate_source(env, "(range 5)").await?;
+    assert!(is_limit(
+        compiler::evaluate_source(env, "(range 1000000000)").await
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn bytes_count_towards_collection_size_limit() -> Result {
+    let limits = Limits {
+        collection_size: Some(10),
+        ..Limits::default()
+    };
+    let env = limited(base::environment(), limits);
+    let (env, _) = compiler::evaluate_source(env, "(def b (bytes [1 2 3 4]))").await?;
+    let (env, _) = compiler::evaluate_source(env, "(concat b b)").await?;
+    let source = "(defn grow [b] (grow (concat b b))) (grow b)";
+    assert!(is_limit(compiler::evaluate_source(env, source).await));
+    Ok(())
+}
+
+#[tokio::test]
+async fn integer_size_limit() -> Result {
+    let limits = Limits {
+        integer_bits: Some(64),
+        ..Limits::default()
+    };
+    let env = limited(base::environment(), limits);
+    let result = compiler::evaluate_source(env, "(let [x 1000000000000] (* x x))").await;
+    assert!(is_limit(result));
+    Ok(())
+}
+
+#[tokio::test]
+async fn eval_inherits_limits() -> Result {
+    let limits = Limits {
+        collection_size: Some(10),
+        ..Limits::default()
+    };
+    let env = limited(base::environment(), limits);
+    let result = compiler::evaluate_source(env, "(eval (read-string \"(range 100)\"))").await;
+    assert!(is_limit(result));
+    Ok(())
+}
+
+#[tokio::test]
+async fn rebinding_sandbox_does_not_lift_limits() -> Result {
+    let limits = Limits {
+        steps: Some(100),
+        ..Limits::default()
+    };
+    let env = limited(base::environment(), limits);
+    let (env, _) =
+        compiler::evaluate_source(env, "(defn f [n] (if (= n 0) 0 (f (- n 1))))").await?;
+    let result = compiler::evaluate_source(env.clone(), "(let [*sandbox* nil] (f 1000))").await;
+    assert!(is_limit(result));
+    let result = compiler::evaluate_source(env, "(def *sandbox* nil) (f 1000)").await;
+    assert!(is_limit(result));
+    Ok(())
+}
+
+#[tokio::test]
+async fn functions_defined_outside_run_under_limits() -> Result {
+    let limits = Limits {
+        steps: Some(100),
+        ..Limits::default()
+    };
+    let (env, _) = compiler::evaluate_source(
+        base::environment(),
+        "(defn f [n] (if (= n 0) 0 (f (- n 1))))",
+    )
+    .await?;
+    let env = limited(env, limits);
+    assert!(is_limit(compiler::evaluate_source(env, "(f 1000)").await));
+    Ok(())
+}
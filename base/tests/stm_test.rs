@@ -0,0 +1,166 @@
+use compiler::Expression;
+use rug::Integer;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+#[tokio::test]
+async fn deref_ref() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def r (ref 5))").await?;
+    let (_, actual) = compiler::evaluate_source(env, "@r").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(5)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn alter_ref_in_transaction() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (ref 10)) (def b (ref 0))").await?;
+    let (env, actual) =
+        compiler::evaluate_source(env, "(dosync (alter a - 3) (alter b + 3))").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    let (_, actual) = compiler::evaluate_source(env, "[@a @b]").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[7 3]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn alter_outside_transaction_is_an_error() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (ref 10))").await?;
+    assert!(compiler::evaluate_source(env, "(alter a inc)")
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_transaction_does_not_commit() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (ref 10))").await?;
+    let result =
+        compiler::evaluate_source(env.clone(), "(dosync (alter a inc) (assert false))").await;
+    assert!(result.is_err());
+    let (_, actual) = compiler::evaluate_source(env, "@a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(10)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_transfers_keep_total() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (def a (ref 100))
+        (def b (ref 100))
+        (defn transfer [n]
+          (dosync
+            (alter a - n)
+            (alter b + n)))
+        (def tasks (for [i (range 20)] (spawn (transfer 1))))
+        "#,
+    )
+    .await?;
+    let (env, _) = compiler::evaluate_source(env, "(for [t tasks] (await t))").await?;
+    let (_, actual) = compiler::evaluate_source(env, "[@a @b (+ @a @b)]").await?;
+    let (_, expected) = compiler::evaluate_source(base::environment(), "[80 120 200]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn commute_and_ensure() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def counter (ref 0)) (def limit (ref 10))").await?;
+    let (env, actual) =
+        compiler::evaluate_source(env, "(dosync (ensure limit) (commute counter + 1))").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(1)));
+    let (_, actual) = compiler::evaluate_source(env, "@counter").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(1)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn commute_can_deref_refs() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def counter (ref 1)) (def step (ref 10))").await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        "(timeout 1000 (dosync (alter step inc) (commute counter (fn [c] (+ c @counter @step)))))",
+    )
+    .await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(13)));
+    let (_, actual) = compiler::evaluate_source(env, "@counter").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(13)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn spawned_tasks_run_outside_the_transaction() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (ref 1))").await?;
+    let result = compiler::evaluate_source(env, "(dosync (await (spawn (alter a inc))))").await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn parallel_children_run_outside_the_transaction() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (ref 1))").await?;
+    let sources = [
+        "(dosync (with-tasks [t (alter a inc)] (await t)))",
+        "(dosync (pmap (fn [x] (alter a + x)) [1 2]))",
+        "(dosync (pfor [x [1 2]] (alter a + x)))",
+    ];
+    for source in sources {
+        assert!(compiler::evaluate_source(env.clone(), source)
+            .await
+            .is_err());
+    }
+    let (_, actual) = compiler::evaluate_source(env, "@a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(1)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn compare_and_set_atom() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (atom 1))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(compare-and-set! a 2 3)").await?;
+    assert_eq!(actual, Expression::Bool(false));
+    let (env, actual) = compiler::evaluate_source(env, "(compare-and-set! a 1 3)").await?;
+    assert_eq!(actual, Expression::Bool(true));
+    let (_, actual) = compiler::evaluate_source(env, "@a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn swap_with_extra_arguments() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def a (atom 1)) (swap! a + 10 100)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "@a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(111)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn swap_does_not_block_readers() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(
+        env,
+        "(def a (atom 1)) (def c (chan)) (def t (spawn (swap! a (fn [x] (take! c) (inc x)))))",
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(env, "(timeout 1000 @a)").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(1)));
+    let (env, _) = compiler::evaluate_source(env, "(put! c :go) (await t)").await?;
+    let (_, actual) = compiler::evaluate_source(env, "@a").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(2)));
+    Ok(())
+}
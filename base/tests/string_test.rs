@@ -1,5 +1,3 @@
-use compiler;
-
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
 #[tokio::test]
@@ -0,0 +1,208 @@
+use compiler::Expression;
+use im::ordmap;
+use rug::Integer;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+#[tokio::test]
+async fn spawn_returns_task() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(env, "(spawn (+ 1 2))").await?;
+    assert!(matches!(actual, Expression::Task(_)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn await_task() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def t (spawn (+ 1 2)))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(await t)").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    let (_, actual) = compiler::evaluate_source(env, "(done? t)").await?;
+    assert_eq!(actual, Expression::Bool(true));
+    Ok(())
+}
+
+#[tokio::test]
+async fn await_failed_task_returns_error() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def t (spawn (assert false)))").await?;
+    assert!(compiler::evaluate_source(env.clone(), "(await t)")
+        .await
+        .is_err());
+    let (_, actual) = compiler::evaluate_source(env, "(:status (result t))").await?;
+    assert_eq!(actual, Expression::Keyword(":error".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancel_task() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def t (spawn (take! (chan))))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(done? t)").await?;
+    assert_eq!(actual, Expression::Bool(false));
+    let (env, actual) = compiler::evaluate_source(env, "(result t)").await?;
+    assert_eq!(actual, Expression::Nil);
+    let (env, _) = compiler::evaluate_source(env, "(cancel! t)").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(done? t)").await?;
+    assert_eq!(actual, Expression::Bool(true));
+    let (env, actual) = compiler::evaluate_source(env, "(result t)").await?;
+    let expected = Expression::Map(ordmap! {
+        Expression::Keyword(":status".to_string()) => Expression::Keyword(":cancelled".to_string())
+    });
+    assert_eq!(actual, expected);
+    assert!(compiler::evaluate_source(env, "(await t)").await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn timeout_returns_value_when_fast_enough() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(env, "(timeout 1000 (+ 1 2))").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn timeout_aborts_evaluation() -> Result {
+    let env = base::environment();
+    let result = compiler::evaluate_source(env, "(timeout 10 (take! (chan)))").await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn timeout_cancels_tasks_spawned_in_its_body() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def child (atom nil))").await?;
+    let source = "(timeout 10 (reset! child (spawn (take! (chan)))) (take! (chan)))";
+    assert!(compiler::evaluate_source(env.clone(), source)
+        .await
+        .is_err());
+    let (_, actual) = compiler::evaluate_source(env, "(:status (result @child))").await?;
+    assert_eq!(actual, Expression::Keyword(":cancelled".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn timeout_in_bytecode_vm() -> Result {
+    let env = base::environment();
+    let result =
+        compiler::vm::evaluate_source(env, "(let [c (chan)] (timeout 10 (take! c)))").await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_tasks_binds_child_tasks() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::evaluate_source(
+        env,
+        "(with-tasks [a (+ 1 2) b (* 2 3)] (+ (await a) (await b)))",
+    )
+    .await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(9)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_tasks_waits_for_children() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def c (chan))").await?;
+    let (env, _) = compiler::evaluate_source(env, "(spawn (put! c 1))").await?;
+    let (env, actual) = compiler::evaluate_source(env, "(with-tasks [t (take! c)] :done)").await?;
+    assert_eq!(actual, Expression::Keyword(":done".to_string()));
+    let (_, actual) = compiler::evaluate_source(env, "(poll! c)").await?;
+    assert_eq!(actual, Expression::Keyword(":channel/empty".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_tasks_cancels_siblings_on_failure() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::evaluate_source(env, "(def sibling (atom nil))").await?;
+    let result = compiler::evaluate_source(
+        env.clone(),
+        "(with-tasks [a (take! (chan)) b (do (reset! sibling a) (assert false))] (await a))",
+    )
+    .await;
+    assert!(result.is_err());
+    let (env, actual) = compiler::evaluate_source(env, "(:status (result @sibling))").await?;
+    assert_eq!(actual, Expression::Keyword(":cancelled".to_string()));
+    let result =
+        compiler::evaluate_source(env, "(with-tasks [a (take! (chan))] (assert false))").await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_tasks_cancels_what_children_spawn() -> Result {
+    let env = base::environment();
+    let source = "(def inner (with-tasks [a (spawn (take! (chan)))] (await a)))";
+    let (env, _) = compiler::evaluate_source(env, source).await?;
+    let (_, actual) = compiler::evaluate_source(env, "(:status (result inner))").await?;
+    assert_eq!(actual, Expression::Keyword(":cancelled".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_tasks_in_bytecode_vm() -> Result {
+    let env = base::environment();
+    let (_, actual) =
+        compiler::vm::evaluate_source(env, "(with-tasks [a (+ 1 2)] (await a))").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn join_waits_for_cancelled_blocking_task() -> Result {
+    let (running, started) = tokio::sync::oneshot::channel();
+    let task = compiler::task::Task::spawn_blocking(async {
+        let _ = running.send(std::time::Instant::now());
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        Ok(Expression::Nil)
+    });
+    let started = started.await.unwrap();
+    task.cancel();
+    assert_eq!(task.wait().await, compiler::task::Outcome::Cancelled);
+    task.join().await;
+    assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancelled_blocking_task_stops_at_next_wait() -> Result {
+    let env = base::environment();
+    let (env, _) =
+        compiler::evaluate_source(env, "(def t (spawn-blocking (take! (chan))))").await?;
+    let (_, task) = compiler::evaluate_source(env, "(cancel! t) t").await?;
+    let Expression::Task(task) = task else {
+        panic!("expected a task");
+    };
+    let joined = tokio::time::timeout(std::time::Duration::from_secs(5), task.join()).await;
+    assert!(joined.is_ok());
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancel_tracked_tasks() -> Result {
+    let (env, tasks) = compiler::task::tracked(base::environment());
+    let (env, _) =
+        compiler::evaluate_source(env, "(defn background [] (spawn (take! (chan))))").await?;
+    let (env, _) = compiler::evaluate_source(env, "(def t (background))").await?;
+    compiler::task::cancel_tracked(&tasks).await;
+    let (_, actual) = compiler::evaluate_source(env, "(:status (result t))").await?;
+    assert_eq!(actual, Expression::Keyword(":cancelled".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancel_tracked_tasks_spawned_inside_timeout() -> Result {
+    let (env, tasks) = compiler::task::tracked(base::environment());
+    let (env, _) =
+        compiler::evaluate_source(env, "(def t (timeout 1000 (spawn (take! (chan)))))").await?;
+    compiler::task::cancel_tracked(&tasks).await;
+    let (_, actual) = compiler::evaluate_source(env, "(:status (result t))").await?;
+    assert_eq!(actual, Expression::Keyword(":cancelled".to_string()));
+    Ok(())
+}
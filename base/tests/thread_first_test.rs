@@ -1,4 +1,3 @@
-use compiler;
 use rug::Integer;
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
@@ -0,0 +1,157 @@
+use rug::Integer;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+#[tokio::test]
+async fn vm_evaluates_arithmetic() -> Result {
+    let env = base::environment();
+    let (_, actual) = compiler::vm::evaluate_source(env, "(+ 1 (* 2 3))").await?;
+    let expected = compiler::Expression::Integer(Integer::from(7));
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_evaluates_let_with_destructuring() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::vm::evaluate_source(
+        env,
+        "(let [x 2 [a b] [3 4] {:c c} {:c 5} x (+ x 1)] [x a b c])",
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, "[3 3 4 5]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_evaluates_conditionals() -> Result {
+    let env = base::environment();
+    let (env, actual) = compiler::vm::evaluate_source(env, r#"(if nil "yes" "no")"#).await?;
+    assert_eq!(actual, compiler::Expression::String("no".to_string()));
+    let (_, actual) = compiler::vm::evaluate_source(env, r#"(when true 1 2)"#).await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(2)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_recursive_function_with_patterns() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::vm::evaluate_source(
+        env,
+        r#"
+        (defn fib
+          ([0] 0)
+          ([1] 1)
+          ([n] (+ (fib (- n 1)) (fib (- n 2)))))
+        "#,
+    )
+    .await?;
+    let (_, actual) = compiler::vm::evaluate_source(env, "(fib 10)").await?;
+    let expected = compiler::Expression::Integer(Integer::from(55));
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_tail_calls_do_not_grow_the_stack() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::vm::evaluate_source(
+        env,
+        r#"
+        (defn sum
+          ([0 acc] acc)
+          ([n acc] (sum (- n 1) (+ acc n))))
+        "#,
+    )
+    .await?;
+    let (_, actual) = compiler::vm::evaluate_source(env, "(sum 10000 0)").await?;
+    let expected = compiler::Expression::Integer(Integer::from(50005000));
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_closures_capture_lexical_variables() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::vm::evaluate_source(env, "(defn adder [x] (fn [y] (+ x y)))").await?;
+    let (env, actual) = compiler::vm::evaluate_source(env, "((adder 3) 4)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(7)));
+    let (_, actual) = compiler::evaluate_source(env, "((adder 5) 4)").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(9)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_falls_back_to_evaluator_for_special_forms() -> Result {
+    let env = base::environment();
+    let (env, actual) =
+        compiler::vm::evaluate_source(env, "(let [n 2] (for [x [1 2 3]] (* x n)))").await?;
+    let (_, expected) = compiler::evaluate_source(env, "[2 4 6]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_passes_special_forms_their_arguments_unevaluated() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "quoted".to_string(),
+        compiler::Expression::SpecialForm(|env, args| {
+            Box::pin(async move { Ok((env, compiler::Expression::Array(args))) })
+        }),
+    );
+    let (env, actual) = compiler::vm::evaluate_source(env, "(quoted (missing 1) y)").await?;
+    let (env, expected) = compiler::evaluate_source(env, "['(missing 1) 'y]").await?;
+    assert_eq!(actual, expected);
+    let (_, actual) =
+        compiler::vm::evaluate_source(env, "(let [for (fn [a b] (+ a b))] (for 1 2))").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(3)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_closures_work_with_atoms() -> Result {
+    let env = base::environment();
+    let (env, _) = compiler::vm::evaluate_source(env, "(def x (atom 5))").await?;
+    let (env, _) = compiler::vm::evaluate_source(env, "(swap! x (fn [v] (* v 2)))").await?;
+    let (_, actual) = compiler::vm::evaluate_source(env, "@x").await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(10)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_reports_unbound_symbols() -> Result {
+    let env = base::environment();
+    let actual = compiler::vm::evaluate_source(env, "(+ x 1)").await;
+    assert!(matches!(actual, Err(compiler::effect::Effect::Error(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_honours_redefined_special_forms() -> Result {
+    let env = base::environment();
+    let source = "(def if (fn [a b c] c)) (if true 1 2)";
+    let (_, actual) = compiler::vm::evaluate_source(env, source).await?;
+    assert_eq!(actual, compiler::Expression::Integer(Integer::from(2)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_does_not_restore_special_forms_missing_from_the_environment() -> Result {
+    let mut env = base::environment();
+    env.remove("def");
+    let actual = compiler::vm::evaluate_source(env, "(def x 1)").await;
+    assert!(matches!(actual, Err(compiler::effect::Effect::Error(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn vm_and_evaluator_agree_on_if_arity() -> Result {
+    let env = base::environment();
+    let vm = compiler::vm::evaluate_source(env.clone(), "(if true 1)").await;
+    assert!(matches!(vm, Err(compiler::effect::Effect::Error(_))));
+    let evaluator = compiler::evaluate_source(env, "(if true 1)").await;
+    assert!(matches!(evaluator, Err(compiler::effect::Effect::Error(_))));
+    Ok(())
+}
@@ -0,0 +1,214 @@
+extern crate alloc;
+
+use crate::effect::{error, Effect};
+use crate::expression::{Call, Environment};
+use crate::{evaluate_expressions, extract, Expression};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use im::Vector;
+use tokio::sync::{mpsc, watch, Mutex};
+
+type Result<T> = core::result::Result<T, Effect>;
+
+pub(crate) struct Action {
+    env: Environment,
+    function: Expression,
+    arguments: Vector<Expression>,
+}
+
+pub struct Shared {
+    pub value: Mutex<Expression>,
+    pub error: Mutex<Option<Effect>>,
+    completed: watch::Sender<u64>,
+}
+
+impl Shared {
+    async fn run(&self, action: Action) {
+        if self.error.lock().await.is_some() {
+            return;
+        }
+        let value = self.value.lock().await.clone();
+        let mut arguments: Vector<Expression> = action
+            .arguments
+            .into_iter()
+            .map(|argument| Expression::Quote(Box::new(argument)))
+            .collect();
+        arguments.push_front(Expression::Quote(Box::new(value)));
+        let call = Expression::Call(Call {
+            function: Box::new(action.function),
+            arguments,
+        });
+        match crate::evaluate(action.env, call).await {
+            Ok((_, value)) => *self.value.lock().await = value,
+            Err(effect) => *self.error.lock().await = Some(effect),
+        }
+    }
+}
+
+pub struct State {
+    pub shared: Arc<Shared>,
+    sender: mpsc::UnboundedSender<Action>,
+    sent: AtomicU64,
+}
+
+pub struct Agent(pub Arc<State>);
+
+impl Agent {
+    pub fn new(value: Expression) -> Agent {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Action>();
+        let (completed, _) = watch::channel(0);
+        let shared = Arc::new(Shared {
+            value: Mutex::new(value),
+            error: Mutex::new(None),
+            completed,
+        });
+        let worker = shared.clone();
+        tokio::spawn(async move {
+            while let Some(action) = receiver.recv().await {
+                worker.run(action).await;
+                worker.completed.send_modify(|completed| *completed += 1);
+            }
+        });
+        Agent(Arc::new(State {
+            shared,
+            sender,
+            sent: AtomicU64::new(0),
+        }))
+    }
+
+    pub async fn send(
+        &self,
+        env: Environment,
+        function: Expression,
+        arguments: Vector<Expression>,
+    ) -> Result<()> {
+        if let Some(effect) = self.0.shared.error.lock().await.clone() {
+            return Err(error(&format!("Agent has failed: {}", effect)));
+        }
+        let action = Action {
+            env: env.without(crate::stm::TRANSACTION),
+            function,
+            arguments,
+        };
+        match crate::stm::hold(&env, self, action).await {
+            Some(action) => self.dispatch(action),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn dispatch(&self, action: Action) -> Result<()> {
+        self.0.sent.fetch_add(1, Ordering::SeqCst);
+        self.0
+            .sender
+            .send(action)
+            .map_err(|_| error("Agent is not running"))
+    }
+
+    pub async fn wait(&self) {
+        let target = self.0.sent.load(Ordering::SeqCst);
+        let mut completed = self.0.shared.completed.subscribe();
+        while *completed.borrow_and_update() < target {
+            if completed.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    pub async fn restart(&self, value: Expression) -> Result<()> {
+        let mut failure = self.0.shared.error.lock().await;
+        if failure.is_none() {
+            return Err(error("Agent does not need a restart"));
+        }
+        *self.0.shared.value.lock().await = value;
+        *failure = None;
+        Ok(())
+    }
+}
+
+impl PartialEq for Agent {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::hash::Hash for Agent {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+impl core::fmt::Debug for Agent {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#agent({:?})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl core::fmt::Display for Agent {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#agent({:?})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl Eq for Agent {}
+
+impl PartialOrd for Agent {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Agent {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        Arc::as_ptr(&self.0).cmp(&Arc::as_ptr(&other.0))
+    }
+}
+
+impl Clone for Agent {
+    fn clone(&self) -> Self {
+        Agent(Arc::clone(&self.0))
+    }
+}
+
+pub async fn agent(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    Ok((env, Expression::Agent(Agent::new(args[0].clone()))))
+}
+
+pub async fn send(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let agent = extract::agent(args[0].clone())?;
+    agent
+        .send(env.clone(), args[1].clone(), args.skip(2))
+        .await?;
+    Ok((env, args[0].clone()))
+}
+
+pub async fn agent_error(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let agent = extract::agent(args[0].clone())?;
+    let failure = agent.0.shared.error.lock().await.clone();
+    let failure = match failure {
+        Some(effect) => Expression::String(effect.to_string()),
+        None => Expression::Nil,
+    };
+    Ok((env, failure))
+}
+
+pub async fn restart_agent(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let agent = extract::agent(args[0].clone())?;
+    agent.restart(args[1].clone()).await?;
+    Ok((env, args[1].clone()))
+}
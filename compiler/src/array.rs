@@ -11,12 +11,15 @@ type Result<T> = core::result::Result<T, Effect>;
 
 pub async fn nth(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
     let (env, args) = evaluate_expressions(env, args).await?;
-    let arr = extract::array(args[0].clone())?;
     let idx = extract::integer(args[1].clone())?
         .to_usize()
         .ok_or_else(|| error("Index out of range"))?;
-    if let Some(value) = arr.get(idx) {
-        Ok((env, value.clone()))
+    let value = match &args[0] {
+        Expression::Bytes(b) => b.get(idx).map(|byte| Expression::Integer((*byte).into())),
+        _ => extract::array(args[0].clone())?.get(idx).cloned(),
+    };
+    if let Some(value) = value {
+        Ok((env, value))
     } else if args.len() == 3 {
         Ok((env, args[2].clone()))
     } else {
@@ -29,6 +32,9 @@ pub async fn count(
     args: Vector<Expression>,
 ) -> Result<(Environment, Expression)> {
     let (env, args) = evaluate_expressions(env, args).await?;
-    let arr = extract::array(args[0].clone())?;
-    Ok((env, Expression::Integer(arr.len().into())))
+    let len = match &args[0] {
+        Expression::Bytes(b) => b.len(),
+        _ => extract::array(args[0].clone())?.len(),
+    };
+    Ok((env, Expression::Integer(len.into())))
 }
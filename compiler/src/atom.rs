@@ -1,14 +1,82 @@
 extern crate alloc;
 
+use crate::effect::{error, Effect};
+use crate::expression::{Call, Environment};
 use crate::Expression;
+use alloc::boxed::Box;
+use alloc::format;
 use alloc::sync::Arc;
+use im::{vector, OrdMap};
 use tokio::sync::Mutex;
 
-pub struct Atom(pub Arc<Mutex<Expression>>);
+#[derive(Clone, Default)]
+pub struct Observers {
+    pub watches: OrdMap<Expression, Expression>,
+    pub validator: Option<Expression>,
+}
+
+pub struct Atom(pub Arc<Mutex<Expression>>, pub Arc<Mutex<Observers>>);
+
+fn quote(expression: Expression) -> Expression {
+    Expression::Quote(Box::new(expression))
+}
 
 impl Atom {
     pub fn new(expression: Expression) -> Atom {
-        Atom(Arc::new(Mutex::new(expression)))
+        Atom(
+            Arc::new(Mutex::new(expression)),
+            Arc::new(Mutex::new(Observers::default())),
+        )
+    }
+
+    pub async fn validate(&self, env: &Environment, value: &Expression) -> Result<(), Effect> {
+        let validator = self.1.lock().await.validator.clone();
+        let Some(validator) = validator else {
+            return Ok(());
+        };
+        let call = Expression::Call(Call {
+            function: Box::new(validator),
+            arguments: vector![quote(value.clone())],
+        });
+        let (_, valid) = crate::evaluate(env.clone(), call).await?;
+        match valid {
+            Expression::Nil | Expression::Bool(false) => {
+                Err(error(&format!("Validator rejected {}", value)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn notify(
+        &self,
+        env: &Environment,
+        old: &Expression,
+        new: &Expression,
+    ) -> Result<(), Effect> {
+        let watches = self.1.lock().await.watches.clone();
+        for (key, watch) in watches {
+            let call = Expression::Call(Call {
+                function: Box::new(watch),
+                arguments: vector![
+                    quote(key),
+                    Expression::Atom(self.clone()),
+                    quote(old.clone()),
+                    quote(new.clone())
+                ],
+            });
+            crate::evaluate(env.clone(), call).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn compare_and_set(&self, old: &Expression, new: Expression) -> bool {
+        let mut value = self.0.lock().await;
+        if *value == *old {
+            *value = new;
+            true
+        } else {
+            false
+        }
     }
 }
 
@@ -52,6 +120,6 @@ impl Ord for Atom {
 
 impl Clone for Atom {
     fn clone(&self) -> Self {
-        Atom(Arc::clone(&self.0))
+        Atom(Arc::clone(&self.0), Arc::clone(&self.1))
     }
 }
@@ -0,0 +1,227 @@
+extern crate alloc;
+
+use crate::effect::{error, Effect};
+use crate::evaluate_expressions;
+use crate::expression::Environment;
+use crate::extract;
+use crate::Expression;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use im::Vector;
+
+type Result<T> = core::result::Result<T, Effect>;
+
+pub fn encode(text: &str, encoding: &str) -> Result<Bytes> {
+    match encoding {
+        ":utf-8" => Ok(Bytes::copy_from_slice(text.as_bytes())),
+        ":ascii" | ":latin-1" => {
+            let limit = if encoding == ":ascii" { 0x7f } else { 0xff };
+            text.chars()
+                .map(|c| match c as u32 {
+                    code if code <= limit => Ok(code as u8),
+                    _ => Err(error(&format!(
+                        "Cannot encode {:?} as {}",
+                        c,
+                        &encoding[1..]
+                    ))),
+                })
+                .collect::<Result<Vec<u8>>>()
+                .map(Bytes::from)
+        }
+        ":utf-16le" => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        ":utf-16be" => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        _ => Err(error(&format!("Unknown encoding {}", encoding))),
+    }
+}
+
+pub fn decode(bytes: &[u8], encoding: &str) -> Result<String> {
+    let units = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        if bytes.len() % 2 != 0 {
+            return Err(error("Expected an even number of bytes for utf-16"));
+        }
+        let units: Vec<u16> = bytes.chunks(2).map(|c| from([c[0], c[1]])).collect();
+        String::from_utf16(&units).map_err(|_| error("Invalid utf-16"))
+    };
+    match encoding {
+        ":utf-8" => String::from_utf8(bytes.to_vec()).map_err(|_| error("Invalid utf-8")),
+        ":ascii" if !bytes.is_ascii() => Err(error("Invalid ascii")),
+        ":ascii" | ":latin-1" => Ok(bytes.iter().map(|&b| b as char).collect()),
+        ":utf-16le" => units(bytes, u16::from_le_bytes),
+        ":utf-16be" => units(bytes, u16::from_be_bytes),
+        _ => Err(error(&format!("Unknown encoding {}", encoding))),
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Bytes> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = core::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect::<Option<Vec<u8>>>()
+        .map(Bytes::from)
+}
+
+fn encoding(args: &Vector<Expression>, index: usize) -> Result<String> {
+    match args.get(index) {
+        Some(encoding) => extract::keyword(encoding.clone()),
+        None => Ok(String::from(":utf-8")),
+    }
+}
+
+fn octets(expression: &Expression) -> Result<Bytes> {
+    match expression {
+        Expression::Bytes(b) => Ok(b.clone()),
+        Expression::String(s) => Ok(Bytes::copy_from_slice(s.as_bytes())),
+        _ => Err(error(&format!(
+            "Expected bytes or string, got {}",
+            expression
+        ))),
+    }
+}
+
+pub async fn bytes(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let result = match &args[0] {
+        Expression::Bytes(b) => b.clone(),
+        Expression::String(s) => encode(s, &encoding(&args, 1)?)?,
+        Expression::Array(values) => values
+            .iter()
+            .map(|value| {
+                extract::integer(value.clone())?
+                    .to_u8()
+                    .ok_or_else(|| error(&format!("Expected a byte, got {}", value)))
+            })
+            .collect::<Result<Vec<u8>>>()?
+            .into(),
+        other => return Err(error(&format!("Cannot convert {} to bytes", other))),
+    };
+    Ok((env, Expression::Bytes(result)))
+}
+
+pub async fn bytes_to_string(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let bytes = extract::bytes(args[0].clone())?;
+    Ok((
+        env,
+        Expression::String(decode(&bytes, &encoding(&args, 1)?)?),
+    ))
+}
+
+pub async fn hex_encode(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    Ok((env, Expression::String(to_hex(&octets(&args[0])?))))
+}
+
+pub async fn hex_decode(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let hex = extract::string(args[0].clone())?;
+    let bytes = from_hex(&hex).ok_or_else(|| error(&format!("Invalid hex string {:?}", hex)))?;
+    Ok((env, Expression::Bytes(bytes)))
+}
+
+pub async fn base64_encode(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    Ok((env, Expression::String(STANDARD.encode(octets(&args[0])?))))
+}
+
+pub async fn base64_decode(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let text = extract::string(args[0].clone())?;
+    let bytes = STANDARD
+        .decode(text)
+        .map_err(|e| error(&format!("Invalid base64 string: {}", e)))?;
+    Ok((env, Expression::Bytes(bytes.into())))
+}
+
+fn bounds(args: &Vector<Expression>, len: usize) -> Result<(usize, usize)> {
+    let index = |expression: &Expression| {
+        extract::integer(expression.clone())?
+            .to_usize()
+            .filter(|index| *index <= len)
+            .ok_or_else(|| error("Index out of range"))
+    };
+    let start = index(&args[1])?;
+    let end = match args.get(2) {
+        Some(end) => index(end)?,
+        None => len,
+    };
+    if start > end {
+        return Err(error("Index out of range"));
+    }
+    Ok((start, end))
+}
+
+pub async fn slice(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let result = match &args[0] {
+        Expression::Bytes(b) => {
+            let (start, end) = bounds(&args, b.len())?;
+            Expression::Bytes(b.slice(start..end))
+        }
+        Expression::Array(a) => {
+            let (start, end) = bounds(&args, a.len())?;
+            Expression::Array(a.clone().slice(start..end))
+        }
+        other => return Err(error(&format!("Cannot slice {}", other))),
+    };
+    Ok((env, result))
+}
+
+pub async fn concat(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let result = match args.front() {
+        None | Some(Expression::Bytes(_)) => {
+            let bytes = args.iter().try_fold(Vec::new(), |mut result, arg| {
+                result.extend_from_slice(&extract::bytes(arg.clone())?);
+                Ok::<_, Effect>(result)
+            })?;
+            Expression::Bytes(bytes.into())
+        }
+        Some(Expression::Array(_)) => {
+            let array = args.iter().try_fold(Vector::new(), |mut result, arg| {
+                result.append(extract::array(arg.clone())?);
+                Ok::<_, Effect>(result)
+            })?;
+            Expression::Array(array)
+        }
+        Some(other) => return Err(error(&format!("Cannot concat {}", other))),
+    };
+    Ok((env, result))
+}
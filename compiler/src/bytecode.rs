@@ -0,0 +1,579 @@
+extern crate alloc;
+
+use crate::effect::{error, Effect};
+use crate::expression::{Call, Environment};
+use crate::extract;
+use crate::Expression;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use im::{vector, Vector};
+
+type Result<T> = core::result::Result<T, Effect>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Constant(usize),
+    Local(usize),
+    SetLocal(usize),
+    Global(usize),
+    Destructure(usize),
+    Pop,
+    Jump(usize),
+    JumpIfFalse(usize),
+    Array(usize),
+    Map(usize),
+    Deref,
+    Closure(usize),
+    Call(usize),
+    Def(usize),
+    Callee(usize, usize),
+    Return,
+}
+
+#[derive(Debug)]
+pub struct Binding {
+    pub pattern: Expression,
+    pub slots: Vec<(String, usize)>,
+}
+
+#[derive(Debug)]
+pub struct Arm {
+    pub binding: Binding,
+    pub positional: bool,
+    pub entry: usize,
+}
+
+#[derive(Debug)]
+pub struct Fallback {
+    pub expression: Expression,
+    pub locals: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Default)]
+pub struct Prototype {
+    pub instructions: Vec<Instruction>,
+    pub constants: Vec<Expression>,
+    pub prototypes: Vec<Arc<Prototype>>,
+    pub bindings: Vec<Binding>,
+    pub fallbacks: Vec<Fallback>,
+    pub arms: Vec<Arm>,
+    pub captures: Vec<(usize, usize)>,
+    pub self_slot: Option<usize>,
+    pub slots: usize,
+}
+
+pub struct Instance {
+    pub prototype: Arc<Prototype>,
+    pub captured: Vec<Expression>,
+    pub env: Environment,
+}
+
+pub struct Closure(pub Arc<Instance>);
+
+impl Closure {
+    pub fn new(prototype: Arc<Prototype>, captured: Vec<Expression>, env: Environment) -> Closure {
+        Closure(Arc::new(Instance {
+            prototype,
+            captured,
+            env,
+        }))
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::hash::Hash for Closure {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+impl core::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#closure({:?})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl core::fmt::Display for Closure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#closure({:?})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl Eq for Closure {}
+
+impl PartialOrd for Closure {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Closure {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        Arc::as_ptr(&self.0).cmp(&Arc::as_ptr(&other.0))
+    }
+}
+
+impl Clone for Closure {
+    fn clone(&self) -> Self {
+        Closure(Arc::clone(&self.0))
+    }
+}
+
+pub(crate) fn pattern_symbols(pattern: &Expression, names: &mut Vec<String>) {
+    match pattern {
+        Expression::Symbol(name) => {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Expression::Array(patterns) => patterns.iter().for_each(|p| pattern_symbols(p, names)),
+        Expression::Map(patterns) => patterns.values().for_each(|p| pattern_symbols(p, names)),
+        _ => {}
+    }
+}
+
+fn free_symbols(expression: &Expression, names: &mut Vec<String>) {
+    match expression {
+        Expression::Symbol(name) => {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Expression::Array(expressions) => expressions.iter().for_each(|e| free_symbols(e, names)),
+        Expression::Map(map) => map.iter().for_each(|(k, v)| {
+            free_symbols(k, names);
+            free_symbols(v, names);
+        }),
+        Expression::Call(Call {
+            function,
+            arguments,
+        }) => {
+            free_symbols(function, names);
+            arguments.iter().for_each(|e| free_symbols(e, names));
+        }
+        Expression::Deref(e) => free_symbols(e, names),
+        _ => {}
+    }
+}
+
+struct State {
+    prototype: Prototype,
+    scopes: Vec<Vec<(String, usize)>>,
+    self_name: Option<String>,
+    function: bool,
+}
+
+impl State {
+    fn new(function: bool, self_name: Option<String>) -> State {
+        State {
+            prototype: Prototype::default(),
+            scopes: vec![vec![]],
+            self_name,
+            function,
+        }
+    }
+}
+
+struct Compiler {
+    states: Vec<State>,
+    env: Environment,
+    defined: Vec<String>,
+}
+
+impl Compiler {
+    fn state(&mut self) -> &mut State {
+        self.states.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        let instructions = &mut self.state().prototype.instructions;
+        instructions.push(instruction);
+        instructions.len() - 1
+    }
+
+    fn patch(&mut self, at: usize) {
+        let instructions = &mut self.state().prototype.instructions;
+        let target = instructions.len();
+        instructions[at] = match instructions[at] {
+            Instruction::Jump(_) => Instruction::Jump(target),
+            Instruction::JumpIfFalse(_) => Instruction::JumpIfFalse(target),
+            Instruction::Callee(fallback, _) => Instruction::Callee(fallback, target),
+            instruction => instruction,
+        };
+    }
+
+    fn constant(&mut self, expression: Expression) -> usize {
+        let constants = &mut self.state().prototype.constants;
+        constants.push(expression);
+        constants.len() - 1
+    }
+
+    fn nil(&mut self) {
+        let index = self.constant(Expression::Nil);
+        self.emit(Instruction::Constant(index));
+    }
+
+    fn allocate(&mut self, depth: usize) -> usize {
+        let prototype = &mut self.states[depth].prototype;
+        prototype.slots += 1;
+        prototype.slots - 1
+    }
+
+    fn declare(&mut self, name: String) -> usize {
+        let slot = self.allocate(self.states.len() - 1);
+        self.state().scopes.last_mut().unwrap().push((name, slot));
+        slot
+    }
+
+    fn bound(&self, name: &str) -> bool {
+        self.states.iter().any(|state| {
+            state.self_name.as_deref() == Some(name)
+                || state.scopes.iter().flatten().any(|(n, _)| n == name)
+        })
+    }
+
+    fn special(&self, name: &str) -> bool {
+        !self.defined.iter().any(|n| n == name)
+            && matches!(self.env.get(name), Some(Expression::SpecialForm(_)))
+    }
+
+    fn resolve(&mut self, depth: usize, name: &str) -> Option<usize> {
+        let state = &self.states[depth];
+        let local = state
+            .scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(n, _)| n == name)
+            .map(|(_, slot)| *slot);
+        if local.is_some() {
+            return local;
+        }
+        if !state.function {
+            return None;
+        }
+        if name == "recur" || state.self_name.as_deref() == Some(name) {
+            if let Some(slot) = state.prototype.self_slot {
+                return Some(slot);
+            }
+            let slot = self.allocate(depth);
+            self.states[depth].prototype.self_slot = Some(slot);
+            return Some(slot);
+        }
+        let outer = self.resolve(depth - 1, name)?;
+        let slot = self.allocate(depth);
+        let state = &mut self.states[depth];
+        state.prototype.captures.push((outer, slot));
+        state.scopes[0].push((name.to_string(), slot));
+        Some(slot)
+    }
+
+    fn expression(&mut self, expression: Expression) -> Result<()> {
+        match expression {
+            Expression::Symbol(name) => {
+                match self.resolve(self.states.len() - 1, &name) {
+                    Some(slot) => self.emit(Instruction::Local(slot)),
+                    None => {
+                        let index = self.constant(Expression::Symbol(name));
+                        self.emit(Instruction::Global(index))
+                    }
+                };
+            }
+            Expression::NamespacedSymbol(_) => {
+                let index = self.constant(expression);
+                self.emit(Instruction::Global(index));
+            }
+            Expression::Call(call) => self.call(call)?,
+            Expression::Array(expressions) => {
+                let count = expressions.len();
+                for e in expressions {
+                    self.expression(e)?;
+                }
+                self.emit(Instruction::Array(count));
+            }
+            Expression::Map(map) => {
+                let count = map.len();
+                for (k, v) in map {
+                    self.expression(k)?;
+                    self.expression(v)?;
+                }
+                self.emit(Instruction::Map(count));
+            }
+            Expression::Quote(e) => {
+                let index = self.constant(*e);
+                self.emit(Instruction::Constant(index));
+            }
+            Expression::Deref(e) => {
+                self.expression(*e)?;
+                self.emit(Instruction::Deref);
+            }
+            e => {
+                let index = self.constant(e);
+                self.emit(Instruction::Constant(index));
+            }
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, call: Call) -> Result<()> {
+        let Call {
+            function,
+            arguments,
+        } = call;
+        let special = match function.as_ref() {
+            Expression::Symbol(name) if !self.bound(name) => Some(name.clone()),
+            _ => None,
+        };
+        match special.as_deref().filter(|name| self.special(name)) {
+            Some("if") => return self.conditional(arguments),
+            Some("when") => return self.when(arguments),
+            Some("do") => return self.sequence(arguments),
+            Some("let") => return self.bindings(arguments),
+            Some("fn") => return self.function(arguments, None),
+            Some("defn") => return self.defn(arguments),
+            Some("def") => return self.def(arguments),
+            Some("->") => return self.thread_first(arguments),
+            _ => {}
+        }
+        let count = arguments.len();
+        let callee = match special.as_deref() {
+            Some(name) if name != "recur" => {
+                let index = self.fallback(Expression::Call(Call {
+                    function: function.clone(),
+                    arguments: arguments.clone(),
+                }));
+                Some(self.emit(Instruction::Callee(index, 0)))
+            }
+            _ => {
+                self.expression(*function)?;
+                None
+            }
+        };
+        for argument in arguments {
+            self.expression(argument)?;
+        }
+        self.emit(Instruction::Call(count));
+        if let Some(callee) = callee {
+            self.patch(callee);
+        }
+        Ok(())
+    }
+
+    fn conditional(&mut self, arguments: Vector<Expression>) -> Result<()> {
+        if arguments.len() != 3 {
+            return Err(error(
+                "if expects a condition, a then branch and an else branch",
+            ));
+        }
+        let mut arguments = arguments.into_iter();
+        self.expression(arguments.next().unwrap())?;
+        let otherwise = self.emit(Instruction::JumpIfFalse(0));
+        self.expression(arguments.next().unwrap())?;
+        let end = self.emit(Instruction::Jump(0));
+        self.patch(otherwise);
+        self.expression(arguments.next().unwrap())?;
+        self.patch(end);
+        Ok(())
+    }
+
+    fn when(&mut self, arguments: Vector<Expression>) -> Result<()> {
+        let mut body = arguments;
+        let condition = body
+            .pop_front()
+            .ok_or_else(|| error("when expects a condition"))?;
+        self.expression(condition)?;
+        let otherwise = self.emit(Instruction::JumpIfFalse(0));
+        self.sequence(body)?;
+        let end = self.emit(Instruction::Jump(0));
+        self.patch(otherwise);
+        self.nil();
+        self.patch(end);
+        Ok(())
+    }
+
+    fn sequence(&mut self, expressions: Vector<Expression>) -> Result<()> {
+        if expressions.is_empty() {
+            self.nil();
+            return Ok(());
+        }
+        let last = expressions.len() - 1;
+        for (i, e) in expressions.into_iter().enumerate() {
+            self.expression(e)?;
+            if i < last {
+                self.emit(Instruction::Pop);
+            }
+        }
+        Ok(())
+    }
+
+    fn destructure(&mut self, pattern: Expression) {
+        if let Expression::Symbol(name) = pattern {
+            let slot = self.declare(name);
+            self.emit(Instruction::SetLocal(slot));
+        } else {
+            let mut names = vec![];
+            pattern_symbols(&pattern, &mut names);
+            let slots = names
+                .into_iter()
+                .map(|name| (name.clone(), self.declare(name)))
+                .collect();
+            let bindings = &mut self.state().prototype.bindings;
+            bindings.push(Binding { pattern, slots });
+            let index = bindings.len() - 1;
+            self.emit(Instruction::Destructure(index));
+        }
+    }
+
+    fn bindings(&mut self, arguments: Vector<Expression>) -> Result<()> {
+        let mut body = arguments;
+        let bindings = body
+            .pop_front()
+            .ok_or_else(|| error("let expects an array of bindings"))?;
+        let bindings = extract::array(bindings)?;
+        if bindings.len() % 2 != 0 {
+            return Err(error("let expects an even number of bindings"));
+        }
+        self.state().scopes.push(vec![]);
+        let mut bindings = bindings.into_iter();
+        while let (Some(pattern), Some(value)) = (bindings.next(), bindings.next()) {
+            self.expression(value)?;
+            self.destructure(pattern);
+        }
+        self.sequence(body)?;
+        self.state().scopes.pop();
+        Ok(())
+    }
+
+    fn function(&mut self, arguments: Vector<Expression>, self_name: Option<String>) -> Result<()> {
+        let arms = match arguments.front() {
+            Some(Expression::Array(_)) => {
+                let mut body = arguments;
+                let parameters = extract::array(body.pop_front().unwrap())?;
+                vec![(parameters, body)]
+            }
+            _ => arguments
+                .into_iter()
+                .map(|arm| {
+                    let call = extract::call(arm)?;
+                    Ok((extract::array(*call.function)?, call.arguments))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
+        self.states.push(State::new(true, self_name));
+        for (parameters, body) in arms {
+            self.state().scopes.push(vec![]);
+            let entry = self.state().prototype.instructions.len();
+            let pattern = Expression::Array(parameters.clone());
+            let mut names = vec![];
+            pattern_symbols(&pattern, &mut names);
+            let positional = names.len() == parameters.len()
+                && parameters
+                    .iter()
+                    .all(|p| matches!(p, Expression::Symbol(_)));
+            let slots = names
+                .into_iter()
+                .map(|name| (name.clone(), self.declare(name)))
+                .collect();
+            self.state().prototype.arms.push(Arm {
+                binding: Binding { pattern, slots },
+                positional,
+                entry,
+            });
+            self.sequence(body)?;
+            self.emit(Instruction::Return);
+            self.state().scopes.pop();
+        }
+        let prototype = self.states.pop().unwrap().prototype;
+        let prototypes = &mut self.state().prototype.prototypes;
+        prototypes.push(Arc::new(prototype));
+        let index = prototypes.len() - 1;
+        self.emit(Instruction::Closure(index));
+        Ok(())
+    }
+
+    fn defn(&mut self, arguments: Vector<Expression>) -> Result<()> {
+        let mut arguments = arguments;
+        let name = arguments
+            .pop_front()
+            .ok_or_else(|| error("defn expects a name"))?;
+        let name = extract::symbol(name)?;
+        self.defined.push(name.clone());
+        self.function(arguments, Some(name.clone()))?;
+        let index = self.constant(Expression::Symbol(name));
+        self.emit(Instruction::Def(index));
+        Ok(())
+    }
+
+    fn def(&mut self, arguments: Vector<Expression>) -> Result<()> {
+        if arguments.len() != 2 {
+            return Err(error("def expects a name and a value"));
+        }
+        let name = extract::symbol(arguments[0].clone())?;
+        self.defined.push(name.clone());
+        self.expression(arguments[1].clone())?;
+        let index = self.constant(Expression::Symbol(name));
+        self.emit(Instruction::Def(index));
+        Ok(())
+    }
+
+    fn thread_first(&mut self, arguments: Vector<Expression>) -> Result<()> {
+        let mut forms = arguments;
+        let mut result = forms
+            .pop_front()
+            .ok_or_else(|| error("-> expects an initial value"))?;
+        for form in forms {
+            result = match form {
+                Expression::Call(Call {
+                    function,
+                    mut arguments,
+                }) => {
+                    arguments.push_front(result);
+                    Expression::Call(Call {
+                        function,
+                        arguments,
+                    })
+                }
+                function => Expression::Call(Call {
+                    function: Box::new(function),
+                    arguments: vector![result],
+                }),
+            };
+        }
+        self.expression(result)
+    }
+
+    fn fallback(&mut self, expression: Expression) -> usize {
+        let depth = self.states.len() - 1;
+        let mut names = vec![];
+        free_symbols(&expression, &mut names);
+        let locals = names
+            .into_iter()
+            .filter_map(|name| self.resolve(depth, &name).map(|slot| (name, slot)))
+            .collect();
+        let fallbacks = &mut self.state().prototype.fallbacks;
+        fallbacks.push(Fallback { expression, locals });
+        fallbacks.len() - 1
+    }
+}
+
+/// Compiles `expression` against `env`: `if`, `let`, `fn` and the other forms the VM knows are
+/// only compiled inline while `env` binds them to their special forms, so a restricted or
+/// redefined environment keeps its meaning under the VM.
+pub fn compile(env: &Environment, expression: Expression) -> Result<Arc<Prototype>> {
+    let mut compiler = Compiler {
+        states: vec![State::new(false, None)],
+        env: env.clone(),
+        defined: vec![],
+    };
+    compiler.expression(expression)?;
+    compiler.emit(Instruction::Return);
+    Ok(Arc::new(compiler.states.pop().unwrap().prototype))
+}
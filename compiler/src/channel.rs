@@ -1,24 +1,51 @@
 extern crate alloc;
 
-use crate::Expression;
-use async_channel::{bounded, Receiver, Sender};
+use crate::effect::{error, Effect};
+use crate::expression::{Call, Environment};
+use crate::{evaluate_expressions, extract, Expression, NativeType};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use async_channel::{bounded, Receiver, Sender, TryRecvError, TrySendError};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use im::{ordmap, vector, OrdMap, Vector};
 use uuid::Uuid;
 
+type Result<T> = core::result::Result<T, Effect>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Buffer {
+    Fixed,
+    Sliding,
+    Dropping,
+}
+
 #[derive(Clone)]
 pub struct Channel {
     pub sender: Sender<Expression>,
     pub receiver: Receiver<Expression>,
     pub uuid: Uuid,
+    pub buffer: Buffer,
 }
 
 impl Channel {
     pub fn new(buffer_size: usize) -> Channel {
+        Channel::with_buffer(buffer_size, Buffer::Fixed)
+    }
+
+    pub fn with_buffer(buffer_size: usize, buffer: Buffer) -> Channel {
         let (sender, receiver) = bounded(buffer_size);
         let uuid = Uuid::new_v4();
         Channel {
             sender,
             receiver,
             uuid,
+            buffer,
         }
     }
 }
@@ -61,6 +88,395 @@ impl Ord for Channel {
     }
 }
 
-pub async fn take(channel: Channel) -> Expression {
-    channel.receiver.recv().await.unwrap_or(Expression::Nil)
+pub const CLOSED: &str = ":channel/closed";
+pub const EMPTY: &str = ":channel/empty";
+
+/// What take! returns once a channel is closed and drained.
+pub fn closed() -> Expression {
+    Expression::Keyword(CLOSED.to_string())
+}
+
+/// What poll! returns when nothing is waiting on an open channel.
+pub fn empty() -> Expression {
+    Expression::Keyword(EMPTY.to_string())
+}
+
+/// Rejects the markers take! and poll! return, so receiving one always means
+/// the channel is closed or empty rather than that someone sent it.
+pub fn sendable(value: &Expression) -> Result<()> {
+    match value {
+        Expression::Keyword(k) if k == CLOSED || k == EMPTY => {
+            Err(error(&format!("Cannot put {} on a channel", k)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Takes the next value, or `None` once the channel is closed and drained.
+pub async fn take(channel: Channel) -> Option<Expression> {
+    channel.receiver.recv().await.ok()
+}
+
+pub async fn put(channel: &Channel, value: Expression) -> bool {
+    match channel.buffer {
+        Buffer::Fixed => channel.sender.send(value).await.is_ok(),
+        Buffer::Sliding | Buffer::Dropping => try_put(channel, value),
+    }
+}
+
+pub fn try_put(channel: &Channel, value: Expression) -> bool {
+    let mut value = value;
+    loop {
+        match channel.sender.try_send(value) {
+            Ok(()) => return true,
+            Err(TrySendError::Closed(_)) => return false,
+            Err(TrySendError::Full(rejected)) => match channel.buffer {
+                Buffer::Fixed => return false,
+                Buffer::Dropping => return true,
+                Buffer::Sliding => {
+                    let _ = channel.receiver.try_recv();
+                    value = rejected;
+                }
+            },
+        }
+    }
+}
+
+pub fn try_take(channel: &Channel) -> Expression {
+    match channel.receiver.try_recv() {
+        Ok(value) => value,
+        Err(TryRecvError::Empty) => empty(),
+        Err(TryRecvError::Closed) => closed(),
+    }
+}
+
+pub fn timeout(duration: core::time::Duration) -> Channel {
+    let channel = Channel::new(1);
+    let closing = channel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(duration).await;
+        closing.sender.close();
+    });
+    channel
+}
+
+fn size(expression: Expression) -> Result<usize> {
+    extract::integer(expression)?
+        .to_usize()
+        .filter(|size| *size > 0)
+        .ok_or(error("Expected positive integer"))
+}
+
+fn buffer(kind: &str, args: Vector<Expression>) -> Result<Expression> {
+    if args.len() != 1 {
+        return Err(error(&format!("{}-buffer expects a size", &kind[1..])));
+    }
+    Ok(Expression::Map(ordmap! {
+        Expression::Keyword(":buffer".to_string()) => Expression::Keyword(kind.to_string()),
+        Expression::Keyword(":size".to_string()) => args[0].clone()
+    }))
+}
+
+pub async fn sliding_buffer(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    Ok((env, buffer(":sliding", args)?))
+}
+
+pub async fn dropping_buffer(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    Ok((env, buffer(":dropping", args)?))
+}
+
+pub async fn chan(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let channel = match args.front() {
+        None => Channel::new(1),
+        Some(Expression::Map(spec)) => {
+            let kind = extract::keyword(extract::key(spec.clone(), ":buffer")?)?;
+            let buffer = match kind.as_str() {
+                ":sliding" => Buffer::Sliding,
+                ":dropping" => Buffer::Dropping,
+                ":fixed" => Buffer::Fixed,
+                _ => return Err(error(&format!("Unknown buffer {}", kind))),
+            };
+            Channel::with_buffer(size(extract::key(spec.clone(), ":size")?)?, buffer)
+        }
+        Some(buffer_size) => Channel::new(size(buffer_size.clone())?),
+    };
+    Ok((env, Expression::Channel(channel)))
+}
+
+pub async fn offer(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let channel = extract::channel(args[0].clone())?;
+    sendable(&args[1])?;
+    Ok((env, Expression::Bool(try_put(&channel, args[1].clone()))))
+}
+
+pub async fn poll(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let channel = extract::channel(args[0].clone())?;
+    Ok((env, try_take(&channel)))
+}
+
+type Operation = Pin<Box<dyn Future<Output = (Expression, Channel)> + Send>>;
+
+pub async fn alts(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let mut operations: Vec<Operation> = vec![];
+    for operation in extract::array(args[0].clone())? {
+        match operation {
+            Expression::Channel(channel) => operations.push(Box::pin(async move {
+                let value = take(channel.clone()).await.unwrap_or_else(closed);
+                (value, channel)
+            })),
+            Expression::Array(pair) if pair.len() == 2 => {
+                let channel = extract::channel(pair[0].clone())?;
+                let value = pair[1].clone();
+                sendable(&value)?;
+                operations.push(Box::pin(async move {
+                    let sent = put(&channel, value).await;
+                    (Expression::Bool(sent), channel)
+                }))
+            }
+            e => {
+                return Err(error(&format!(
+                    "Expected channel or [channel value] got {}",
+                    e
+                )))
+            }
+        }
+    }
+    if operations.is_empty() {
+        return Err(error("alts! expects at least one operation"));
+    }
+    let ((value, channel), _, _) = futures::future::select_all(operations).await;
+    Ok((
+        env,
+        Expression::Array(vector![value, Expression::Channel(channel)]),
+    ))
+}
+
+fn close_flag(args: &Vector<Expression>, index: usize) -> bool {
+    !matches!(
+        args.get(index),
+        Some(Expression::Nil | Expression::Bool(false))
+    )
+}
+
+pub async fn pipe(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let from = extract::channel(args[0].clone())?;
+    let to = extract::channel(args[1].clone())?;
+    let close = close_flag(&args, 2);
+    let output = to.clone();
+    tokio::spawn(async move {
+        while let Ok(value) = from.receiver.recv().await {
+            if !put(&to, value).await {
+                return;
+            }
+        }
+        if close {
+            to.sender.close();
+        }
+    });
+    Ok((env, Expression::Channel(output)))
+}
+
+pub async fn merge(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let channels = match args.len() {
+        1 => extract::array(args[0].clone())?,
+        _ => return Err(error("merge-channels expects an array of channels")),
+    };
+    let channels = channels
+        .into_iter()
+        .map(extract::channel)
+        .collect::<Result<Vec<_>>>()?;
+    let output = Channel::new(1);
+    if channels.is_empty() {
+        output.sender.close();
+    }
+    let remaining = Arc::new(AtomicUsize::new(channels.len()));
+    for channel in channels {
+        let output = output.clone();
+        let remaining = remaining.clone();
+        tokio::spawn(async move {
+            while let Ok(value) = channel.receiver.recv().await {
+                if !put(&output, value).await {
+                    return;
+                }
+            }
+            if remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+                output.sender.close();
+            }
+        });
+    }
+    Ok((env, Expression::Channel(output)))
+}
+
+async fn with<S: 'static, T>(native: &NativeType, f: impl FnOnce(&mut S) -> T) -> Result<T> {
+    let mut value = native.value.lock().await;
+    match value.downcast_mut::<S>() {
+        Some(state) => Ok(f(state)),
+        None => Err(error(&format!("Unexpected {}", native.name))),
+    }
+}
+
+async fn distribute(subscribers: Vec<(Channel, bool)>, value: &Expression) {
+    for (channel, _) in subscribers {
+        put(&channel, value.clone()).await;
+    }
+}
+
+fn close_all(subscribers: Vec<(Channel, bool)>) {
+    for (channel, close) in subscribers {
+        if close {
+            channel.sender.close();
+        }
+    }
+}
+
+struct Mult {
+    taps: Vec<(Channel, bool)>,
+}
+
+pub async fn mult(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let source = extract::channel(args[0].clone())?;
+    let native = NativeType::new(Mult { taps: vec![] }, "mult".to_string());
+    let shared = native.clone();
+    tokio::spawn(async move {
+        while let Ok(value) = source.receiver.recv().await {
+            let taps = with(&shared, |mult: &mut Mult| mult.taps.clone()).await?;
+            distribute(taps, &value).await;
+        }
+        close_all(with(&shared, |mult: &mut Mult| mult.taps.clone()).await?);
+        Ok::<(), Effect>(())
+    });
+    Ok((env, Expression::NativeType(native)))
+}
+
+pub async fn tap(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let native = extract::native_type(args[0].clone())?;
+    let channel = extract::channel(args[1].clone())?;
+    let close = close_flag(&args, 2);
+    let tapped = channel.clone();
+    with(&native, |mult: &mut Mult| mult.taps.push((tapped, close))).await?;
+    Ok((env, Expression::Channel(channel)))
+}
+
+pub async fn untap(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let native = extract::native_type(args[0].clone())?;
+    let channel = extract::channel(args[1].clone())?;
+    with(&native, |mult: &mut Mult| {
+        mult.taps.retain(|(tap, _)| *tap != channel)
+    })
+    .await?;
+    Ok((env, Expression::Nil))
+}
+
+struct Publication {
+    subscribers: OrdMap<Expression, Vec<(Channel, bool)>>,
+}
+
+pub async fn publish(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let source = extract::channel(args[0].clone())?;
+    let topic = args[1].clone();
+    let native = NativeType::new(
+        Publication {
+            subscribers: OrdMap::new(),
+        },
+        "pub".to_string(),
+    );
+    let shared = native.clone();
+    let topic_env = env.clone();
+    tokio::spawn(async move {
+        while let Ok(value) = source.receiver.recv().await {
+            let call = Expression::Call(Call {
+                function: Box::new(topic.clone()),
+                arguments: vector![Expression::Quote(Box::new(value.clone()))],
+            });
+            let Ok((_, key)) = crate::evaluate(topic_env.clone(), call).await else {
+                continue;
+            };
+            let subscribers = with(&shared, |publication: &mut Publication| {
+                publication
+                    .subscribers
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .await?;
+            distribute(subscribers, &value).await;
+        }
+        let subscribers = with(&shared, |publication: &mut Publication| {
+            publication
+                .subscribers
+                .values()
+                .flatten()
+                .cloned()
+                .collect()
+        })
+        .await?;
+        close_all(subscribers);
+        Ok::<(), Effect>(())
+    });
+    Ok((env, Expression::NativeType(native)))
+}
+
+pub async fn sub(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let native = extract::native_type(args[0].clone())?;
+    let topic = args[1].clone();
+    let channel = extract::channel(args[2].clone())?;
+    let close = close_flag(&args, 3);
+    let subscribed = channel.clone();
+    with(&native, |publication: &mut Publication| {
+        publication
+            .subscribers
+            .entry(topic)
+            .or_insert_with(Vec::new)
+            .push((subscribed, close))
+    })
+    .await?;
+    Ok((env, Expression::Channel(channel)))
+}
+
+pub async fn unsub(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let native = extract::native_type(args[0].clone())?;
+    let topic = args[1].clone();
+    let channel = extract::channel(args[2].clone())?;
+    with(&native, |publication: &mut Publication| {
+        if let Some(subscribers) = publication.subscribers.get_mut(&topic) {
+            subscribers.retain(|(subscriber, _)| *subscriber != channel);
+        }
+    })
+    .await?;
+    Ok((env, Expression::Nil))
 }
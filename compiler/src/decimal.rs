@@ -0,0 +1,176 @@
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+use rug::{Integer, Rational};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    Up,
+    Down,
+    Ceiling,
+    Floor,
+    HalfUp,
+    HalfDown,
+    HalfEven,
+}
+
+impl RoundingMode {
+    pub fn from_keyword(keyword: &str) -> Option<RoundingMode> {
+        match keyword {
+            ":up" => Some(RoundingMode::Up),
+            ":down" => Some(RoundingMode::Down),
+            ":ceiling" => Some(RoundingMode::Ceiling),
+            ":floor" => Some(RoundingMode::Floor),
+            ":half-up" => Some(RoundingMode::HalfUp),
+            ":half-down" => Some(RoundingMode::HalfDown),
+            ":half-even" => Some(RoundingMode::HalfEven),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Decimal {
+    unscaled: Integer,
+    scale: u32,
+}
+
+fn power_of_ten(scale: u32) -> Integer {
+    Integer::from(Integer::u_pow_u(10, scale))
+}
+
+impl Decimal {
+    pub fn new(unscaled: Integer, scale: u32) -> Decimal {
+        Decimal { unscaled, scale }
+    }
+
+    pub fn from_str(s: &str) -> Option<Decimal> {
+        let (sign, digits) = match s.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let valid = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() && fraction.is_empty() || !valid(whole) || !valid(fraction) {
+            return None;
+        }
+        let mut unscaled = String::from(sign);
+        unscaled.push_str(whole);
+        unscaled.push_str(fraction);
+        Some(Decimal {
+            unscaled: unscaled.parse().ok()?,
+            scale: fraction.len() as u32,
+        })
+    }
+
+    pub fn from_rational(rational: &Rational) -> Option<Decimal> {
+        let mut denominator = rational.denom().clone();
+        let twos = denominator.remove_factor_mut(&Integer::from(2));
+        let fives = denominator.remove_factor_mut(&Integer::from(5));
+        if denominator != 1 {
+            return None;
+        }
+        let scale = twos.max(fives);
+        let unscaled = (rational.clone() * power_of_ten(scale)).numer().clone();
+        Some(Decimal { unscaled, scale })
+    }
+
+    pub fn round(rational: &Rational, scale: u32, mode: RoundingMode) -> Decimal {
+        let scaled = rational.clone() * power_of_ten(scale);
+        let (fraction, truncated) = scaled.clone().fract_trunc(Integer::new());
+        if fraction == 0 {
+            return Decimal::new(truncated, scale);
+        }
+        let away = Integer::from(&truncated + scaled.cmp0() as i32);
+        let half = fraction.abs().cmp(&Rational::from((1, 2)));
+        let unscaled = match (mode, half) {
+            (RoundingMode::Up, _) => away,
+            (RoundingMode::Down, _) => truncated,
+            (RoundingMode::Ceiling, _) if scaled > 0 => away,
+            (RoundingMode::Floor, _) if scaled < 0 => away,
+            (RoundingMode::Ceiling | RoundingMode::Floor, _) => truncated,
+            (_, Ordering::Greater) => away,
+            (_, Ordering::Less) => truncated,
+            (RoundingMode::HalfUp, Ordering::Equal) => away,
+            (RoundingMode::HalfDown, Ordering::Equal) => truncated,
+            (RoundingMode::HalfEven, Ordering::Equal) if truncated.is_odd() => away,
+            (RoundingMode::HalfEven, Ordering::Equal) => truncated,
+        };
+        Decimal::new(unscaled, scale)
+    }
+
+    pub fn to_rational(&self) -> Rational {
+        Rational::from((self.unscaled.clone(), power_of_ten(self.scale)))
+    }
+
+    pub fn unscaled(&self) -> &Integer {
+        &self.unscaled
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    fn normalized(&self) -> (Integer, u32) {
+        let mut unscaled = self.unscaled.clone();
+        let mut scale = self.scale;
+        while scale > 0 && unscaled.is_divisible_u(10) {
+            unscaled /= 10;
+            scale -= 1;
+        }
+        (unscaled, scale)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_rational().cmp(&other.to_rational())
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+impl core::fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}M", self)
+    }
+}
+
+impl core::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.unscaled < 0 {
+            write!(f, "-")?;
+        }
+        let digits = Integer::from(self.unscaled.abs_ref()).to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}", digits);
+        }
+        if digits.len() > scale {
+            let (whole, fraction) = digits.split_at(digits.len() - scale);
+            write!(f, "{}.{}", whole, fraction)
+        } else {
+            write!(f, "0.{:0>width$}", digits, width = scale)
+        }
+    }
+}
@@ -6,16 +6,22 @@ use core::error::Error;
 #[derive(PartialEq, Eq, Clone)]
 pub enum Effect {
     Error(String),
+    Limit(String),
 }
 
 pub fn error(message: &str) -> Effect {
     Effect::Error(message.to_string())
 }
 
+pub fn limit(message: &str) -> Effect {
+    Effect::Limit(message.to_string())
+}
+
 impl core::fmt::Debug for Effect {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Effect::Error(message) => write!(f, "#effect::error({})", message),
+            Effect::Limit(message) => write!(f, "#effect::limit({})", message),
         }
     }
 }
@@ -24,6 +30,7 @@ impl core::fmt::Display for Effect {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Effect::Error(message) => write!(f, "#effect::error({})", message),
+            Effect::Limit(message) => write!(f, "#effect::limit({})", message),
         }
     }
 }
@@ -12,7 +12,7 @@ use alloc::vec::Vec;
 use async_recursion::async_recursion;
 use im::Vector;
 
-fn evaluate_symbol(environment: Environment, symbol: String) -> Result {
+pub(crate) fn evaluate_symbol(environment: Environment, symbol: String) -> Result {
     if let Some(e) = environment.get(&symbol) {
         Ok((environment.clone(), e.clone()))
     } else {
@@ -23,7 +23,7 @@ fn evaluate_symbol(environment: Environment, symbol: String) -> Result {
     }
 }
 
-fn evaluate_namespaced_symbol(environment: Environment, symbol: &[String]) -> Result {
+pub(crate) fn evaluate_namespaced_symbol(environment: Environment, symbol: &[String]) -> Result {
     let (first, rest) = symbol.split_first().unwrap();
     let (environment, value) = evaluate_symbol(environment, first.clone())?;
     if rest.is_empty() {
@@ -123,6 +123,32 @@ pub fn pattern_match(
     }
 }
 
+const CONTEXT: [&str; 2] = [crate::sandbox::KEY, crate::task::TRACKED];
+const DYNAMIC: [&str; 4] = [
+    "*precision*",
+    "*numbers*",
+    "*concurrency*",
+    crate::stm::TRANSACTION,
+];
+
+/// Runs a function under the caller's sandbox and task tracking, even one
+/// defined outside of them, and with the caller's dynamically scoped bindings
+/// such as `*precision*`, `*numbers*` and the running transaction.
+pub(crate) fn inherit(caller: &Environment, mut callee: Environment) -> Environment {
+    for name in CONTEXT {
+        if let Some(value) = caller.get(name) {
+            callee.insert(name.to_string(), value.clone());
+        }
+    }
+    for name in DYNAMIC {
+        match caller.get(name) {
+            Some(value) => callee.insert(name.to_string(), value.clone()),
+            None => callee.remove(name),
+        };
+    }
+    callee
+}
+
 fn find_pattern_match(
     env: Environment,
     patterns: Vector<Pattern>,
@@ -158,7 +184,8 @@ async fn evaluate_call(environment: Environment, call: Call) -> Result {
             let original_environment = environment.clone();
             let (_, arguments) = evaluate_expressions(environment, arguments).await?;
             let cloned_function = function.clone();
-            let (mut env, body) = find_pattern_match(function.env, function.patterns, arguments)?;
+            let env = inherit(&original_environment, function.env);
+            let (mut env, body) = find_pattern_match(env, function.patterns, arguments)?;
             env.insert(
                 "recur".to_string(),
                 Expression::Function(cloned_function.clone()),
@@ -167,9 +194,17 @@ async fn evaluate_call(environment: Environment, call: Call) -> Result {
                 env.insert(name.to_string(), Expression::Function(cloned_function));
             }
             let (_, results) = evaluate_expressions(env, body).await?;
-            Ok((original_environment, results.last().unwrap_or(&Expression::Nil).clone()))
+            Ok((
+                original_environment,
+                results.last().unwrap_or(&Expression::Nil).clone(),
+            ))
+        }
+        Expression::Closure(closure) => {
+            let (environment, arguments) = evaluate_expressions(environment, arguments).await?;
+            let value = crate::vm::call(&environment, closure, arguments).await?;
+            Ok((environment, value))
         }
-        Expression::NativeFunction(f) => {
+        Expression::NativeFunction(f) | Expression::SpecialForm(f) => {
             let (env, value) = f(environment, arguments).await?;
             Ok((env, value))
         }
@@ -202,11 +237,25 @@ async fn evaluate_call(environment: Environment, call: Call) -> Result {
     }
 }
 
+pub(crate) async fn deref(
+    environment: &Environment,
+    expression: Expression,
+) -> core::result::Result<Expression, Effect> {
+    match expression {
+        Expression::Ref(reference) => crate::stm::deref(environment, &reference).await,
+        Expression::Agent(agent) => Ok(agent.0.shared.value.lock().await.clone()),
+        expression => {
+            let atom = extract::atom(expression)?;
+            let value = atom.0.lock().await;
+            Ok(value.clone())
+        }
+    }
+}
+
 async fn evaluate_deref(environment: Environment, expression: Expression) -> Result {
     let (environment, expression) = evaluate(environment, expression).await?;
-    let atom = extract::atom(expression)?;
-    let value = atom.0.lock().await;
-    Ok((environment, value.clone()))
+    let value = deref(&environment, expression).await?;
+    Ok((environment, value))
 }
 
 #[async_recursion]
@@ -214,7 +263,12 @@ pub async fn evaluate(environment: Environment, expression: Expression) -> Resul
     match expression {
         Expression::Symbol(s) => evaluate_symbol(environment, s),
         Expression::NamespacedSymbol(s) => evaluate_namespaced_symbol(environment, &s),
-        Expression::Call(call) => evaluate_call(environment, call).await,
+        Expression::Call(call) => {
+            crate::sandbox::step(&environment).await?;
+            let (environment, value) = evaluate_call(environment, call).await?;
+            crate::sandbox::check(&environment, &value).await?;
+            Ok((environment, value))
+        }
         Expression::Array(a) => {
             let (environment, a) = evaluate_expressions(environment, a).await?;
             Ok((environment, Expression::Array(a)))
@@ -240,38 +294,25 @@ pub async fn evaluate_expressions(
     env: Environment,
     exprs: Vector<Expression>,
 ) -> core::result::Result<(Environment, Vector<Expression>), Effect> {
-    let futures: Vec<_> = exprs
-        .iter()
-        .map(|expr| {
-            let env = env.clone();
-            let expr = expr.clone();
-            async move {
-                let (_, value) = evaluate(env, expr).await?;
-                Ok(value)
-            }
-        })
-        .collect();
-    let results = futures::future::join_all(futures).await;
     let mut values = Vector::new();
-    for result in results {
-        match result {
-            Ok(v) => values.push_back(v),
-            Err(e) => return Err(e),
-        }
+    for expr in exprs {
+        let (_, value) = evaluate(env.clone(), expr).await?;
+        values.push_back(value);
     }
     Ok((env, values))
 }
 
 pub async fn evaluate_source(
     mut env: Environment,
-    source: &str
+    source: &str,
 ) -> core::result::Result<(Environment, Expression), Effect> {
-    let tokens = crate::tokenize(source);
+    let tokens = crate::tokenize(source)?;
     let mut tokens = &tokens[..];
     let mut result = Expression::Nil;
     while !tokens.is_empty() {
         let (new_tokens, expression) = crate::parse(tokens);
         tokens = new_tokens;
+        let (expression, _) = crate::optimize(&env, expression);
         let (new_env, new_result) = evaluate(env, expression).await?;
         result = new_result;
         env = new_env;
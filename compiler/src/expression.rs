@@ -1,14 +1,21 @@
 extern crate alloc;
 
+use crate::agent::Agent;
 use crate::atom::Atom;
+use crate::binary::to_hex;
+use crate::bytecode::Closure;
 use crate::channel::Channel;
-use crate::effect::Effect;
+use crate::decimal::Decimal;
+use crate::effect::{error, Effect};
 use crate::numerics::Float;
+use crate::stm::Ref;
+use crate::task::Task;
 use crate::NativeType;
 use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use bytes::Bytes;
 use core::fmt::{self, Display, Formatter};
 use core::future::Future;
 use core::hash::Hash;
@@ -51,24 +58,36 @@ pub enum Expression {
     NamespacedSymbol(Vec<String>),
     Keyword(String),
     String(String),
+    Bytes(Bytes),
     Integer(Integer),
     Float(Float),
     Ratio(Rational),
+    Decimal(Decimal),
     Bool(bool),
     Nil,
     Array(Expressions),
     Map(OrdMap<Expression, Expression>),
     Call(Call),
     Function(Function),
+    Closure(Closure),
     Quote(Box<Expression>),
     Deref(Box<Expression>),
     Atom(Atom),
+    Ref(Ref),
+    Agent(Agent),
     Channel(Channel),
+    Task(Task),
     NativeFunction(NativeFunction),
+    /// A native that receives its arguments unevaluated, like `if` or `for`.
+    SpecialForm(NativeFunction),
     NativeType(NativeType),
     Module(Environment),
 }
 
+pub fn truthy(expression: &Expression) -> bool {
+    !matches!(expression, Expression::Nil | Expression::Bool(false))
+}
+
 impl Display for Expression {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
@@ -111,9 +130,11 @@ impl Display for Expression {
                 }
                 write!(f, "\"")
             }
+            Expression::Bytes(b) => write!(f, "#x\"{}\"", to_hex(b)),
             Expression::Integer(i) => write!(f, "{}", i),
             Expression::Float(fl) => write!(f, "{}", fl),
             Expression::Ratio(r) => write!(f, "{}/{}", r.numer(), r.denom()),
+            Expression::Decimal(d) => write!(f, "{}M", d),
             Expression::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
             Expression::Nil => write!(f, "nil"),
             Expression::Call(Call {
@@ -143,10 +164,15 @@ impl Display for Expression {
                     write!(f, ")")
                 }
             }
+            Expression::Closure(c) => write!(f, "{}", c),
             Expression::NativeFunction(_) => write!(f, "#native_function"),
+            Expression::SpecialForm(_) => write!(f, "#special_form"),
             Expression::NativeType(t) => write!(f, "{}", t),
             Expression::Atom(a) => write!(f, "{}", a),
+            Expression::Ref(r) => write!(f, "{}", r),
+            Expression::Agent(a) => write!(f, "{}", a),
             Expression::Channel(c) => write!(f, "{}", c),
+            Expression::Task(t) => write!(f, "{}", t),
             Expression::Quote(e) => write!(f, "'{}", e),
             Expression::Deref(e) => write!(f, "@{}", e),
             Expression::Module(e) => write!(
@@ -157,34 +183,158 @@ impl Display for Expression {
         }
     }
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Numbers {
+    #[default]
+    Exact,
+    String,
+    Float,
+}
+
+impl Numbers {
+    pub fn from_options(
+        env: &Environment,
+        options: Option<&Expression>,
+    ) -> core::result::Result<Numbers, Effect> {
+        let mode = match options {
+            Some(Expression::Map(map)) => map.get(&Expression::Keyword(":numbers".to_string())),
+            _ => None,
+        };
+        match mode.or(env.get("*numbers*")) {
+            None | Some(Expression::Nil) => Ok(Numbers::Exact),
+            Some(Expression::Keyword(k)) if k == ":exact" => Ok(Numbers::Exact),
+            Some(Expression::Keyword(k)) if k == ":string" => Ok(Numbers::String),
+            Some(Expression::Keyword(k)) if k == ":float" => Ok(Numbers::Float),
+            Some(other) => Err(error(&format!(
+                "Unknown number serialization mode {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Serializes an expression, writing exact numbers that don't fit an `i64`,
+/// `u64` or `f64` as strings unless `numbers` says otherwise. Bytes go through
+/// `serialize_bytes`, which JSON writes as an array of integers.
+pub struct Serialized<'a> {
+    pub expression: &'a Expression,
+    pub numbers: Numbers,
+}
+
+pub fn map_key(key: &Expression) -> Option<String> {
+    match key {
+        Expression::Keyword(k) => Some(k[1..].to_string()),
+        Expression::String(s) => Some(s.clone()),
+        Expression::Integer(i) => Some(i.to_string()),
+        Expression::Decimal(d) => Some(d.to_string()),
+        Expression::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn exact_float(float: &Float) -> Option<String> {
+    let decimal = Decimal::from_rational(&float.as_float().to_rational()?)?.to_string();
+    match decimal.contains('.') {
+        true => Some(decimal),
+        false => Some(format!("{}.0", decimal)),
+    }
+}
+
+fn exactly_f64(float: &Float) -> bool {
+    let value = float.to_f64();
+    value.is_finite() && *float.as_float() == value
+}
+
+/// The digits of a number that `Serialized` writes as a string under
+/// `numbers` because it doesn't fit an `i64`, `u64` or `f64`, for formats
+/// that can write numbers of any size exactly.
+pub fn exact_number(expression: &Expression, numbers: Numbers) -> Option<String> {
+    match (expression, numbers) {
+        (Expression::Integer(i), Numbers::Exact)
+            if i.to_i64().is_none() && i.to_u64().is_none() =>
+        {
+            Some(i.to_string())
+        }
+        (Expression::Float(f), Numbers::Exact) if !exactly_f64(f) => exact_float(f),
+        _ => None,
+    }
+}
+
 impl Serialize for Expression {
     fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        match self {
+        Serialized {
+            expression: self,
+            numbers: Numbers::default(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'a> Serialize for Serialized<'a> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let numbers = self.numbers;
+        let nested = |expression| Serialized {
+            expression,
+            numbers,
+        };
+        match self.expression {
             Expression::Keyword(s) => s[1..].serialize(serializer),
             Expression::String(s) => s.serialize(serializer),
-            Expression::Integer(i) => i.to_i64().serialize(serializer),
-            Expression::Float(f) => f.to_f64().serialize(serializer),
-            Expression::Ratio(r) => r.to_f64().serialize(serializer),
+            Expression::Bytes(b) => serializer.serialize_bytes(b),
+            Expression::Integer(i) => match (i.to_i64(), i.to_u64(), numbers) {
+                (Some(i), _, _) => serializer.serialize_i64(i),
+                (_, Some(u), _) => serializer.serialize_u64(u),
+                (_, _, Numbers::Exact | Numbers::String) => {
+                    serializer.serialize_str(&i.to_string())
+                }
+                (_, _, Numbers::Float) => serializer.serialize_f64(i.to_f64()),
+            },
+            Expression::Float(f) => {
+                let value = f.to_f64();
+                match (numbers, exact_float(f)) {
+                    _ if exactly_f64(f) => serializer.serialize_f64(value),
+                    (Numbers::Exact, Some(text)) => serializer.serialize_str(&text),
+                    (Numbers::Float, _) if value.is_finite() => serializer.serialize_f64(value),
+                    _ => serializer.serialize_str(&f.to_string()),
+                }
+            }
+            Expression::Ratio(r) => match numbers {
+                Numbers::Float => serializer.serialize_f64(r.to_f64()),
+                _ => serializer.serialize_str(&format!("{}/{}", r.numer(), r.denom())),
+            },
+            Expression::Decimal(d) => match numbers {
+                Numbers::Float => serializer.serialize_f64(d.to_rational().to_f64()),
+                _ => serializer.serialize_str(&d.to_string()),
+            },
             Expression::Bool(b) => b.serialize(serializer),
             Expression::Nil => serializer.serialize_unit(),
             Expression::Array(expressions) => {
                 let mut seq = serializer.serialize_seq(Some(expressions.len()))?;
                 for e in expressions {
-                    seq.serialize_element(e)?;
+                    seq.serialize_element(&nested(e))?;
                 }
                 seq.end()
             }
             Expression::Map(map) => {
                 let mut map_ser = serializer.serialize_map(Some(map.len()))?;
                 for (k, v) in map {
-                    map_ser.serialize_entry(k, v)?;
+                    let key = map_key(k).ok_or_else(|| {
+                        serde::ser::Error::custom(format!("Cannot use {} as a map key", k))
+                    })?;
+                    map_ser.serialize_entry(&key, &nested(v))?;
                 }
                 map_ser.end()
             }
-            _ => Err(serde::ser::Error::custom("unsupported variant")),
+            expression => Err(serde::ser::Error::custom(format!(
+                "Cannot serialize {}",
+                expression
+            ))),
         }
     }
 }
@@ -1,8 +1,11 @@
 extern crate alloc;
+use crate::agent::Agent;
 use crate::atom::Atom;
 use crate::channel::Channel;
 use crate::effect::{error, Effect};
 use crate::expression::Call;
+use crate::stm::Ref;
+use crate::task::Task;
 use crate::{Environment, Expression, NativeType};
 use alloc::format;
 use alloc::string::{String, ToString};
@@ -31,6 +34,13 @@ pub fn string(expr: Expression) -> Result<String> {
     }
 }
 
+pub fn bytes(expr: Expression) -> Result<bytes::Bytes> {
+    match expr {
+        Expression::Bytes(b) => Ok(b),
+        _ => Err(error("Expected bytes")),
+    }
+}
+
 pub fn keyword(expr: Expression) -> Result<String> {
     match expr {
         Expression::Keyword(k) => Ok(k),
@@ -87,9 +97,30 @@ pub fn atom(expr: Expression) -> Result<Atom> {
     }
 }
 
+pub fn agent(expr: Expression) -> Result<Agent> {
+    match expr {
+        Expression::Agent(a) => Ok(a),
+        _ => Err(error("Expected agent")),
+    }
+}
+
+pub fn reference(expr: Expression) -> Result<Ref> {
+    match expr {
+        Expression::Ref(r) => Ok(r),
+        _ => Err(error("Expected ref")),
+    }
+}
+
 pub fn channel(expr: Expression) -> Result<Channel> {
     match expr {
         Expression::Channel(c) => Ok(c),
         _ => Err(error("Expected channel")),
     }
 }
+
+pub fn task(expr: Expression) -> Result<Task> {
+    match expr {
+        Expression::Task(t) => Ok(t),
+        _ => Err(error("Expected task")),
+    }
+}
@@ -2,9 +2,13 @@
 #![forbid(unsafe_code)]
 #![feature(error_in_core)]
 
+pub mod agent;
 pub mod array;
 pub mod atom;
+pub mod binary;
+pub mod bytecode;
 pub mod channel;
+mod decimal;
 pub mod effect;
 mod evaluator;
 pub mod expression;
@@ -12,12 +16,23 @@ pub mod extract;
 pub mod map;
 mod native_type;
 mod numerics;
+mod optimizer;
 mod parser;
+pub mod sandbox;
+pub mod stm;
+pub mod task;
 mod tokenizer;
+pub mod vm;
 
+pub use decimal::{Decimal, RoundingMode};
 pub use evaluator::{evaluate, evaluate_expressions, evaluate_source, pattern_match};
-pub use expression::{Environment, Expression};
+pub use expression::{truthy, Environment, Expression};
 pub use native_type::NativeType;
-pub use numerics::{bits_to_decimal_digits, decimal_digits_to_bits, ratio, Float};
+pub use numerics::{
+    abs, arithmetic, arithmetic_with_precision, as_float, bits_to_decimal_digits, compare,
+    decimal_digits_to_bits, precision_from, ratio, round, round_decimal, to_decimal, to_float,
+    to_integer, to_rational, Float, Operation, Rounding, DEFAULT_PRECISION,
+};
+pub use optimizer::optimize;
 pub use parser::{parse, parse_all};
 pub use tokenizer::{tokenize, Token};
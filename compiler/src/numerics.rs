@@ -1,6 +1,15 @@
-use rug::{self, float::OrdFloat, Rational};
+extern crate alloc;
 
+use rug::{self, float::OrdFloat, Integer, Rational};
+
+use crate::decimal::{Decimal, RoundingMode};
+use crate::effect::{error, Effect};
+use crate::expression::Environment;
 use crate::Expression;
+use alloc::format;
+use core::cmp::Ordering;
+
+pub const DEFAULT_PRECISION: u32 = f64::MANTISSA_DIGITS;
 
 pub fn decimal_digits_to_bits(decimal_digits: usize) -> u32 {
     (decimal_digits as f64 * 3.322).ceil() as u32
@@ -17,7 +26,7 @@ impl Float {
     pub fn from_str(s: &str) -> Float {
         let offset = if s.starts_with("-") { 2 } else { 1 };
         let digits = s.len() - offset;
-        let bits = decimal_digits_to_bits(digits);
+        let bits = decimal_digits_to_bits(digits).max(DEFAULT_PRECISION);
         let parsed = rug::Float::parse(s).unwrap();
         let float = rug::Float::with_val(bits, parsed);
         Float(OrdFloat::from(float))
@@ -27,6 +36,14 @@ impl Float {
         self.0.as_float().to_f64()
     }
 
+    pub fn from_float(float: rug::Float) -> Float {
+        Float(OrdFloat::from(float))
+    }
+
+    pub fn as_float(&self) -> &rug::Float {
+        self.0.as_float()
+    }
+
     pub fn from_f64(f: f64) -> Float {
         let bits = f64::MANTISSA_DIGITS;
         let float = rug::Float::with_val(bits, f);
@@ -59,3 +76,290 @@ pub fn ratio(rational: Rational) -> Expression {
         Expression::Ratio(rational)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+fn rational(expression: &Expression) -> Option<Rational> {
+    match expression {
+        Expression::Integer(i) => Some(Rational::from(i)),
+        Expression::Ratio(r) => Some(r.clone()),
+        Expression::Decimal(d) => Some(d.to_rational()),
+        _ => None,
+    }
+}
+
+fn decimal(expression: &Expression) -> Option<Decimal> {
+    match expression {
+        Expression::Integer(i) => Some(Decimal::new(i.clone(), 0)),
+        Expression::Decimal(d) => Some(d.clone()),
+        _ => None,
+    }
+}
+
+fn decimal_arithmetic(
+    operation: Operation,
+    lhs: Decimal,
+    rhs: Decimal,
+) -> Result<Expression, Effect> {
+    let (l, r) = (lhs.to_rational(), rhs.to_rational());
+    let exact = |value: Rational, scale: u32| {
+        Expression::Decimal(Decimal::round(&value, scale, RoundingMode::Down))
+    };
+    match operation {
+        Operation::Add => Ok(exact(l + r, lhs.scale().max(rhs.scale()))),
+        Operation::Subtract => Ok(exact(l - r, lhs.scale().max(rhs.scale()))),
+        Operation::Multiply => Ok(exact(l * r, lhs.scale() + rhs.scale())),
+        Operation::Divide if r == 0 => Err(error("Division by zero")),
+        // A quotient without a terminating decimal expansion stays exact as a ratio.
+        Operation::Divide => {
+            let quotient = l / r;
+            Ok(match Decimal::from_rational(&quotient) {
+                Some(d) => Expression::Decimal(d),
+                None => ratio(quotient),
+            })
+        }
+    }
+}
+
+fn precision(expression: &Expression) -> Option<u32> {
+    match expression {
+        Expression::Float(f) => Some(f.as_float().prec()),
+        _ => None,
+    }
+}
+
+fn float(expression: &Expression, prec: u32) -> Option<rug::Float> {
+    match expression {
+        Expression::Integer(i) => Some(rug::Float::with_val(prec, i)),
+        Expression::Ratio(r) => Some(rug::Float::with_val(prec, r)),
+        Expression::Decimal(d) => Some(rug::Float::with_val(prec, d.to_rational())),
+        Expression::Float(f) => Some(rug::Float::with_val(prec, f.as_float())),
+        _ => None,
+    }
+}
+
+pub fn arithmetic(
+    operation: Operation,
+    lhs: Expression,
+    rhs: Expression,
+) -> Result<Expression, Effect> {
+    arithmetic_with_precision(operation, lhs, rhs, None)
+}
+
+pub fn arithmetic_with_precision(
+    operation: Operation,
+    lhs: Expression,
+    rhs: Expression,
+    precision_bits: Option<u32>,
+) -> Result<Expression, Effect> {
+    if let (Expression::Integer(l), Expression::Integer(r)) = (&lhs, &rhs) {
+        match operation {
+            Operation::Add => return Ok(Expression::Integer(Integer::from(l + r))),
+            Operation::Subtract => return Ok(Expression::Integer(Integer::from(l - r))),
+            Operation::Multiply => return Ok(Expression::Integer(Integer::from(l * r))),
+            Operation::Divide => {}
+        }
+    }
+    if matches!(lhs, Expression::Decimal(_)) || matches!(rhs, Expression::Decimal(_)) {
+        if let (Some(l), Some(r)) = (decimal(&lhs), decimal(&rhs)) {
+            return decimal_arithmetic(operation, l, r);
+        }
+    }
+    if let (Some(l), Some(r)) = (rational(&lhs), rational(&rhs)) {
+        return match operation {
+            Operation::Add => Ok(ratio(l + r)),
+            Operation::Subtract => Ok(ratio(l - r)),
+            Operation::Multiply => Ok(ratio(l * r)),
+            Operation::Divide if r == 0 => Err(error("Division by zero")),
+            Operation::Divide => Ok(ratio(l / r)),
+        };
+    }
+    let prec = match (precision(&lhs), precision(&rhs), precision_bits) {
+        (None, None, _) => 0,
+        (_, _, Some(bits)) => bits,
+        (Some(l), Some(r), None) => l.max(r),
+        (Some(p), None, None) | (None, Some(p), None) => p,
+    };
+    match (float(&lhs, prec), float(&rhs, prec)) {
+        (Some(l), Some(r)) if prec > 0 => {
+            let result = match operation {
+                Operation::Add => rug::Float::with_val(prec, &l + &r),
+                Operation::Subtract => rug::Float::with_val(prec, &l - &r),
+                Operation::Multiply => rug::Float::with_val(prec, &l * &r),
+                Operation::Divide => rug::Float::with_val(prec, &l / &r),
+            };
+            Ok(Expression::Float(Float::from_float(result)))
+        }
+        _ => {
+            let verb = match operation {
+                Operation::Add => "add",
+                Operation::Subtract => "subtract",
+                Operation::Multiply => "multiply",
+                Operation::Divide => "divide",
+            };
+            Err(error(&format!("Cannot {} {} and {}", verb, lhs, rhs)))
+        }
+    }
+}
+
+pub fn precision_from(env: &Environment) -> Result<Option<u32>, Effect> {
+    match env.get("*precision*") {
+        None | Some(Expression::Nil) => Ok(None),
+        Some(Expression::Integer(bits)) => bits
+            .to_u32()
+            .filter(|bits| (rug::float::prec_min()..=rug::float::prec_max()).contains(bits))
+            .map(Some)
+            .ok_or_else(|| error(&format!("Invalid precision of {} bits", bits))),
+        Some(other) => Err(error(&format!("Expected precision in bits, got {}", other))),
+    }
+}
+
+pub fn compare(lhs: &Expression, rhs: &Expression) -> Result<Ordering, Effect> {
+    let ordering = match (lhs, rhs) {
+        (Expression::Float(l), Expression::Float(r)) => l.as_float().partial_cmp(r.as_float()),
+        (Expression::Float(l), r) => rational(r).and_then(|r| l.as_float().partial_cmp(&r)),
+        (l, Expression::Float(r)) => rational(l)
+            .and_then(|l| r.as_float().partial_cmp(&l))
+            .map(Ordering::reverse),
+        (l, r) => match (rational(l), rational(r)) {
+            (Some(l), Some(r)) => Some(l.cmp(&r)),
+            _ => None,
+        },
+    };
+    ordering.ok_or_else(|| error(&format!("Cannot compare {} and {}", lhs, rhs)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+    Round,
+    Truncate,
+}
+
+pub fn round(rounding: Rounding, expression: Expression) -> Result<Expression, Effect> {
+    match &expression {
+        Expression::Integer(_) => Ok(expression),
+        Expression::Ratio(_) | Expression::Decimal(_) => {
+            let r = rational(&expression).unwrap_or_default();
+            Ok(Expression::Integer(match rounding {
+                Rounding::Floor => Integer::from(r.floor_ref()),
+                Rounding::Ceil => Integer::from(r.ceil_ref()),
+                Rounding::Round => Integer::from(r.round_ref()),
+                Rounding::Truncate => Integer::from(r.trunc_ref()),
+            }))
+        }
+        Expression::Float(f) => {
+            let f = f.as_float().clone();
+            let f = match rounding {
+                Rounding::Floor => f.floor(),
+                Rounding::Ceil => f.ceil(),
+                Rounding::Round => f.round(),
+                Rounding::Truncate => f.trunc(),
+            };
+            f.to_integer()
+                .map(Expression::Integer)
+                .ok_or_else(|| error(&format!("Cannot round {}", expression)))
+        }
+        _ => Err(error(&format!("Cannot round {}", expression))),
+    }
+}
+
+pub fn abs(expression: Expression) -> Result<Expression, Effect> {
+    match expression {
+        Expression::Integer(i) => Ok(Expression::Integer(i.abs())),
+        Expression::Ratio(r) => Ok(Expression::Ratio(r.abs())),
+        Expression::Decimal(d) => Ok(Expression::Decimal(Decimal::new(
+            d.unscaled().clone().abs(),
+            d.scale(),
+        ))),
+        Expression::Float(f) => Ok(Expression::Float(Float::from_float(
+            f.as_float().clone().abs(),
+        ))),
+        _ => Err(error(&format!(
+            "Cannot take absolute value of {}",
+            expression
+        ))),
+    }
+}
+
+pub fn to_integer(expression: Expression) -> Result<Expression, Effect> {
+    match to_rational(expression.clone()) {
+        Ok(Expression::Integer(i)) => Ok(Expression::Integer(i)),
+        _ => Err(error(&format!(
+            "Cannot convert {} to an integer exactly",
+            expression
+        ))),
+    }
+}
+
+pub fn to_rational(expression: Expression) -> Result<Expression, Effect> {
+    match &expression {
+        Expression::Integer(_) | Expression::Ratio(_) => Ok(expression),
+        Expression::Decimal(d) => Ok(ratio(d.to_rational())),
+        Expression::String(s) => s
+            .parse::<Rational>()
+            .map(ratio)
+            .map_err(|_| error(&format!("Cannot convert {} to a ratio", expression))),
+        Expression::Float(f) => f
+            .as_float()
+            .to_rational()
+            .map(ratio)
+            .ok_or_else(|| error(&format!("Cannot convert {} to a ratio", expression))),
+        _ => Err(error(&format!("Cannot convert {} to a ratio", expression))),
+    }
+}
+
+pub fn as_float(
+    expression: &Expression,
+    precision_bits: Option<u32>,
+) -> Result<rug::Float, Effect> {
+    let prec = precision_bits
+        .or(precision(expression))
+        .unwrap_or(DEFAULT_PRECISION);
+    float(expression, prec)
+        .ok_or_else(|| error(&format!("Cannot convert {} to a float", expression)))
+}
+
+pub fn to_float(expression: Expression, precision_bits: Option<u32>) -> Result<Expression, Effect> {
+    as_float(&expression, precision_bits).map(|f| Expression::Float(Float::from_float(f)))
+}
+
+pub fn to_decimal(expression: Expression) -> Result<Expression, Effect> {
+    let decimal = match &expression {
+        Expression::Decimal(_) => return Ok(expression),
+        Expression::String(s) => Decimal::from_str(s),
+        _ => to_rational(expression.clone())
+            .ok()
+            .and_then(|r| rational(&r))
+            .and_then(|r| Decimal::from_rational(&r)),
+    };
+    decimal.map(Expression::Decimal).ok_or_else(|| {
+        error(&format!(
+            "Cannot convert {} to a decimal exactly",
+            expression
+        ))
+    })
+}
+
+pub fn round_decimal(
+    expression: Expression,
+    scale: u32,
+    mode: RoundingMode,
+) -> Result<Expression, Effect> {
+    let value = match &expression {
+        Expression::String(s) => Decimal::from_str(s).map(|d| d.to_rational()),
+        _ => to_rational(expression.clone())
+            .ok()
+            .and_then(|r| rational(&r)),
+    };
+    value
+        .map(|r| Expression::Decimal(Decimal::round(&r, scale, mode)))
+        .ok_or_else(|| error(&format!("Cannot convert {} to a decimal", expression)))
+}
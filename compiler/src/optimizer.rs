@@ -0,0 +1,393 @@
+extern crate alloc;
+
+use crate::bytecode::pattern_symbols;
+use crate::expression::{Call, Environment};
+use crate::numerics::{arithmetic_with_precision, precision_from, Operation};
+use crate::{truthy, Expression};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use im::{vector, Vector};
+use rug::Integer;
+
+fn literal(expression: &Expression) -> bool {
+    matches!(
+        expression,
+        Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Ratio(_)
+            | Expression::Decimal(_)
+            | Expression::String(_)
+            | Expression::Bytes(_)
+            | Expression::Keyword(_)
+            | Expression::Bool(_)
+            | Expression::Nil
+    )
+}
+
+fn exact(expression: &Expression) -> bool {
+    matches!(
+        expression,
+        Expression::Integer(_) | Expression::Ratio(_) | Expression::Decimal(_)
+    )
+}
+
+fn definitions(expression: &Expression, names: &mut Vec<String>) {
+    match expression {
+        Expression::Call(Call {
+            function,
+            arguments,
+        }) => {
+            if let (Expression::Symbol(form), Some(Expression::Symbol(name))) =
+                (function.as_ref(), arguments.front())
+            {
+                if (form == "def" || form == "defn") && !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            definitions(function, names);
+            arguments.iter().for_each(|e| definitions(e, names));
+        }
+        Expression::Array(expressions) => expressions.iter().for_each(|e| definitions(e, names)),
+        Expression::Map(map) => map.iter().for_each(|(k, v)| {
+            definitions(k, names);
+            definitions(v, names);
+        }),
+        Expression::Deref(e) => definitions(e, names),
+        _ => {}
+    }
+}
+
+struct Optimizer<'a> {
+    env: &'a Environment,
+    changes: Vec<String>,
+    /// The `*precision*` float arithmetic runs at, or `None` where it depends
+    /// on the caller, such as in function bodies and `with-precision`.
+    precision: Option<Option<u32>>,
+}
+
+impl Optimizer<'_> {
+    fn builtin(&self, name: &str, shadowed: &[String]) -> bool {
+        !shadowed.iter().any(|s| s == name)
+            && matches!(
+                self.env.get(name),
+                Some(Expression::NativeFunction(_) | Expression::SpecialForm(_))
+            )
+    }
+
+    fn numeric(&self, expression: &Expression, shadowed: &[String]) -> bool {
+        match expression {
+            Expression::Float(_) => {
+                self.precision.is_some() && !shadowed.iter().any(|s| s == "*precision*")
+            }
+            e => exact(e),
+        }
+    }
+
+    fn dynamic<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let precision = self.precision.take();
+        let result = f(self);
+        self.precision = precision;
+        result
+    }
+
+    fn report(&mut self, action: &str, before: &Expression, after: &Expression) {
+        self.changes
+            .push(format!("{} {} into {}", action, before, after));
+    }
+
+    fn expression(&mut self, expression: Expression, shadowed: &[String]) -> Expression {
+        match expression {
+            Expression::Call(call) => self.call(call, shadowed),
+            Expression::Array(expressions) => Expression::Array(
+                expressions
+                    .into_iter()
+                    .map(|e| self.expression(e, shadowed))
+                    .collect(),
+            ),
+            Expression::Map(map) => Expression::Map(
+                map.into_iter()
+                    .map(|(k, v)| (self.expression(k, shadowed), self.expression(v, shadowed)))
+                    .collect(),
+            ),
+            Expression::Deref(e) => Expression::Deref(Box::new(self.expression(*e, shadowed))),
+            e => e,
+        }
+    }
+
+    fn expressions(
+        &mut self,
+        expressions: Vector<Expression>,
+        shadowed: &[String],
+    ) -> Vector<Expression> {
+        expressions
+            .into_iter()
+            .map(|e| self.expression(e, shadowed))
+            .collect()
+    }
+
+    fn call(&mut self, call: Call, shadowed: &[String]) -> Expression {
+        let name = match call.function.as_ref() {
+            Expression::Symbol(name) if self.builtin(name, shadowed) => name.clone(),
+            _ => return Expression::Call(self.arguments(call, shadowed)),
+        };
+        match name.as_str() {
+            "+" => self.fold(Operation::Add, call, shadowed),
+            "-" => self.fold(Operation::Subtract, call, shadowed),
+            "*" => self.fold(Operation::Multiply, call, shadowed),
+            "/" => self.fold(Operation::Divide, call, shadowed),
+            "if" => self.conditional(call, shadowed),
+            "when" => self.when(call, shadowed),
+            "do" => self.sequence(call, shadowed),
+            "->" => self.thread_first(call, shadowed),
+            "fn" => self.dynamic(|o| o.function(call, 0, shadowed)),
+            "defn" => self.dynamic(|o| o.function(call, 1, shadowed)),
+            "with-precision" => self.dynamic(|o| Expression::Call(o.arguments(call, shadowed))),
+            "let" | "for" | "pfor" | "with-tasks" => self.bindings(call, shadowed),
+            "def" => self.define(call, shadowed),
+            "bound?" | "import" => Expression::Call(call),
+            _ => Expression::Call(self.arguments(call, shadowed)),
+        }
+    }
+
+    fn arguments(&mut self, call: Call, shadowed: &[String]) -> Call {
+        let Call {
+            function,
+            arguments,
+        } = call;
+        Call {
+            function: Box::new(self.expression(*function, shadowed)),
+            arguments: self.expressions(arguments, shadowed),
+        }
+    }
+
+    fn fold(&mut self, operation: Operation, call: Call, shadowed: &[String]) -> Expression {
+        let call = self.arguments(call, shadowed);
+        if !call.arguments.iter().all(|e| self.numeric(e, shadowed)) {
+            return Expression::Call(call);
+        }
+        let mut arguments = call.arguments.clone().into_iter();
+        let initial = match (arguments.next(), operation) {
+            (Some(e), _) => e,
+            (None, Operation::Add) => Expression::Integer(Integer::from(0)),
+            (None, Operation::Multiply) => Expression::Integer(Integer::from(1)),
+            (None, _) => return Expression::Call(call),
+        };
+        let before = Expression::Call(call);
+        let precision = self.precision.flatten();
+        let result = arguments.try_fold(initial, |lhs, rhs| {
+            arithmetic_with_precision(operation, lhs, rhs, precision)
+        });
+        match result {
+            Ok(after) if crate::sandbox::fits(self.env, &after) => {
+                self.report("folded", &before, &after);
+                after
+            }
+            _ => before,
+        }
+    }
+
+    fn conditional(&mut self, call: Call, shadowed: &[String]) -> Expression {
+        let call = self.arguments(call, shadowed);
+        if call.arguments.len() != 3 || !literal(&call.arguments[0]) {
+            return Expression::Call(call);
+        }
+        let after = if truthy(&call.arguments[0]) {
+            call.arguments[1].clone()
+        } else {
+            call.arguments[2].clone()
+        };
+        self.report("simplified", &Expression::Call(call), &after);
+        after
+    }
+
+    fn when(&mut self, call: Call, shadowed: &[String]) -> Expression {
+        let call = self.arguments(call, shadowed);
+        match call.arguments.front() {
+            Some(condition) if literal(condition) => {}
+            _ => return Expression::Call(call),
+        }
+        let body = call.arguments.clone().split_off(1);
+        let after = if !truthy(&call.arguments[0]) || body.is_empty() {
+            Expression::Nil
+        } else if self.builtin("do", shadowed) {
+            Expression::Call(Call {
+                function: Box::new(Expression::Symbol("do".to_string())),
+                arguments: self.prune(body),
+            })
+        } else {
+            return Expression::Call(call);
+        };
+        self.report("simplified", &Expression::Call(call), &after);
+        after
+    }
+
+    fn prune(&mut self, body: Vector<Expression>) -> Vector<Expression> {
+        let last = body.len().saturating_sub(1);
+        body.into_iter()
+            .enumerate()
+            .filter(|(i, e)| {
+                let dead = *i < last && literal(e);
+                if dead {
+                    self.changes.push(format!("removed unused {}", e));
+                }
+                !dead
+            })
+            .map(|(_, e)| e)
+            .collect()
+    }
+
+    fn sequence(&mut self, call: Call, shadowed: &[String]) -> Expression {
+        let mut call = self.arguments(call, shadowed);
+        call.arguments = self.prune(call.arguments);
+        Expression::Call(call)
+    }
+
+    fn thread_first(&mut self, call: Call, shadowed: &[String]) -> Expression {
+        let mut forms = call.arguments.clone();
+        let Some(mut after) = forms.pop_front() else {
+            return Expression::Call(call);
+        };
+        for form in forms {
+            after = match form {
+                Expression::Call(Call {
+                    function,
+                    mut arguments,
+                }) => {
+                    arguments.push_front(after);
+                    Expression::Call(Call {
+                        function,
+                        arguments,
+                    })
+                }
+                function => Expression::Call(Call {
+                    function: Box::new(function),
+                    arguments: vector![after],
+                }),
+            };
+        }
+        self.report("inlined", &Expression::Call(call), &after);
+        self.expression(after, shadowed)
+    }
+
+    fn function(&mut self, call: Call, names: usize, shadowed: &[String]) -> Expression {
+        let Call {
+            function,
+            arguments,
+        } = call;
+        let mut shadowed = shadowed.to_vec();
+        let mut arguments = arguments;
+        let rest = arguments.split_off(names.min(arguments.len()));
+        arguments
+            .iter()
+            .for_each(|name| pattern_symbols(name, &mut shadowed));
+        let rest = match rest.front() {
+            Some(Expression::Array(parameters)) => {
+                let mut shadowed = shadowed.clone();
+                parameters
+                    .iter()
+                    .for_each(|p| pattern_symbols(p, &mut shadowed));
+                let mut body = rest.clone();
+                let parameters = body.pop_front().unwrap();
+                let mut rest = vector![parameters];
+                rest.append(self.expressions(body, &shadowed));
+                rest
+            }
+            _ => rest
+                .into_iter()
+                .map(|arm| match arm {
+                    Expression::Call(Call {
+                        function,
+                        arguments,
+                    }) => {
+                        let mut shadowed = shadowed.clone();
+                        pattern_symbols(&function, &mut shadowed);
+                        Expression::Call(Call {
+                            function,
+                            arguments: self.expressions(arguments, &shadowed),
+                        })
+                    }
+                    arm => arm,
+                })
+                .collect(),
+        };
+        arguments.append(rest);
+        Expression::Call(Call {
+            function,
+            arguments,
+        })
+    }
+
+    fn bindings(&mut self, call: Call, shadowed: &[String]) -> Expression {
+        let Call {
+            function,
+            mut arguments,
+        } = call;
+        let bindings = match arguments.pop_front() {
+            Some(Expression::Array(bindings)) => bindings,
+            Some(other) => {
+                arguments.push_front(other);
+                return Expression::Call(Call {
+                    function,
+                    arguments,
+                });
+            }
+            None => {
+                return Expression::Call(Call {
+                    function,
+                    arguments,
+                })
+            }
+        };
+        let mut shadowed = shadowed.to_vec();
+        let mut optimized = Vector::new();
+        let mut bindings = bindings.into_iter();
+        while let Some(pattern) = bindings.next() {
+            let value = bindings
+                .next()
+                .map(|value| self.expression(value, &shadowed));
+            pattern_symbols(&pattern, &mut shadowed);
+            optimized.push_back(pattern);
+            if let Some(value) = value {
+                optimized.push_back(value);
+            }
+        }
+        let mut arguments = self.expressions(arguments, &shadowed);
+        arguments.push_front(Expression::Array(optimized));
+        Expression::Call(Call {
+            function,
+            arguments,
+        })
+    }
+
+    fn define(&mut self, call: Call, shadowed: &[String]) -> Expression {
+        let Call {
+            function,
+            mut arguments,
+        } = call;
+        let rest = arguments.split_off(1.min(arguments.len()));
+        if matches!(arguments.front(), Some(Expression::Symbol(name)) if name == "*precision*") {
+            self.precision = None;
+        }
+        arguments.append(self.expressions(rest, shadowed));
+        Expression::Call(Call {
+            function,
+            arguments,
+        })
+    }
+}
+
+pub fn optimize(env: &Environment, expression: Expression) -> (Expression, Vec<String>) {
+    let mut optimizer = Optimizer {
+        env,
+        changes: Vec::new(),
+        precision: precision_from(env).ok(),
+    };
+    // Names the program defines shadow builtins everywhere, since a `def`
+    // may run before any call that follows it.
+    let mut shadowed = Vec::new();
+    definitions(&expression, &mut shadowed);
+    let expression = optimizer.expression(expression, &shadowed);
+    (expression, optimizer.changes)
+}
@@ -1,8 +1,8 @@
 extern crate alloc;
 
 use crate::expression::Call;
-use crate::Expression;
 use crate::tokenizer::Token;
+use crate::Expression;
 use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
@@ -34,10 +34,13 @@ fn call(tokens: &[Token]) -> (&[Token], Expression) {
             }
         }
     }
-    (tokens, Expression::Call(Call {
-        function,
-        arguments,
-    }))
+    (
+        tokens,
+        Expression::Call(Call {
+            function,
+            arguments,
+        }),
+    )
 }
 
 fn array(mut tokens: &[Token]) -> (&[Token], Expression) {
@@ -100,9 +103,11 @@ pub fn parse(tokens: &[Token]) -> (&[Token], Expression) {
         Some(Token::NamespacedSymbol(s)) => (rest(tokens), Expression::NamespacedSymbol(s.clone())),
         Some(Token::Keyword(s)) => (rest(tokens), Expression::Keyword(s.clone())),
         Some(Token::String(s)) => (rest(tokens), Expression::String(s.clone())),
+        Some(Token::Bytes(b)) => (rest(tokens), Expression::Bytes(b.clone())),
         Some(Token::Integer(i)) => (rest(tokens), Expression::Integer(i.clone())),
         Some(Token::Float(f)) => (rest(tokens), Expression::Float(f.clone())),
         Some(Token::Ratio(r)) => (rest(tokens), Expression::Ratio(r.clone())),
+        Some(Token::Decimal(d)) => (rest(tokens), Expression::Decimal(d.clone())),
         Some(Token::LeftParen) => call(rest(tokens)),
         Some(Token::LeftBracket) => array(rest(tokens)),
         Some(Token::LeftBrace) => map(rest(tokens)),
@@ -111,7 +116,6 @@ pub fn parse(tokens: &[Token]) -> (&[Token], Expression) {
         Some(t) => panic!("Unexpected token {:?}", t),
         None => panic!("Expected token got None"),
     }
-
 }
 
 pub fn parse_all(mut tokens: &[Token]) -> Vec<Expression> {
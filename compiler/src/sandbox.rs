@@ -0,0 +1,201 @@
+extern crate alloc;
+
+use crate::effect::{limit, Effect};
+use crate::expression::{Environment, Result};
+use crate::{Expression, NativeType};
+use alloc::format;
+use alloc::string::ToString;
+use core::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub steps: Option<u64>,
+    pub collection_size: Option<usize>,
+    pub string_size: Option<usize>,
+    pub integer_bits: Option<u32>,
+    pub time: Option<Duration>,
+}
+
+/// The binding that holds the sandbox state. Symbols can never contain
+/// whitespace, so sandboxed code cannot read, shadow or redefine it.
+pub const KEY: &str = "sandbox limits";
+
+struct Sandbox {
+    limits: Limits,
+    steps: u64,
+    deadline: Option<Instant>,
+}
+
+pub fn restrict(env: &Environment, capabilities: &[&str]) -> Environment {
+    let mut restricted = Environment::new();
+    for capability in capabilities {
+        let mut path = capability.split('/');
+        let name = path.next().unwrap_or_default();
+        match (path.next(), env.get(name)) {
+            (None, Some(value)) => {
+                restricted.insert(name.to_string(), value.clone());
+            }
+            (Some(member), Some(Expression::Module(module))) => {
+                let Some(value) = module.get(member) else {
+                    continue;
+                };
+                let mut partial = match restricted.get(name) {
+                    Some(Expression::Module(partial)) => partial.clone(),
+                    _ => Environment::new(),
+                };
+                partial.insert(member.to_string(), value.clone());
+                restricted.insert(name.to_string(), Expression::Module(partial));
+            }
+            _ => {}
+        }
+    }
+    restricted
+}
+
+pub fn limited(mut env: Environment, limits: Limits) -> Environment {
+    let sandbox = Sandbox {
+        limits,
+        steps: 0,
+        deadline: limits.time.map(|time| Instant::now() + time),
+    };
+    env.insert(
+        KEY.to_string(),
+        Expression::NativeType(NativeType::new(sandbox, "sandbox".to_string())),
+    );
+    env
+}
+
+async fn with_sandbox<T>(
+    env: &Environment,
+    f: impl FnOnce(&mut Sandbox) -> core::result::Result<T, Effect>,
+) -> core::result::Result<Option<T>, Effect> {
+    let Some(Expression::NativeType(native)) = env.get(KEY) else {
+        return Ok(None);
+    };
+    let mut value = native.value.lock().await;
+    match value.downcast_mut::<Sandbox>() {
+        Some(sandbox) => f(sandbox).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub(crate) async fn step(env: &Environment) -> core::result::Result<(), Effect> {
+    with_sandbox(env, |sandbox| {
+        sandbox.steps += 1;
+        if let Some(steps) = sandbox.limits.steps {
+            if sandbox.steps > steps {
+                return Err(limit(&format!("Exceeded limit of {} steps", steps)));
+            }
+        }
+        if let Some(deadline) = sandbox.deadline {
+            if Instant::now() >= deadline {
+                return Err(limit("Exceeded time limit"));
+            }
+        }
+        Ok(())
+    })
+    .await?;
+    Ok(())
+}
+
+pub async fn allocate(env: &Environment, size: usize) -> core::result::Result<(), Effect> {
+    with_sandbox(env, |sandbox| match sandbox.limits.collection_size {
+        Some(limit_size) if size > limit_size => Err(limit(&format!(
+            "Exceeded collection size limit of {}",
+            limit_size
+        ))),
+        _ => Ok(()),
+    })
+    .await?;
+    Ok(())
+}
+
+fn within(limits: Limits, value: &Expression) -> core::result::Result<(), Effect> {
+    let size = match value {
+        Expression::Array(array) => array.len(),
+        Expression::Map(map) => map.len(),
+        Expression::Bytes(bytes) => bytes.len(),
+        _ => 0,
+    };
+    match (value, limits) {
+        (
+            _,
+            Limits {
+                collection_size: Some(collection_size),
+                ..
+            },
+        ) if size > collection_size => Err(limit(&format!(
+            "Exceeded collection size limit of {}",
+            collection_size
+        ))),
+        (
+            Expression::String(s),
+            Limits {
+                string_size: Some(string_size),
+                ..
+            },
+        ) if s.len() > string_size => Err(limit(&format!(
+            "Exceeded string size limit of {}",
+            string_size
+        ))),
+        (
+            Expression::Integer(i),
+            Limits {
+                integer_bits: Some(integer_bits),
+                ..
+            },
+        ) if i.significant_bits() > integer_bits => Err(limit(&format!(
+            "Exceeded integer size limit of {} bits",
+            integer_bits
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) async fn check(
+    env: &Environment,
+    value: &Expression,
+) -> core::result::Result<(), Effect> {
+    with_sandbox(env, |sandbox| within(sandbox.limits, value)).await?;
+    Ok(())
+}
+
+/// Whether `value` is within the limits of the sandbox in `env`, for callers
+/// that can't wait on the sandbox. A busy sandbox counts as exceeded.
+pub(crate) fn fits(env: &Environment, value: &Expression) -> bool {
+    let Some(Expression::NativeType(native)) = env.get(KEY) else {
+        return true;
+    };
+    let Ok(sandbox) = native.value.try_lock() else {
+        return false;
+    };
+    match sandbox.downcast_ref::<Sandbox>() {
+        Some(sandbox) => within(sandbox.limits, value).is_ok(),
+        None => true,
+    }
+}
+
+pub async fn evaluate(env: Environment, expression: Expression) -> Result {
+    let (expression, _) = crate::optimize(&env, expression);
+    let deadline = with_sandbox(&env, |sandbox| Ok(sandbox.deadline))
+        .await?
+        .flatten();
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, crate::evaluate(env, expression))
+            .await
+            .map_err(|_| limit("Exceeded time limit"))?,
+        None => crate::evaluate(env, expression).await,
+    }
+}
+
+pub async fn evaluate_source(mut env: Environment, source: &str) -> Result {
+    let tokens = crate::tokenize(source)?;
+    let mut result = Expression::Nil;
+    for expression in crate::parse_all(&tokens) {
+        let (new_env, new_result) = evaluate(env, expression).await?;
+        env = new_env;
+        result = new_result;
+    }
+    Ok((env, result))
+}
@@ -0,0 +1,375 @@
+extern crate alloc;
+
+use crate::agent::{Action, Agent};
+use crate::effect::{error, Effect};
+use crate::expression::{Call, Environment};
+use crate::{evaluate_expressions, extract, Expression, NativeType};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use im::Vector;
+use tokio::sync::Mutex;
+
+type Result<T> = core::result::Result<T, Effect>;
+
+const MAX_RETRIES: usize = 10_000;
+
+static CLOCK: AtomicU64 = AtomicU64::new(0);
+
+pub struct Versioned {
+    pub version: u64,
+    pub value: Expression,
+}
+
+pub struct Ref(pub Arc<Mutex<Versioned>>);
+
+impl Ref {
+    pub fn new(value: Expression) -> Ref {
+        Ref(Arc::new(Mutex::new(Versioned { version: 0, value })))
+    }
+}
+
+impl PartialEq for Ref {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::hash::Hash for Ref {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+impl core::fmt::Debug for Ref {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#ref({:?})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl core::fmt::Display for Ref {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#ref({:?})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl Eq for Ref {}
+
+impl PartialOrd for Ref {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ref {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        Arc::as_ptr(&self.0).cmp(&Arc::as_ptr(&other.0))
+    }
+}
+
+impl Clone for Ref {
+    fn clone(&self) -> Self {
+        Ref(Arc::clone(&self.0))
+    }
+}
+
+struct Commute {
+    env: Environment,
+    function: Expression,
+    arguments: Vector<Expression>,
+}
+
+struct State {
+    read_point: u64,
+    values: BTreeMap<Ref, Expression>,
+    altered: BTreeSet<Ref>,
+    ensured: BTreeSet<Ref>,
+    commutes: BTreeMap<Ref, Vec<Commute>>,
+    sends: Vec<(Agent, Action)>,
+    conflicted: bool,
+}
+
+#[derive(Clone)]
+struct Transaction(Arc<Mutex<State>>);
+
+/// Environment key holding the running transaction. It contains a space so
+/// no symbol can name it, and function calls only see it while their caller
+/// does.
+pub const TRANSACTION: &str = "running transaction";
+
+async fn current(env: &Environment) -> Result<Transaction> {
+    let Some(Expression::NativeType(native)) = env.get(TRANSACTION) else {
+        return Err(error("No transaction running"));
+    };
+    let value = native.value.lock().await;
+    value
+        .downcast_ref::<Transaction>()
+        .cloned()
+        .ok_or_else(|| error("No transaction running"))
+}
+
+async fn apply(
+    env: Environment,
+    function: Expression,
+    value: Expression,
+    arguments: Vector<Expression>,
+) -> Result<Expression> {
+    let mut quoted: Vector<Expression> = arguments
+        .into_iter()
+        .map(|argument| Expression::Quote(Box::new(argument)))
+        .collect();
+    quoted.push_front(Expression::Quote(Box::new(value)));
+    let (_, value) = crate::evaluate(
+        env,
+        Expression::Call(Call {
+            function: Box::new(function),
+            arguments: quoted,
+        }),
+    )
+    .await?;
+    Ok(value)
+}
+
+impl Transaction {
+    fn new() -> Transaction {
+        Transaction(Arc::new(Mutex::new(State {
+            read_point: CLOCK.load(Ordering::SeqCst),
+            values: BTreeMap::new(),
+            altered: BTreeSet::new(),
+            ensured: BTreeSet::new(),
+            commutes: BTreeMap::new(),
+            sends: Vec::new(),
+            conflicted: false,
+        })))
+    }
+
+    async fn read(&self, reference: &Ref) -> Result<Expression> {
+        let mut state = self.0.lock().await;
+        if let Some(value) = state.values.get(reference) {
+            return Ok(value.clone());
+        }
+        let current = reference.0.lock().await;
+        if current.version > state.read_point {
+            state.conflicted = true;
+            return Err(error("Transaction conflict"));
+        }
+        Ok(current.value.clone())
+    }
+
+    async fn write(&self, reference: &Ref, value: Expression) -> Result<()> {
+        let mut state = self.0.lock().await;
+        if state.commutes.contains_key(reference) {
+            return Err(error(
+                "Cannot set a ref after commute in the same transaction",
+            ));
+        }
+        state.values.insert(reference.clone(), value);
+        state.altered.insert(reference.clone());
+        Ok(())
+    }
+
+    /// Commute functions rerun against the latest committed values before
+    /// any ref is locked, so they can deref refs like the rest of the
+    /// transaction. If a commuted ref changes before the locks are taken the
+    /// commit fails and the transaction retries.
+    async fn commit(&self) -> Result<bool> {
+        let (references, commutes) = {
+            let mut state = self.0.lock().await;
+            let references: BTreeSet<Ref> = state
+                .altered
+                .iter()
+                .chain(state.ensured.iter())
+                .chain(state.commutes.keys())
+                .cloned()
+                .collect();
+            (references, core::mem::take(&mut state.commutes))
+        };
+        let mut commuted = BTreeMap::new();
+        for (reference, commutes) in commutes {
+            let (version, mut value) = {
+                let current = reference.0.lock().await;
+                (current.version, current.value.clone())
+            };
+            for commute in commutes {
+                self.0
+                    .lock()
+                    .await
+                    .values
+                    .insert(reference.clone(), value.clone());
+                value = match apply(commute.env, commute.function, value, commute.arguments).await {
+                    Ok(value) => value,
+                    Err(_) if self.0.lock().await.conflicted => return Ok(false),
+                    Err(effect) => return Err(effect),
+                };
+            }
+            self.0
+                .lock()
+                .await
+                .values
+                .insert(reference.clone(), value.clone());
+            commuted.insert(reference, (version, value));
+        }
+        let mut state = self.0.lock().await;
+        let mut guards = Vec::new();
+        for reference in &references {
+            guards.push(reference.0.lock().await);
+        }
+        for (reference, guard) in references.iter().zip(guards.iter()) {
+            let checked = state.altered.contains(reference) || state.ensured.contains(reference);
+            let moved = commuted
+                .get(reference)
+                .map_or(false, |(version, _)| guard.version != *version);
+            if moved || (checked && guard.version > state.read_point) {
+                state.conflicted = true;
+                return Ok(false);
+            }
+        }
+        let version = CLOCK.fetch_add(1, Ordering::SeqCst) + 1;
+        for (reference, guard) in references.iter().zip(guards.iter_mut()) {
+            let value = match commuted.remove(reference) {
+                Some((_, value)) => Some(value),
+                None if state.altered.contains(reference) => Some(state.values[reference].clone()),
+                None => None,
+            };
+            if let Some(value) = value {
+                guard.value = value;
+                guard.version = version;
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Holds an agent action sent inside a transaction until the transaction
+/// commits, so a retry doesn't send it again and a failed transaction never
+/// sends it. Outside a transaction the action is handed back.
+pub(crate) async fn hold(env: &Environment, agent: &Agent, action: Action) -> Option<Action> {
+    let Ok(transaction) = current(env).await else {
+        return Some(action);
+    };
+    transaction
+        .0
+        .lock()
+        .await
+        .sends
+        .push((agent.clone(), action));
+    None
+}
+
+pub async fn deref(env: &Environment, reference: &Ref) -> Result<Expression> {
+    match current(env).await {
+        Ok(transaction) => transaction.read(reference).await,
+        Err(_) => Ok(reference.0.lock().await.value.clone()),
+    }
+}
+
+pub async fn reference(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    Ok((env, Expression::Ref(Ref::new(args[0].clone()))))
+}
+
+pub async fn dosync(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let body = Expression::Call(Call {
+        function: Box::new(Expression::Symbol("do".to_string())),
+        arguments: args,
+    });
+    if current(&env).await.is_ok() {
+        return crate::evaluate(env, body).await;
+    }
+    for _ in 0..MAX_RETRIES {
+        let transaction = Transaction::new();
+        let native = NativeType::new(transaction.clone(), "transaction".to_string());
+        let scoped = env.update(TRANSACTION.to_string(), Expression::NativeType(native));
+        match crate::evaluate(scoped, body.clone()).await {
+            Ok((env, value)) => {
+                if transaction.commit().await? {
+                    let sends = core::mem::take(&mut transaction.0.lock().await.sends);
+                    for (agent, action) in sends {
+                        agent.dispatch(action)?;
+                    }
+                    return Ok((env.without(TRANSACTION), value));
+                }
+            }
+            Err(effect) => {
+                if !transaction.0.lock().await.conflicted {
+                    return Err(effect);
+                }
+            }
+        }
+        tokio::task::yield_now().await;
+    }
+    Err(error("Transaction retried too many times"))
+}
+
+pub async fn alter(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let transaction = current(&env).await?;
+    let reference = extract::reference(args[0].clone())?;
+    let value = transaction.read(&reference).await?;
+    let value = apply(env.clone(), args[1].clone(), value, args.skip(2)).await?;
+    transaction.write(&reference, value.clone()).await?;
+    Ok((env, value))
+}
+
+pub async fn ref_set(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let transaction = current(&env).await?;
+    let reference = extract::reference(args[0].clone())?;
+    transaction.read(&reference).await?;
+    transaction.write(&reference, args[1].clone()).await?;
+    Ok((env, args[1].clone()))
+}
+
+pub async fn commute(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let transaction = current(&env).await?;
+    let reference = extract::reference(args[0].clone())?;
+    let function = args[1].clone();
+    let arguments = args.skip(2);
+    let local = transaction.0.lock().await.values.get(&reference).cloned();
+    let value = match local {
+        Some(value) => value,
+        None => reference.0.lock().await.value.clone(),
+    };
+    let value = apply(env.clone(), function.clone(), value, arguments.clone()).await?;
+    let mut state = transaction.0.lock().await;
+    state.values.insert(reference.clone(), value.clone());
+    if !state.altered.contains(&reference) {
+        state.commutes.entry(reference).or_default().push(Commute {
+            env: env.clone(),
+            function,
+            arguments,
+        });
+    }
+    Ok((env, value))
+}
+
+pub async fn ensure(
+    env: Environment,
+    args: Vector<Expression>,
+) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let transaction = current(&env).await?;
+    let reference = extract::reference(args[0].clone())?;
+    let value = transaction.read(&reference).await?;
+    transaction.0.lock().await.ensured.insert(reference);
+    Ok((env, value))
+}
@@ -0,0 +1,316 @@
+extern crate alloc;
+
+use crate::effect::{error, Effect};
+use crate::expression::{Call, Environment};
+use crate::{extract, pattern_match, Expression, NativeType};
+use alloc::boxed::Box;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::sync::atomic::{AtomicBool, Ordering};
+use futures::future::Either;
+use futures::stream::{self, FuturesUnordered, StreamExt, TryStreamExt};
+use im::Vector;
+use tokio::sync::{watch, Notify};
+use tokio::task::AbortHandle;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Value(Expression),
+    Failed(Effect),
+    Cancelled,
+}
+
+pub struct State {
+    abort: AbortHandle,
+    stop: Arc<Notify>,
+    cancelled: AtomicBool,
+    outcome: watch::Receiver<Option<Outcome>>,
+}
+
+pub struct Task(pub Arc<State>);
+
+fn settle(
+    sender: watch::Sender<Option<Outcome>>,
+    result: core::result::Result<Expression, Effect>,
+) {
+    let outcome = match result {
+        Ok(value) => Outcome::Value(value),
+        Err(effect) => Outcome::Failed(effect),
+    };
+    let _ = sender.send(Some(outcome));
+}
+
+impl Task {
+    fn new(
+        abort: AbortHandle,
+        stop: Arc<Notify>,
+        outcome: watch::Receiver<Option<Outcome>>,
+    ) -> Task {
+        Task(Arc::new(State {
+            abort,
+            stop,
+            cancelled: AtomicBool::new(false),
+            outcome,
+        }))
+    }
+
+    pub fn spawn<F>(future: F) -> Task
+    where
+        F: Future<Output = core::result::Result<Expression, Effect>> + Send + 'static,
+    {
+        let (sender, receiver) = watch::channel(None);
+        let handle = tokio::spawn(async move { settle(sender, future.await) });
+        Task::new(handle.abort_handle(), Arc::new(Notify::new()), receiver)
+    }
+
+    /// Runs the future on a blocking thread. Aborting can't interrupt that
+    /// thread, so a cancelled task stops the next time its body waits, such
+    /// as on a channel or a sleep; pure computation runs to the end.
+    pub fn spawn_blocking<F>(future: F) -> Task
+    where
+        F: Future<Output = core::result::Result<Expression, Effect>> + Send + 'static,
+    {
+        let (sender, receiver) = watch::channel(None);
+        let runtime = tokio::runtime::Handle::current();
+        let stop = Arc::new(Notify::new());
+        let stopped = stop.clone();
+        let handle = tokio::task::spawn_blocking(move || {
+            let stopped = stopped.notified();
+            let raced = futures::future::select(Box::pin(future), Box::pin(stopped));
+            if let Either::Left((result, _)) = runtime.block_on(raced) {
+                settle(sender, result);
+            }
+        });
+        Task::new(handle.abort_handle(), stop, receiver)
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.abort.abort();
+        self.0.stop.notify_one();
+    }
+
+    pub fn poll(&self) -> Option<Outcome> {
+        match self.0.outcome.borrow().clone() {
+            Some(outcome) => Some(outcome),
+            None if self.0.cancelled.load(Ordering::SeqCst) => Some(Outcome::Cancelled),
+            None => None,
+        }
+    }
+
+    /// Waits until the task has stopped running, whether it finished or was
+    /// cancelled. Unlike `wait`, this doesn't return early on cancellation.
+    pub async fn join(&self) {
+        let mut receiver = self.0.outcome.clone();
+        while receiver.changed().await.is_ok() {}
+    }
+
+    pub async fn wait(&self) -> Outcome {
+        let mut receiver = self.0.outcome.clone();
+        loop {
+            if let Some(outcome) = self.poll() {
+                return outcome;
+            }
+            if receiver.changed().await.is_err() {
+                return self.poll().unwrap_or(Outcome::Cancelled);
+            }
+        }
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::hash::Hash for Task {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+impl core::fmt::Debug for Task {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#task({:?})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl core::fmt::Display for Task {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#task({:?})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl Eq for Task {}
+
+impl PartialOrd for Task {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Task {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        Arc::as_ptr(&self.0).cmp(&Arc::as_ptr(&other.0))
+    }
+}
+
+impl Clone for Task {
+    fn clone(&self) -> Self {
+        Task(Arc::clone(&self.0))
+    }
+}
+
+/// The binding that collects the tasks spawned while evaluating a form, so
+/// they can all be cancelled if it is interrupted. Like the sandbox binding,
+/// code cannot name it.
+pub const TRACKED: &str = "tracked tasks";
+
+/// Tasks spawned in a scope. A task is also recorded in every enclosing
+/// scope, so cancelling an outer scope reaches tasks spawned in inner ones.
+struct Tracker {
+    tasks: Vec<Task>,
+    parent: Option<NativeType>,
+}
+
+pub fn tracked(mut env: Environment) -> (Environment, NativeType) {
+    let parent = match env.get(TRACKED) {
+        Some(Expression::NativeType(parent)) => Some(parent.clone()),
+        _ => None,
+    };
+    let tracker = Tracker {
+        tasks: Vec::new(),
+        parent,
+    };
+    let tasks = NativeType::new(tracker, "tasks".to_string());
+    env.insert(TRACKED.to_string(), Expression::NativeType(tasks.clone()));
+    (env, tasks)
+}
+
+pub async fn track(env: &Environment, task: &Task) {
+    let mut next = match env.get(TRACKED) {
+        Some(Expression::NativeType(native)) => Some(native.clone()),
+        _ => None,
+    };
+    while let Some(native) = next {
+        let mut value = native.value.lock().await;
+        let Some(tracker) = value.downcast_mut::<Tracker>() else {
+            return;
+        };
+        tracker.tasks.retain(|task| task.poll().is_none());
+        tracker.tasks.push(task.clone());
+        next = tracker.parent.clone();
+    }
+}
+
+pub async fn cancel_tracked(tasks: &NativeType) {
+    let mut value = tasks.value.lock().await;
+    if let Some(tracker) = value.downcast_mut::<Tracker>() {
+        tracker.tasks.drain(..).for_each(|task| task.cancel());
+    }
+}
+
+struct Group(Vec<Task>);
+
+impl Group {
+    async fn stop(&self) {
+        self.0.iter().for_each(Task::cancel);
+        for task in &self.0 {
+            task.join().await;
+        }
+    }
+}
+
+impl Drop for Group {
+    // Only reached when with-tasks itself is cancelled mid-flight, as there is
+    // no way to wait for the children from here.
+    fn drop(&mut self) {
+        self.0.iter().for_each(Task::cancel);
+    }
+}
+
+/// Runs each future as its own task, at most `limit` at a time, and returns
+/// their values in order. The first failure cancels the tasks still running.
+pub async fn parallel<F>(
+    futures: Vec<F>,
+    limit: usize,
+) -> core::result::Result<Vector<Expression>, Effect>
+where
+    F: Future<Output = core::result::Result<Expression, Effect>> + Send + 'static,
+{
+    let mut group = Group(Vec::new());
+    let result = stream::iter(futures)
+        .map(|future| {
+            let task = Task::spawn(future);
+            group.0.push(task.clone());
+            async move {
+                match task.wait().await {
+                    Outcome::Value(value) => Ok(value),
+                    Outcome::Failed(effect) => Err(effect),
+                    Outcome::Cancelled => Err(error("Task cancelled")),
+                }
+            }
+        })
+        .buffered(limit)
+        .try_collect()
+        .await;
+    group.stop().await;
+    result
+}
+
+async fn supervise(tasks: &[Task]) -> core::result::Result<(), Effect> {
+    let mut pending: FuturesUnordered<_> = tasks.iter().map(Task::wait).collect();
+    while let Some(outcome) = pending.next().await {
+        if let Outcome::Failed(effect) = outcome {
+            return Err(effect);
+        }
+    }
+    Ok(())
+}
+
+pub async fn with_tasks(
+    env: Environment,
+    args: Vector<Expression>,
+) -> core::result::Result<(Environment, Expression), Effect> {
+    let original_env = env.clone();
+    let bindings = extract::array(args[0].clone())?;
+    if bindings.len() % 2 != 0 {
+        return Err(error("with-tasks expects pairs of names and expressions"));
+    }
+    let mut env = env;
+    let mut group = Group(Vec::new());
+    let (_, spawned) = tracked(env.clone());
+    let mut bindings = bindings.into_iter();
+    while let (Some(pattern), Some(expression)) = (bindings.next(), bindings.next()) {
+        let task = Task::spawn({
+            let env = env
+                .without(crate::stm::TRANSACTION)
+                .update(TRACKED.to_string(), Expression::NativeType(spawned.clone()));
+            async move {
+                let (_, value) = crate::evaluate(env, expression).await?;
+                Ok(value)
+            }
+        });
+        group.0.push(task.clone());
+        env = pattern_match(env, pattern, Expression::Task(task))?;
+    }
+    let body = async {
+        let (_, value) = crate::evaluate(
+            env,
+            Expression::Call(Call {
+                function: Box::new(Expression::Symbol("do".to_string())),
+                arguments: args.skip(1),
+            }),
+        )
+        .await?;
+        Ok(value)
+    };
+    let result = futures::future::try_join(body, supervise(&group.0)).await;
+    group.stop().await;
+    cancel_tracked(&spawned).await;
+    let (value, _) = result?;
+    Ok((original_env, value))
+}
@@ -1,9 +1,13 @@
 extern crate alloc;
 
+use crate::binary::from_hex;
+use crate::decimal::Decimal;
+use crate::effect::{error, Effect};
 use crate::numerics::Float;
 use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use bytes::Bytes;
 use rug::{Integer, Rational};
 
 #[derive(PartialEq, Debug)]
@@ -12,9 +16,11 @@ pub enum Token {
     NamespacedSymbol(Vec<String>),
     Keyword(String),
     String(String),
+    Bytes(Bytes),
     Integer(Integer),
     Float(Float),
     Ratio(Rational),
+    Decimal(Decimal),
     LeftParen,
     RightParen,
     LeftBracket,
@@ -76,6 +82,19 @@ fn string(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
     (chars.as_str(), push(tokens, Token::String(string)))
 }
 
+type Result<'a> = core::result::Result<(&'a str, Vec<Token>), Effect>;
+
+fn bytes(input: &str, tokens: Vec<Token>) -> Result {
+    let Some((hex, input)) = input.split_once('"') else {
+        return Err(error("Expected closing '\"' after bytes literal"));
+    };
+    let hex: String = hex.chars().filter(|c| !is_whitespace(*c)).collect();
+    match from_hex(&hex) {
+        Some(bytes) => Ok((input, push(tokens, Token::Bytes(bytes)))),
+        None => Err(error(&format!("Invalid bytes literal #x\"{}\"", hex))),
+    }
+}
+
 fn keyword(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
     let mut chars = input.chars();
     let mut keyword = String::new();
@@ -89,18 +108,22 @@ fn keyword(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
             }
         }
     }
-    (chars.as_str(), push(tokens, Token::Keyword(format!(":{}", keyword))))
+    (
+        chars.as_str(),
+        push(tokens, Token::Keyword(format!(":{}", keyword))),
+    )
 }
 
 fn comment(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
     let mut chars = input.chars();
     while let Some(c) = chars.next() {
-        if c == '\n' { break; }
+        if c == '\n' {
+            break;
+        }
     }
     (chars.as_str(), tokens)
 }
 
-
 fn symbol(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
     let mut chars = input.chars();
     let mut symbol = String::new();
@@ -123,7 +146,7 @@ fn symbol(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
     (chars.as_str(), push(tokens, token))
 }
 
-fn number(input: &str, mut tokens: Vec<Token>, negative: Negative) -> (&str, Vec<Token>) {
+fn number(input: &str, mut tokens: Vec<Token>, negative: Negative) -> Result {
     let mut chars = input.chars();
     let mut number_string = String::new();
     let mut is_float = false;
@@ -135,7 +158,9 @@ fn number(input: &str, mut tokens: Vec<Token>, negative: Negative) -> (&str, Vec
                 chars.next();
             }
             _ if c.is_digit(10) || c == '_' => {
-                if c != '_' { number_string.push(c); }
+                if c != '_' {
+                    number_string.push(c);
+                }
                 chars.next();
             }
             _ => break,
@@ -144,7 +169,12 @@ fn number(input: &str, mut tokens: Vec<Token>, negative: Negative) -> (&str, Vec
     if negative == Negative::Yes {
         number_string.insert(0, '-');
     }
-    let token = if is_float {
+    let token = if chars.clone().next() == Some('M') {
+        chars.next();
+        let decimal = Decimal::from_str(&number_string)
+            .ok_or_else(|| error(&format!("Invalid decimal literal {}M", number_string)))?;
+        Token::Decimal(decimal)
+    } else if is_float {
         Token::Float(Float::from_str(&number_string))
     } else {
         let numerator = number_string.parse::<Integer>().unwrap();
@@ -158,33 +188,45 @@ fn number(input: &str, mut tokens: Vec<Token>, negative: Negative) -> (&str, Vec
                     }
                     _ => Negative::No,
                 };
-                let (input, new_tokens) = number(chars.as_str(), tokens, negative);
+                if !chars.clone().next().is_some_and(|c| c.is_ascii_digit()) {
+                    return Err(error(&format!("Expected denominator after {}/", numerator)));
+                }
+                let (input, new_tokens) = number(chars.as_str(), tokens, negative)?;
                 chars = input.chars();
                 tokens = new_tokens;
-                if let Some(Token::Integer(denominator)) = tokens.pop() {
-                    let rational = Rational::from((numerator, denominator.clone()));
-                    if rational.is_integer() {
-                        Token::Integer(rational.numer().clone())
-                    } else {
-                        Token::Ratio(rational)
+                match tokens.pop() {
+                    Some(Token::Integer(denominator)) if denominator == 0 => {
+                        return Err(error(&format!("Zero denominator in {}/0", numerator)));
+                    }
+                    Some(Token::Integer(denominator)) => {
+                        let rational = Rational::from((numerator, denominator));
+                        if rational.is_integer() {
+                            Token::Integer(rational.numer().clone())
+                        } else {
+                            Token::Ratio(rational)
+                        }
+                    }
+                    _ => {
+                        return Err(error(&format!(
+                            "Expected an integer denominator after {}/",
+                            numerator
+                        )))
                     }
-                } else {
-                    panic!("Expected denominator after '/'");
                 }
             }
             _ => Token::Integer(numerator),
         }
     };
-    (chars.as_str(), push(tokens, token))
+    Ok((chars.as_str(), push(tokens, token)))
 }
 
-fn negative_number_or_symbol(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
+fn negative_number_or_symbol(input: &str, tokens: Vec<Token>) -> Result {
     match input.chars().peekable().peek() {
         Some(&c) if c.is_digit(10) => number(input, tokens, Negative::Yes),
         _ => {
             let (input, mut tokens) = symbol(input, tokens);
             if let Some(Token::Symbol(symbol)) = tokens.pop() {
-                (input, push(tokens, Token::Symbol(format!("-{}", symbol))))
+                Ok((input, push(tokens, Token::Symbol(format!("-{}", symbol)))))
             } else {
                 panic!("Expected symbol got {:?}", input.chars().peekable().peek());
             }
@@ -192,8 +234,8 @@ fn negative_number_or_symbol(input: &str, tokens: Vec<Token>) -> (&str, Vec<Toke
     }
 }
 
-fn next(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
-    match input.chars().next() {
+fn next(input: &str, tokens: Vec<Token>) -> Result {
+    Ok(match input.chars().next() {
         Some('@') => (rest(input), push(tokens, Token::Deref)),
         Some('(') => (rest(input), push(tokens, Token::LeftParen)),
         Some(')') => (rest(input), push(tokens, Token::RightParen)),
@@ -203,23 +245,23 @@ fn next(input: &str, tokens: Vec<Token>) -> (&str, Vec<Token>) {
         Some(']') => (rest(input), push(tokens, Token::RightBracket)),
         Some('\'') => (rest(input), push(tokens, Token::Quote)),
         Some('"') => string(rest(input), tokens),
+        Some('#') if input.starts_with("#x\"") => bytes(&input[3..], tokens)?,
         Some(':') => keyword(rest(input), tokens),
         Some(';') => comment(rest(input), tokens),
         Some('/') => (rest(input), push(tokens, Token::Symbol("/".to_string()))),
-        Some('-') => negative_number_or_symbol(rest(input), tokens),
+        Some('-') => negative_number_or_symbol(rest(input), tokens)?,
         Some(c) if is_whitespace(c) => (rest(input), tokens),
-        Some(c) if c.is_digit(10) => number(input, tokens, Negative::No),
+        Some(c) if c.is_digit(10) => number(input, tokens, Negative::No)?,
         Some(_) => symbol(input, tokens),
         None => (input, tokens),
-    }
+    })
 }
 
-pub fn tokenize(input: &str) -> Vec<Token> {
+pub fn tokenize(input: &str) -> core::result::Result<Vec<Token>, Effect> {
     let mut tokens = Vec::new();
     let mut input = input;
     while !input.is_empty() {
-        (input, tokens) = next(input, tokens);
+        (input, tokens) = next(input, tokens)?;
     }
-    tokens
+    Ok(tokens)
 }
-
@@ -0,0 +1,286 @@
+extern crate alloc;
+
+use crate::bytecode::{compile, Binding, Closure, Instruction, Prototype};
+use crate::effect::{error, Effect};
+use crate::evaluator::{deref, evaluate_namespaced_symbol, evaluate_symbol, inherit};
+use crate::expression::{Call, Environment, Result};
+use crate::{extract, pattern_match, truthy, Expression};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use im::{OrdMap, Vector};
+
+struct Frame {
+    prototype: Arc<Prototype>,
+    closure: Option<Closure>,
+    env: Environment,
+    ip: usize,
+    base: usize,
+}
+
+fn pop(stack: &mut Vec<Expression>) -> Expression {
+    stack.pop().unwrap_or(Expression::Nil)
+}
+
+fn quote(arguments: Vector<Expression>) -> Vector<Expression> {
+    arguments
+        .into_iter()
+        .map(|argument| Expression::Quote(Box::new(argument)))
+        .collect()
+}
+
+fn global(env: &Environment, symbol: &Expression) -> core::result::Result<Expression, Effect> {
+    match symbol {
+        Expression::Symbol(s) => Ok(evaluate_symbol(env.clone(), s.clone())?.1),
+        Expression::NamespacedSymbol(s) => Ok(evaluate_namespaced_symbol(env.clone(), s)?.1),
+        e => Err(error(&format!("Expected symbol got {}", e))),
+    }
+}
+
+fn bind(
+    binding: &Binding,
+    value: Expression,
+    stack: &mut [Expression],
+    base: usize,
+) -> core::result::Result<(), Effect> {
+    let env = pattern_match(Environment::new(), binding.pattern.clone(), value)?;
+    for (name, slot) in &binding.slots {
+        stack[base + slot] = env.get(name).cloned().unwrap_or(Expression::Nil);
+    }
+    Ok(())
+}
+
+fn enter(
+    closure: &Closure,
+    arguments: Vector<Expression>,
+    stack: &mut Vec<Expression>,
+) -> core::result::Result<usize, Effect> {
+    let prototype = &closure.0.prototype;
+    let base = stack.len();
+    stack.resize(base + prototype.slots, Expression::Nil);
+    for ((_, slot), value) in prototype.captures.iter().zip(closure.0.captured.iter()) {
+        stack[base + slot] = value.clone();
+    }
+    if let Some(slot) = prototype.self_slot {
+        stack[base + slot] = Expression::Closure(closure.clone());
+    }
+    let mut failures = String::new();
+    for arm in &prototype.arms {
+        if arm.positional && arm.binding.slots.len() == arguments.len() {
+            for ((_, slot), value) in arm.binding.slots.iter().zip(arguments.iter()) {
+                stack[base + slot] = value.clone();
+            }
+            return Ok(arm.entry);
+        }
+        match bind(
+            &arm.binding,
+            Expression::Array(arguments.clone()),
+            stack,
+            base,
+        ) {
+            Ok(()) => return Ok(arm.entry),
+            Err(e) => failures.push_str(&format!("{}\n", e)),
+        }
+    }
+    stack.truncate(base);
+    Err(error(&failures))
+}
+
+async fn execute(mut frame: Frame, mut stack: Vec<Expression>) -> Result {
+    let mut frames: Vec<Frame> = vec![];
+    loop {
+        let instruction = frame.prototype.instructions[frame.ip];
+        frame.ip += 1;
+        match instruction {
+            Instruction::Constant(i) => stack.push(frame.prototype.constants[i].clone()),
+            Instruction::Local(i) => {
+                let value = stack[frame.base + i].clone();
+                stack.push(value);
+            }
+            Instruction::SetLocal(i) => {
+                let value = pop(&mut stack);
+                stack[frame.base + i] = value;
+            }
+            Instruction::Global(i) => {
+                let value = global(&frame.env, &frame.prototype.constants[i])?;
+                stack.push(value);
+            }
+            Instruction::Destructure(i) => {
+                let value = pop(&mut stack);
+                bind(&frame.prototype.bindings[i], value, &mut stack, frame.base)?;
+            }
+            Instruction::Pop => {
+                stack.pop();
+            }
+            Instruction::Jump(target) => frame.ip = target,
+            Instruction::JumpIfFalse(target) => {
+                if !truthy(&pop(&mut stack)) {
+                    frame.ip = target;
+                }
+            }
+            Instruction::Array(count) => {
+                let items = stack.split_off(stack.len() - count);
+                stack.push(Expression::Array(items.into_iter().collect()));
+            }
+            Instruction::Map(count) => {
+                let mut items = stack.split_off(stack.len() - 2 * count).into_iter();
+                let mut map = OrdMap::new();
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    map.insert(k, v);
+                }
+                stack.push(Expression::Map(map));
+            }
+            Instruction::Deref => {
+                let value = deref(&frame.env, pop(&mut stack)).await?;
+                stack.push(value);
+            }
+            Instruction::Closure(i) => {
+                let prototype = frame.prototype.prototypes[i].clone();
+                let captured = prototype
+                    .captures
+                    .iter()
+                    .map(|(outer, _)| stack[frame.base + outer].clone())
+                    .collect();
+                let closure = Closure::new(prototype, captured, frame.env.clone());
+                stack.push(Expression::Closure(closure));
+            }
+            Instruction::Def(i) => {
+                let value = pop(&mut stack);
+                let name = extract::symbol(frame.prototype.constants[i].clone())?;
+                frame.env.insert(name, value);
+                stack.push(Expression::Nil);
+            }
+            Instruction::Callee(i, end) => {
+                let prototype = frame.prototype.clone();
+                let fallback = &prototype.fallbacks[i];
+                let Expression::Call(call) = &fallback.expression else {
+                    return Err(error("Expected a call"));
+                };
+                let function = global(&frame.env, &call.function)?;
+                if !matches!(function, Expression::SpecialForm(_)) {
+                    stack.push(function);
+                    continue;
+                }
+                let mut env = frame.env.clone();
+                for (name, slot) in &fallback.locals {
+                    env.insert(name.clone(), stack[frame.base + slot].clone());
+                }
+                let (mut env, value) = crate::evaluate(env, fallback.expression.clone()).await?;
+                for (name, _) in &fallback.locals {
+                    match frame.env.get(name) {
+                        Some(original) => env.insert(name.clone(), original.clone()),
+                        None => env.remove(name),
+                    };
+                }
+                frame.env = env;
+                stack.push(value);
+                frame.ip = end;
+            }
+            Instruction::Call(count) => {
+                crate::sandbox::step(&frame.env).await?;
+                let arguments: Vector<Expression> =
+                    stack.split_off(stack.len() - count).into_iter().collect();
+                let function = pop(&mut stack);
+                match function {
+                    Expression::Closure(closure) => {
+                        let tail = frame.closure.is_some()
+                            && frame.prototype.instructions.get(frame.ip)
+                                == Some(&Instruction::Return);
+                        if tail {
+                            stack.truncate(frame.base);
+                        }
+                        let base = stack.len();
+                        let ip = enter(&closure, arguments, &mut stack)?;
+                        let callee = Frame {
+                            prototype: closure.0.prototype.clone(),
+                            env: inherit(&frame.env, closure.0.env.clone()),
+                            closure: Some(closure),
+                            ip,
+                            base,
+                        };
+                        let caller = core::mem::replace(&mut frame, callee);
+                        if !tail {
+                            frames.push(caller);
+                        }
+                    }
+                    Expression::NativeFunction(f) | Expression::SpecialForm(f) => {
+                        let (env, value) = f(frame.env.clone(), quote(arguments)).await?;
+                        crate::sandbox::check(&env, &value).await?;
+                        frame.env = env;
+                        stack.push(value);
+                    }
+                    function => {
+                        let call = Expression::Call(Call {
+                            function: Box::new(function),
+                            arguments: quote(arguments),
+                        });
+                        let (_, value) = crate::evaluate(frame.env.clone(), call).await?;
+                        crate::sandbox::check(&frame.env, &value).await?;
+                        stack.push(value);
+                    }
+                }
+            }
+            Instruction::Return => {
+                let value = pop(&mut stack);
+                stack.truncate(frame.base);
+                match frames.pop() {
+                    Some(caller) => {
+                        frame = caller;
+                        stack.push(value);
+                    }
+                    None => return Ok((frame.env, value)),
+                }
+            }
+        }
+    }
+}
+
+pub async fn run(env: Environment, prototype: Arc<Prototype>) -> Result {
+    let stack = vec![Expression::Nil; prototype.slots];
+    let frame = Frame {
+        prototype,
+        closure: None,
+        env,
+        ip: 0,
+        base: 0,
+    };
+    execute(frame, stack).await
+}
+
+pub async fn call(
+    env: &Environment,
+    closure: Closure,
+    arguments: Vector<Expression>,
+) -> core::result::Result<Expression, Effect> {
+    let mut stack = vec![];
+    let ip = enter(&closure, arguments, &mut stack)?;
+    let frame = Frame {
+        prototype: closure.0.prototype.clone(),
+        env: inherit(env, closure.0.env.clone()),
+        closure: Some(closure),
+        ip,
+        base: 0,
+    };
+    let (_, value) = execute(frame, stack).await?;
+    Ok(value)
+}
+
+pub async fn evaluate(env: Environment, expression: Expression) -> Result {
+    let (expression, _) = crate::optimize(&env, expression);
+    let prototype = compile(&env, expression)?;
+    run(env, prototype).await
+}
+
+pub async fn evaluate_source(mut env: Environment, source: &str) -> Result {
+    let tokens = crate::tokenize(source)?;
+    let mut result = Expression::Nil;
+    for expression in crate::parse_all(&tokens) {
+        let (new_env, new_result) = evaluate(env, expression).await?;
+        env = new_env;
+        result = new_result;
+    }
+    Ok((env, result))
+}
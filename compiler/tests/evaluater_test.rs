@@ -1,4 +1,3 @@
-use compiler;
 use compiler::expression::Call;
 use im::{ordmap, vector};
 use rug::Integer;
@@ -1,11 +1,10 @@
-use compiler;
 use compiler::expression::Call;
 use im::{ordmap, vector};
 use rug::{Integer, Rational};
 
 #[test]
 fn parse_symbol() {
-    let tokens = compiler::tokenize("x");
+    let tokens = compiler::tokenize("x").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Symbol("x".to_string());
     assert_eq!(actual, expected);
@@ -14,7 +13,7 @@ fn parse_symbol() {
 
 #[test]
 fn parse_keyword() {
-    let tokens = compiler::tokenize(":x");
+    let tokens = compiler::tokenize(":x").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Keyword(":x".to_string());
     assert_eq!(actual, expected);
@@ -23,7 +22,7 @@ fn parse_keyword() {
 
 #[test]
 fn parse_string() {
-    let tokens = compiler::tokenize(r#""hello""#);
+    let tokens = compiler::tokenize(r#""hello""#).unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::String("hello".to_string());
     assert_eq!(actual, expected);
@@ -32,7 +31,7 @@ fn parse_string() {
 
 #[test]
 fn parse_integer() {
-    let tokens = compiler::tokenize("123");
+    let tokens = compiler::tokenize("123").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Integer(Integer::from(123));
     assert_eq!(actual, expected);
@@ -41,7 +40,7 @@ fn parse_integer() {
 
 #[test]
 fn parse_float() {
-    let tokens = compiler::tokenize("3.14");
+    let tokens = compiler::tokenize("3.14").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Float(compiler::Float::from_str("3.14"));
     assert_eq!(actual, expected);
@@ -50,7 +49,7 @@ fn parse_float() {
 
 #[test]
 fn parse_homogenous_array() {
-    let tokens = compiler::tokenize("[1 2 3]");
+    let tokens = compiler::tokenize("[1 2 3]").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Array(vector![
         compiler::Expression::Integer(Integer::from(1)),
@@ -63,7 +62,7 @@ fn parse_homogenous_array() {
 
 #[test]
 fn parse_heterogenous_array() {
-    let tokens = compiler::tokenize("[3.14 2 3]");
+    let tokens = compiler::tokenize("[3.14 2 3]").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Array(vector![
         compiler::Expression::Float(compiler::Float::from_str("3.14")),
@@ -76,7 +75,7 @@ fn parse_heterogenous_array() {
 
 #[test]
 fn parse_call() {
-    let tokens = compiler::tokenize("(+ 1 2)");
+    let tokens = compiler::tokenize("(+ 1 2)").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Call(Call {
         function: Box::new(compiler::Expression::Symbol("+".to_string())),
@@ -91,7 +90,7 @@ fn parse_call() {
 
 #[test]
 fn parse_nested_array() {
-    let tokens = compiler::tokenize("[3.14 [2 3]]");
+    let tokens = compiler::tokenize("[3.14 [2 3]]").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Array(vector![
         compiler::Expression::Float(compiler::Float::from_str("3.14")),
@@ -106,7 +105,7 @@ fn parse_nested_array() {
 
 #[test]
 fn parse_nested_call() {
-    let tokens = compiler::tokenize("(+ 3.14 (- 2 3))");
+    let tokens = compiler::tokenize("(+ 3.14 (- 2 3))").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Call(Call {
         function: Box::new(compiler::Expression::Symbol("+".to_string())),
@@ -127,7 +126,7 @@ fn parse_nested_call() {
 
 #[test]
 fn parse_call_inside_array() {
-    let tokens = compiler::tokenize("[3.14 (+ 2 3)]");
+    let tokens = compiler::tokenize("[3.14 (+ 2 3)]").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Array(vector![
         compiler::Expression::Float(compiler::Float::from_str("3.14")),
@@ -145,7 +144,7 @@ fn parse_call_inside_array() {
 
 #[test]
 fn parse_array_inside_call() {
-    let tokens = compiler::tokenize("(+ 3.14 [2 3])");
+    let tokens = compiler::tokenize("(+ 3.14 [2 3])").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Call(Call {
         function: Box::new(compiler::Expression::Symbol("+".to_string())),
@@ -163,7 +162,7 @@ fn parse_array_inside_call() {
 
 #[test]
 fn parse_rational() {
-    let tokens = compiler::tokenize("1/2");
+    let tokens = compiler::tokenize("1/2").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected =
         compiler::Expression::Ratio(Rational::from((Integer::from(1), Integer::from(2))));
@@ -173,7 +172,7 @@ fn parse_rational() {
 
 #[test]
 fn parse_map() {
-    let tokens = compiler::tokenize("{:a 1 :b 2}");
+    let tokens = compiler::tokenize("{:a 1 :b 2}").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Map(ordmap![
         compiler::Expression::Keyword(":a".to_string()) => compiler::Expression::Integer(Integer::from(1)),
@@ -185,7 +184,7 @@ fn parse_map() {
 
 #[test]
 fn parse_true() {
-    let tokens = compiler::tokenize("true");
+    let tokens = compiler::tokenize("true").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Bool(true);
     assert_eq!(actual, expected);
@@ -194,7 +193,7 @@ fn parse_true() {
 
 #[test]
 fn parse_false() {
-    let tokens = compiler::tokenize("false");
+    let tokens = compiler::tokenize("false").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Bool(false);
     assert_eq!(actual, expected);
@@ -203,7 +202,7 @@ fn parse_false() {
 
 #[test]
 fn parse_nil() {
-    let tokens = compiler::tokenize("nil");
+    let tokens = compiler::tokenize("nil").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Nil;
     assert_eq!(actual, expected);
@@ -212,7 +211,7 @@ fn parse_nil() {
 
 #[test]
 fn parse_quote() {
-    let tokens = compiler::tokenize("'(1 2)");
+    let tokens = compiler::tokenize("'(1 2)").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected = compiler::Expression::Quote(Box::new(compiler::Expression::Call(Call {
         function: Box::new(compiler::Expression::Integer(Integer::from(1))),
@@ -224,7 +223,7 @@ fn parse_quote() {
 
 #[test]
 fn parse_deref() {
-    let tokens = compiler::tokenize("@x");
+    let tokens = compiler::tokenize("@x").unwrap();
     let (tokens, actual) = compiler::parse(&tokens);
     let expected =
         compiler::Expression::Deref(Box::new(compiler::Expression::Symbol("x".to_string())));
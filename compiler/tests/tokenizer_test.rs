@@ -1,11 +1,11 @@
-use compiler;
 use rug::{Integer, Rational};
 
 #[test]
 fn tokenize_symbol() {
     let actual = compiler::tokenize(
         "snake_case PascalCase kebab-case camelCase predicate? -> namespaced/symbol",
-    );
+    )
+    .unwrap();
     let expected = vec![
         compiler::Token::Symbol("snake_case".to_string()),
         compiler::Token::Symbol("PascalCase".to_string()),
@@ -20,9 +20,9 @@ fn tokenize_symbol() {
 
 #[test]
 fn tokenize_keyword() {
-    let actual = compiler::tokenize(
-        ":snake_case :PascalCase :kebab-case :camelCase :predicate? :that's",
-    );
+    let actual =
+        compiler::tokenize(":snake_case :PascalCase :kebab-case :camelCase :predicate? :that's")
+            .unwrap();
     let expected = vec![
         compiler::Token::Keyword(":snake_case".to_string()),
         compiler::Token::Keyword(":PascalCase".to_string()),
@@ -36,9 +36,9 @@ fn tokenize_keyword() {
 
 #[test]
 fn tokenize_string_literal() {
-    let actual = compiler::tokenize(
-        r#""hello" "world" "123" "that's" "that’s" "Quoted \"String\"""#,
-    );
+    let actual =
+        compiler::tokenize(r#""hello" "world" "123" "that's" "that’s" "Quoted \"String\"""#)
+            .unwrap();
     let expected = vec![
         compiler::Token::String("hello".to_string()),
         compiler::Token::String("world".to_string()),
@@ -52,8 +52,7 @@ fn tokenize_string_literal() {
 
 #[test]
 fn tokenize_integer() {
-    let actual =
-        compiler::tokenize("123 456 789 1_000 -321 -456");
+    let actual = compiler::tokenize("123 456 789 1_000 -321 -456").unwrap();
     let expected = vec![
         compiler::Token::Integer(Integer::from(123)),
         compiler::Token::Integer(Integer::from(456)),
@@ -67,7 +66,7 @@ fn tokenize_integer() {
 
 #[test]
 fn tokenize_float() {
-    let actual = compiler::tokenize("1.23 4.56 7.89 1_000.0 -3.23");
+    let actual = compiler::tokenize("1.23 4.56 7.89 1_000.0 -3.23").unwrap();
     let expected = vec![
         compiler::Token::Float(compiler::Float::from_str("1.23")),
         compiler::Token::Float(compiler::Float::from_str("4.56")),
@@ -80,7 +79,7 @@ fn tokenize_float() {
 
 #[test]
 fn tokenize_delimiters() {
-    let actual = compiler::tokenize("( { [ ] } )");
+    let actual = compiler::tokenize("( { [ ] } )").unwrap();
     let expected = vec![
         compiler::Token::LeftParen,
         compiler::Token::LeftBrace,
@@ -94,7 +93,7 @@ fn tokenize_delimiters() {
 
 #[test]
 fn tokenize_call_inside_array() {
-    let actual = compiler::tokenize("[3.14 (+ 2 3)]");
+    let actual = compiler::tokenize("[3.14 (+ 2 3)]").unwrap();
     let expected = vec![
         compiler::Token::LeftBracket,
         compiler::Token::Float(compiler::Float::from_str("3.14")),
@@ -110,7 +109,7 @@ fn tokenize_call_inside_array() {
 
 #[test]
 fn tokenize_quote() {
-    let actual = compiler::tokenize("'(1 2)");
+    let actual = compiler::tokenize("'(1 2)").unwrap();
     let expected = vec![
         compiler::Token::Quote,
         compiler::Token::LeftParen,
@@ -123,7 +122,7 @@ fn tokenize_quote() {
 
 #[test]
 fn tokenize_ratio() {
-    let actual = compiler::tokenize("5/3 4/2");
+    let actual = compiler::tokenize("5/3 4/2").unwrap();
     let expected = vec![
         compiler::Token::Ratio(Rational::from((Integer::from(5), Integer::from(3)))),
         compiler::Token::Integer(Integer::from(2)),
@@ -133,7 +132,7 @@ fn tokenize_ratio() {
 
 #[test]
 fn tokenize_deref() {
-    let actual = compiler::tokenize("@ @x @(atom x)");
+    let actual = compiler::tokenize("@ @x @(atom x)").unwrap();
     let expected = vec![
         compiler::Token::Deref,
         compiler::Token::Deref,
@@ -149,7 +148,7 @@ fn tokenize_deref() {
 
 #[test]
 fn tokenize_comment_after_expression() {
-    let actual = compiler::tokenize("(+ 1 2) ; comment after expression");
+    let actual = compiler::tokenize("(+ 1 2) ; comment after expression").unwrap();
     let expected = vec![
         compiler::Token::LeftParen,
         compiler::Token::Symbol("+".to_string()),
@@ -167,7 +166,8 @@ fn tokenize_comment_before_expression() {
           ; comment before expression
           (+ 1 2)
         "#,
-    );
+    )
+    .unwrap();
     let expected = vec![
         compiler::Token::LeftParen,
         compiler::Token::Symbol("+".to_string()),
@@ -185,7 +185,8 @@ fn tokenize_comment_in_between_expression() {
           (+ 1 ; comment before expression
              2)
         "#,
-    );
+    )
+    .unwrap();
     let expected = vec![
         compiler::Token::LeftParen,
         compiler::Token::Symbol("+".to_string()),
@@ -198,7 +199,7 @@ fn tokenize_comment_in_between_expression() {
 
 #[test]
 fn tokenize_paren_after_keyword() {
-    let actual = compiler::tokenize("(get map :key)");
+    let actual = compiler::tokenize("(get map :key)").unwrap();
     let expected = vec![
         compiler::Token::LeftParen,
         compiler::Token::Symbol("get".to_string()),
@@ -208,3 +209,11 @@ fn tokenize_paren_after_keyword() {
     ];
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn tokenize_ratio_with_non_integer_denominator_is_an_error() {
+    assert!(compiler::tokenize("1/2M").is_err());
+    assert!(compiler::tokenize("1/2.5").is_err());
+    assert!(compiler::tokenize("1/x").is_err());
+    assert!(compiler::tokenize("1/0").is_err());
+}
@@ -1,7 +1,3 @@
-use base;
-use compiler;
-use html;
-
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
 #[tokio::test]
@@ -0,0 +1,224 @@
+extern crate alloc;
+extern crate std;
+
+use crate::Result;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use axum::http::Request;
+use axum::response::{IntoResponse, Response};
+use compiler::effect::error;
+use compiler::extract;
+use compiler::Expression;
+use hyper::header::{
+    ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
+    IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, LOCATION,
+};
+use hyper::{Body, Method, StatusCode};
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+enum Range {
+    Full,
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+pub fn mounted(value: &Expression) -> bool {
+    match value {
+        Expression::Map(map) => map.contains_key(&Expression::Keyword(":dir".to_string())),
+        _ => false,
+    }
+}
+
+fn index(options: &Expression) -> Result<Option<String>> {
+    match extract::map(options.clone())?.get(&Expression::Keyword(":index".to_string())) {
+        None => Ok(Some("index.html".to_string())),
+        Some(Expression::Nil | Expression::Bool(false)) => Ok(None),
+        Some(index) => Ok(Some(extract::string(index.clone())?)),
+    }
+}
+
+fn decode(segment: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' if tail.len() >= 2 => {
+                let hex = core::str::from_utf8(&tail[..2]).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            }
+            b'%' => return None,
+            byte => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn relative(tail: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for segment in tail.split('/') {
+        let segment = decode(segment)?;
+        match &segment[..] {
+            "" | "." => continue,
+            ".." => return None,
+            s if s.contains(['/', '\\', '\0']) => return None,
+            s => path.push(s),
+        }
+    }
+    Some(path)
+}
+
+fn not_found() -> Response {
+    StatusCode::NOT_FOUND.into_response()
+}
+
+async fn resolve(root: &Path, tail: &str) -> Option<PathBuf> {
+    let root = tokio::fs::canonicalize(root).await.ok()?;
+    let path = tokio::fs::canonicalize(root.join(relative(tail)?))
+        .await
+        .ok()?;
+    path.starts_with(&root).then_some(path)
+}
+
+fn range(headers: &HeaderMap, len: u64, etag: &str, modified: &str) -> Range {
+    let Some(header) = headers
+        .get(hyper::header::RANGE)
+        .and_then(|h| h.to_str().ok())
+    else {
+        return Range::Full;
+    };
+    if let Some(if_range) = headers.get(IF_RANGE).and_then(|h| h.to_str().ok()) {
+        if if_range != etag && if_range != modified {
+            return Range::Full;
+        }
+    }
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Range::Full;
+    };
+    let Some((start, end)) = spec.split_once('-').filter(|_| !spec.contains(',')) else {
+        return Range::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    if start.is_empty() {
+        return match end.parse::<u64>() {
+            Ok(0) => Range::Unsatisfiable,
+            Ok(_) if len == 0 => Range::Unsatisfiable,
+            Ok(suffix) => Range::Partial(len - suffix.min(len), len - 1),
+            Err(_) => Range::Full,
+        };
+    }
+    let Ok(start) = start.parse::<u64>() else {
+        return Range::Full;
+    };
+    let end = match end {
+        "" => len.saturating_sub(1),
+        end => match end.parse::<u64>() {
+            Ok(end) => end.min(len.saturating_sub(1)),
+            Err(_) => return Range::Full,
+        },
+    };
+    if start >= len || start > end {
+        return Range::Unsatisfiable;
+    }
+    Range::Partial(start, end)
+}
+
+fn fresh(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+    if let Some(tags) = headers.get(IF_NONE_MATCH).and_then(|h| h.to_str().ok()) {
+        return tags
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag);
+    }
+    let since = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| httpdate::parse_http_date(h).ok());
+    let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    since.map_or(false, |since| seconds(modified) <= seconds(since))
+}
+
+pub async fn serve(options: &Expression, prefix: &str, req: Request<Body>) -> Result<Response> {
+    let (method, request_headers) = (req.method().clone(), req.headers().clone());
+    if method != Method::GET && method != Method::HEAD {
+        return Ok((StatusCode::METHOD_NOT_ALLOWED, [(ALLOW, "GET, HEAD")]).into_response());
+    }
+    let root = PathBuf::from(extract::string(extract::key(options.clone(), ":dir")?)?);
+    let request_path = req.uri().path().to_string();
+    drop(req);
+    let tail = request_path
+        .strip_prefix(prefix.trim_end_matches('/'))
+        .unwrap_or("");
+    let Some(mut path) = resolve(&root, tail).await else {
+        return Ok(not_found());
+    };
+    let mut metadata = tokio::fs::metadata(&path)
+        .await
+        .map_err(|e| error(&format!("Could not read {}: {}", path.display(), e)))?;
+    if metadata.is_dir() {
+        let Some(index) = index(options)? else {
+            return Ok(not_found());
+        };
+        if !request_path.ends_with('/') {
+            let location = format!("{}/", request_path);
+            return Ok((StatusCode::PERMANENT_REDIRECT, [(LOCATION, location)]).into_response());
+        }
+        path.push(index);
+        match tokio::fs::metadata(&path).await {
+            Ok(index) if index.is_file() => metadata = index,
+            _ => return Ok(not_found()),
+        }
+    }
+    let len = metadata.len();
+    let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+    let stamp = modified
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let etag = format!("\"{:x}-{:x}\"", len, stamp);
+    let last_modified = httpdate::fmt_http_date(modified);
+    let mut headers = HeaderMap::new();
+    let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+    let header = |value: &str| HeaderValue::from_str(value).map_err(|e| error(&e.to_string()));
+    headers.insert(CONTENT_TYPE, header(content_type.as_ref())?);
+    headers.insert(ETAG, header(&etag)?);
+    headers.insert(LAST_MODIFIED, header(&last_modified)?);
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if fresh(&request_headers, &etag, modified) {
+        headers.remove(CONTENT_TYPE);
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+    let (status, start, end) = match range(&request_headers, len, &etag, &last_modified) {
+        Range::Full => (StatusCode::OK, 0, len),
+        Range::Partial(start, end) => {
+            let content_range = format!("bytes {}-{}/{}", start, end, len);
+            headers.insert(CONTENT_RANGE, header(&content_range)?);
+            (StatusCode::PARTIAL_CONTENT, start, end + 1)
+        }
+        Range::Unsatisfiable => {
+            let content_range = format!("bytes */{}", len);
+            let headers = [(CONTENT_RANGE, content_range)];
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+        }
+    };
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(end - start));
+    if method == Method::HEAD {
+        return Ok((status, headers).into_response());
+    }
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| error(&format!("Could not open {}: {}", path.display(), e)))?;
+    file.seek(SeekFrom::Start(start))
+        .await
+        .map_err(|e| error(&format!("Could not read {}: {}", path.display(), e)))?;
+    let body = Body::wrap_stream(ReaderStream::new(file.take(end - start)));
+    Ok((status, headers, body).into_response())
+}
@@ -1,43 +1,86 @@
 #![no_std]
 #![forbid(unsafe_code)]
 #![feature(ip_in_core)]
-#![feature(async_closure)]
 
 extern crate alloc;
+extern crate std;
 
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 use axum::http::Request;
 use axum::http::Response as HttpResponse;
-use axum::response::{Html, IntoResponse, Json, Redirect};
-use axum::routing::{delete, get, post, put};
+use axum::response::{Html, IntoResponse};
+use axum::routing::{any, MethodFilter, MethodRouter};
 use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
 use compiler::atom::Atom;
 use compiler::effect::{error, Effect};
 use compiler::evaluate;
 use compiler::evaluate_expressions;
-use compiler::expression::{Call, Environment};
+use compiler::expression::{Call, Environment, Function, Numbers, Pattern};
 use compiler::extract;
 use compiler::Expression::{self, NativeFunction};
 use compiler::NativeType;
-use core::future::Future;
 use core::net::IpAddr;
 use core::net::Ipv4Addr;
 use core::net::SocketAddr;
+use core::time::Duration;
 use futures::stream;
 use html::html_string_to_expression;
-use hyper::header::CONTENT_TYPE;
+use hyper::header::{ALLOW, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE};
 use hyper::{Body, StatusCode};
 use im::{ordmap, vector, OrdMap, Vector};
-use reqwest::{RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use reqwest::redirect::Policy;
+use reqwest::{Method, RequestBuilder, Response};
 use rug::Integer;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::Instant;
+
+mod files;
+mod middleware;
+
+use middleware::{Builtin, Exchange};
+
+enum Listener {
+    Tcp(SocketAddr, bool),
+    Unix(String),
+}
 
 struct Server {
     tx: broadcast::Sender<()>,
-    port: u16,
+    listener: Listener,
+}
+
+impl Listener {
+    fn key(&self) -> Expression {
+        match self {
+            Listener::Tcp(addr, _) => Expression::String(addr.to_string()),
+            Listener::Unix(path) => Expression::String(path.clone()),
+        }
+    }
+
+    fn info(&self) -> Expression {
+        match self {
+            Listener::Tcp(addr, tls) => {
+                let scheme = if *tls { "https" } else { "http" };
+                Expression::Map(ordmap! {
+                    Expression::Keyword(":host".to_string()) => Expression::String(addr.ip().to_string()),
+                    Expression::Keyword(":port".to_string()) => Expression::Integer(Integer::from(addr.port())),
+                    Expression::Keyword(":scheme".to_string()) => Expression::String(scheme.to_string()),
+                    Expression::Keyword(":url".to_string()) => Expression::String(format!("{}://{}", scheme, addr))
+                })
+            }
+            Listener::Unix(path) => Expression::Map(ordmap! {
+                Expression::Keyword(":socket".to_string()) => Expression::String(path.clone())
+            }),
+        }
+    }
 }
 
 type Result<T> = core::result::Result<T, Effect>;
@@ -68,17 +111,31 @@ async fn request_map(route_path: &str, req: Request<Body>) -> Result<Expression>
         .get(CONTENT_TYPE)
         .map(|v| v.to_str().unwrap_or(""));
     let method = req.method().to_string();
-    let path_and_query = req.uri().path_and_query().unwrap();
-    let actual_path = path_and_query.path().to_string();
+    let actual_path = req.uri().path().to_string();
     let headers = req.headers().iter().fold(OrdMap::new(), |mut m, (k, v)| {
         m.insert(
             Expression::Keyword(format!(":{}", k.to_string())),
-            Expression::String(v.to_str().unwrap().to_string()),
+            Expression::String(String::from_utf8_lossy(v.as_bytes()).into_owned()),
         );
         m
     });
-    let query = path_and_query.query().unwrap_or("");
-    let query: BTreeMap<String, String> = serde_qs::from_str(query).unwrap();
+    let cookies = req
+        .headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .fold(OrdMap::new(), |mut m, (name, value)| {
+            m.insert(
+                Expression::Keyword(format!(":{}", name)),
+                Expression::String(value.to_string()),
+            );
+            m
+        });
+    let query = req.uri().query().unwrap_or("");
+    let query: BTreeMap<String, String> = serde_qs::from_str(query)
+        .map_err(|e| error(&format!("Failed to parse query string: {}", e)))?;
     let query = query.iter().fold(OrdMap::new(), |mut m, (k, v)| {
         m.insert(
             Expression::Keyword(format!(":{}", k.to_string())),
@@ -107,7 +164,7 @@ async fn request_map(route_path: &str, req: Request<Body>) -> Result<Expression>
     ];
     match content_type {
         Some("application/x-www-form-urlencoded") => {
-            let form = parse_form_data(req).await.unwrap_or_else(|_| OrdMap::new());
+            let form = parse_form_data(req).await?;
             if !form.is_empty() {
                 map.insert(
                     Expression::Keyword(":form".to_string()),
@@ -119,8 +176,9 @@ async fn request_map(route_path: &str, req: Request<Body>) -> Result<Expression>
             let body_bytes = hyper::body::to_bytes(req.into_body())
                 .await
                 .map_err(|_| error("Failed to read body"))?;
-            let json_data = serde_json::from_slice::<Expression>(&body_bytes)
-                .map_err(|_| error("Failed to parse JSON body"))?;
+            let json_data = serde_json::from_slice(&body_bytes)
+                .map_err(|e| error(&format!("Failed to parse JSON body: {}", e)))?;
+            let json_data = json::from_value(json_data)?;
             if let Expression::Map(json_map) = json_data {
                 if !json_map.is_empty() {
                     map.insert(
@@ -130,7 +188,25 @@ async fn request_map(route_path: &str, req: Request<Body>) -> Result<Expression>
                 }
             }
         }
-        _ => {}
+        content_type => {
+            let text = content_type.map_or(false, textual);
+            let body_bytes = hyper::body::to_bytes(req.into_body())
+                .await
+                .map_err(|_| error("Failed to read body"))?;
+            if !body_bytes.is_empty() {
+                let body = match core::str::from_utf8(&body_bytes) {
+                    Ok(body) if text => Expression::String(body.to_string()),
+                    _ => Expression::Bytes(body_bytes.clone()),
+                };
+                map.insert(Expression::Keyword(":body".to_string()), body);
+            }
+        }
+    }
+    if cookies.len() > 0 {
+        map.insert(
+            Expression::Keyword(":cookies".to_string()),
+            Expression::Map(cookies),
+        );
     }
     if query.len() > 0 {
         map.insert(
@@ -147,54 +223,273 @@ async fn request_map(route_path: &str, req: Request<Body>) -> Result<Expression>
     Ok(Expression::Map(map))
 }
 
-fn create_handler(expression: Expression) -> impl Future<Output = impl IntoResponse> {
-    async move {
-        match expression {
-            Expression::String(text) => text.into_response(),
-            Expression::Array(_) => {
-                let mut string = String::new();
-                html::build_string(expression, &mut string).unwrap();
-                Html(string).into_response()
+fn event_stream(chan: compiler::channel::Channel, numbers: Numbers) -> axum::response::Response {
+    let stream = stream::unfold(chan, move |chan| async move {
+        let value = compiler::channel::take(chan.clone()).await?;
+        let data = match value {
+            Expression::String(s) => s,
+            value => json::to_value(&value, numbers)
+                .ok()
+                .and_then(|value| serde_json::to_string(&value).ok())
+                .unwrap_or_default(),
+        };
+        let s = format!("data: {}\n\n", data);
+        Some((Ok::<_, hyper::Error>(s), chan))
+    });
+    HttpResponse::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .header("Connection", "keep-alive")
+        .body(Body::wrap_stream(stream))
+        .unwrap()
+        .into_response()
+}
+
+fn body_response(body: Expression, numbers: Numbers) -> Result<axum::response::Response> {
+    match body {
+        Expression::String(text) => Ok(text.into_response()),
+        Expression::Bytes(bytes) => Ok(bytes.into_response()),
+        Expression::Nil => Ok(().into_response()),
+        Expression::Array(_) => {
+            let mut string = String::new();
+            html::build_string(body, &mut string)?;
+            Ok(Html(string).into_response())
+        }
+        Expression::Channel(chan) => Ok(event_stream(chan, numbers)),
+        Expression::Map(_)
+        | Expression::Keyword(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::Ratio(_)
+        | Expression::Decimal(_)
+        | Expression::Bool(_) => {
+            let json = serde_json::to_vec(&json::to_value(&body, numbers)?)
+                .map_err(|e| error(&format!("Could not serialize response: {}", e)))?;
+            Ok(([(CONTENT_TYPE, "application/json")], json).into_response())
+        }
+        other => Err(error(&format!("Cannot respond with {}", other))),
+    }
+}
+
+fn status_code(status: &Expression) -> Result<StatusCode> {
+    extract::integer(status.clone())?
+        .to_u16()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .ok_or_else(|| error(&format!("Invalid status code {}", status)))
+}
+
+fn redirect(
+    redirect: &Expression,
+    status: Option<&Expression>,
+) -> Result<axum::response::Response> {
+    let url = match redirect {
+        Expression::String(url) => url.clone(),
+        Expression::Map(map) => {
+            let mut url = extract::string(extract::key(map.clone(), ":url")?)?;
+            if let Some(query) = map.get(&Expression::Keyword(":query".to_string())) {
+                let query = serde_qs::to_string(query)
+                    .map_err(|e| error(&format!("Could not encode redirect query: {}", e)))?;
+                url.push('?');
+                url.push_str(&query);
             }
-            Expression::Map(ref m) => match m.get(&Expression::Keyword(":redirect".to_string())) {
-                Some(Expression::String(url)) => Redirect::permanent(&url).into_response(),
-                Some(Expression::Map(map)) => {
-                    let url = extract::key(map.clone(), ":url").unwrap();
-                    let mut url = extract::string(url).unwrap();
-                    if let Some(query) = map.get(&Expression::Keyword(":query".to_string())) {
-                        let query = serde_qs::to_string(query).unwrap();
-                        url.push('?');
-                        url.push_str(&query);
-                    }
-                    Redirect::permanent(&url).into_response()
-                }
-                _ => Json(expression).into_response(),
-            },
-            Expression::Channel(chan) => {
-                let stream = stream::unfold(chan, move |chan| async move {
-                    let value = compiler::channel::take(chan.clone()).await;
-                    if let Expression::String(s) = value {
-                        let s = format!("data: {}\n\n", s);
-                        Some((Ok::<_, hyper::Error>(s), chan))
-                    } else {
-                        None
-                    }
-                });
-                HttpResponse::builder()
-                    .status(StatusCode::OK)
-                    .header("Content-Type", "text/event-stream")
-                    .header("Cache-Control", "no-cache")
-                    .header("Connection", "keep-alive")
-                    .body(Body::wrap_stream(stream))
-                    .unwrap()
-                    .into_response()
+            url
+        }
+        other => return Err(error(&format!("Expected redirect url, got {}", other))),
+    };
+    let status = match status {
+        Some(status) => status_code(status)?,
+        None => StatusCode::FOUND,
+    };
+    if !status.is_redirection() {
+        return Err(error(&format!(
+            "Expected a redirect status, got {}",
+            status
+        )));
+    }
+    let location =
+        HeaderValue::from_str(&url).map_err(|_| error(&format!("Invalid redirect url {}", url)))?;
+    Ok((status, [(LOCATION, location)]).into_response())
+}
+
+fn cookie(name: &Expression, cookie: &Expression) -> Result<HeaderValue> {
+    let name = match name {
+        Expression::Keyword(k) => k[1..].to_string(),
+        name => extract::string(name.clone())?,
+    };
+    let attributes = match cookie {
+        Expression::Map(attributes) => attributes.clone(),
+        value => ordmap! { Expression::Keyword(":value".to_string()) => value.clone() },
+    };
+    let value = extract::string(extract::key(attributes.clone(), ":value")?)?;
+    let mut header = format!("{}={}", name, value);
+    for (key, value) in attributes.iter() {
+        let attribute = match (&extract::keyword(key.clone())?[..], value) {
+            (":value", _) => continue,
+            (":path", Expression::String(path)) => format!("Path={}", path),
+            (":domain", Expression::String(domain)) => format!("Domain={}", domain),
+            (":expires", Expression::String(expires)) => format!("Expires={}", expires),
+            (":max-age", Expression::Integer(age)) => format!("Max-Age={}", age),
+            (":secure", secure) if base::truthy(secure) => "Secure".to_string(),
+            (":http-only", http_only) if base::truthy(http_only) => "HttpOnly".to_string(),
+            (":secure" | ":http-only", _) => continue,
+            (":same-site", Expression::Keyword(k)) if k == ":strict" => {
+                "SameSite=Strict".to_string()
+            }
+            (":same-site", Expression::Keyword(k)) if k == ":lax" => "SameSite=Lax".to_string(),
+            (":same-site", Expression::Keyword(k)) if k == ":none" => "SameSite=None".to_string(),
+            (key, value) => {
+                return Err(error(&format!(
+                    "Unsupported cookie attribute {} {}",
+                    key, value
+                )))
             }
-            _ => unimplemented!(),
+        };
+        header.push_str("; ");
+        header.push_str(&attribute);
+    }
+    HeaderValue::from_str(&header).map_err(|_| error(&format!("Invalid cookie {}", name)))
+}
+
+fn response_map(
+    map: &OrdMap<Expression, Expression>,
+    numbers: Numbers,
+) -> Result<axum::response::Response> {
+    let status = map.get(&Expression::Keyword(":status".to_string()));
+    let mut response = match map.get(&Expression::Keyword(":redirect".to_string())) {
+        Some(location) => redirect(location, status)?,
+        None => {
+            let body = map
+                .get(&Expression::Keyword(":body".to_string()))
+                .cloned()
+                .unwrap_or(Expression::Nil);
+            let mut response = body_response(body, numbers)?;
+            if let Some(status) = status {
+                *response.status_mut() = status_code(status)?;
+            }
+            response
         }
+    };
+    if let Some(headers) = map.get(&Expression::Keyword(":headers".to_string())) {
+        response
+            .headers_mut()
+            .extend(header_map(&extract::map(headers.clone())?)?);
     }
+    if let Some(cookies) = map.get(&Expression::Keyword(":cookies".to_string())) {
+        for (name, value) in extract::map(cookies.clone())?.iter() {
+            response
+                .headers_mut()
+                .append(SET_COOKIE, cookie(name, value)?);
+        }
+    }
+    Ok(response)
+}
+
+fn create_response(expression: Expression, numbers: Numbers) -> Result<axum::response::Response> {
+    match &expression {
+        Expression::Map(map)
+            if [":redirect", ":status", ":body"]
+                .iter()
+                .any(|key| map.contains_key(&Expression::Keyword(key.to_string()))) =>
+        {
+            response_map(map, numbers)
+        }
+        _ => body_response(expression, numbers),
+    }
+}
+
+enum Format {
+    Events,
+    Stream,
+    Json,
+    Html,
+    Text,
+    Body,
+    Bytes,
 }
 
-async fn encode_response(response: Response) -> Result<Expression> {
+fn mime(content_type: &str) -> (String, String) {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    match mime.split_once('/') {
+        Some((kind, subtype)) => (kind.to_string(), subtype.to_string()),
+        None => (mime, String::new()),
+    }
+}
+
+fn textual(content_type: &str) -> bool {
+    let (kind, subtype) = mime(content_type);
+    kind == "text"
+        || subtype.ends_with("+json")
+        || subtype.ends_with("+xml")
+        || matches!(
+            &subtype[..],
+            "json"
+                | "xml"
+                | "javascript"
+                | "ecmascript"
+                | "x-www-form-urlencoded"
+                | "csv"
+                | "yaml"
+                | "x-yaml"
+                | "toml"
+                | "graphql"
+                | "sql"
+        )
+}
+
+fn format(content_type: Option<&str>, chunked: bool, as_: Option<&str>) -> Result<Format> {
+    match as_ {
+        Some(":text") => return Ok(Format::Text),
+        Some(":json") => return Ok(Format::Json),
+        Some(":bytes") => return Ok(Format::Bytes),
+        Some(":stream") => return Ok(Format::Stream),
+        Some(other) => return Err(error(&format!("Unsupported response format {}", other))),
+        None => {}
+    }
+    let Some(content_type) = content_type else {
+        return Ok(Format::Bytes);
+    };
+    let (kind, subtype) = mime(content_type);
+    Ok(match (&kind[..], &subtype[..]) {
+        ("text", "event-stream") if chunked => Format::Events,
+        ("text", "html") => Format::Html,
+        ("text", "plain") => Format::Text,
+        (_, "json") => Format::Json,
+        (_, subtype) if subtype.ends_with("+json") => Format::Json,
+        _ if textual(content_type) => Format::Body,
+        _ => Format::Bytes,
+    })
+}
+
+fn chunks(response: Response, events: bool, text: bool) -> Expression {
+    let channel = compiler::channel::Channel::new(10);
+    let channel_cloned = channel.clone();
+    tokio::spawn(async move {
+        let mut response = response;
+        while let Some(chunk) = response.chunk().await.unwrap_or(None) {
+            let value = if events {
+                let chunk = String::from_utf8_lossy(&chunk);
+                Expression::String(chunk[6..chunk.len() - 2].to_string())
+            } else if text {
+                Expression::String(String::from_utf8_lossy(&chunk).into_owned())
+            } else {
+                Expression::Bytes(chunk)
+            };
+            if !compiler::channel::put(&channel, value).await {
+                break;
+            }
+        }
+        channel.sender.close();
+    });
+    Expression::Channel(channel_cloned)
+}
+
+async fn encode_response(response: Response, as_: Option<&str>) -> Result<Expression> {
     let status = Expression::Integer(Integer::from(response.status().as_u16()));
     let headers = response.headers();
     let mut headers_map = ordmap! {};
@@ -215,198 +510,1048 @@ async fn encode_response(response: Response) -> Result<Expression> {
     let content_type = headers
         .get("content-type")
         .map(|value| value.to_str().ok())
-        .flatten();
-    let transfer_encoding = headers
+        .flatten()
+        .map(|value| value.to_string());
+    let chunked = headers
         .get("transfer-encoding")
         .map(|value| value.to_str().unwrap_or(""))
-        .unwrap_or("");
-    if transfer_encoding == "chunked" && content_type == Some("text/event-stream") {
-        let channel = compiler::channel::Channel::new(10);
-        let channel_cloned = channel.clone();
-        tokio::spawn(async move {
-            let mut response = response;
-            while let Some(chunk) = response.chunk().await.unwrap_or(None) {
-                let chunk = String::from_utf8_lossy(&chunk);
-                let chunk = &chunk[6..chunk.len() - 2];
-                channel
-                    .sender
-                    .send(Expression::String(chunk.to_string()))
-                    .await
-                    .unwrap();
-            }
-            channel.sender.close();
-            Ok::<(), Effect>(())
-        });
-        result.insert(
-            Expression::Keyword(":channel".to_string()),
-            Expression::Channel(channel_cloned),
-        );
-        return Ok(Expression::Map(result));
-    }
-    match content_type {
-        Some(t) if t.starts_with("application/json") => {
-            let json = response
-                .json::<Expression>()
-                .await
-                .map_err(|_| error("Could not get text from response"))?;
+        .unwrap_or("")
+        == "chunked";
+    let content_type = content_type.as_deref();
+    let text = |e: reqwest::Error| error(&format!("Could not get text from response: {}", e));
+    match format(content_type, chunked, as_)? {
+        Format::Events => {
+            let channel = chunks(response, true, true);
+            result.insert(Expression::Keyword(":channel".to_string()), channel);
+        }
+        Format::Stream => {
+            let channel = chunks(response, false, content_type.map_or(false, textual));
+            result.insert(Expression::Keyword(":channel".to_string()), channel);
+        }
+        Format::Json => {
+            let text = response.text().await.map_err(text)?;
+            let json = serde_json::from_str(&text)
+                .map_err(|e| error(&format!("Could not parse JSON from response: {}", e)))?;
+            let json = json::from_value(json)?;
             result.insert(Expression::Keyword(":json".to_string()), json);
         }
-        Some(t) if t.starts_with("text/html") => {
-            let text = response
-                .text()
-                .await
-                .map_err(|_| error("Could not get text from response"))?;
+        Format::Html => {
+            let text = response.text().await.map_err(text)?;
             let expression = html_string_to_expression(&text);
             result.insert(Expression::Keyword(":html".to_string()), expression);
         }
-        Some(t) if t.starts_with("text/plain") => {
-            let text = response
-                .text()
+        Format::Text => {
+            let text = response.text().await.map_err(text)?;
+            result.insert(
+                Expression::Keyword(":text".to_string()),
+                Expression::String(text),
+            );
+        }
+        Format::Body => {
+            let text = response.text().await.map_err(text)?;
+            result.insert(
+                Expression::Keyword(":body".to_string()),
+                Expression::String(text),
+            );
+        }
+        Format::Bytes => {
+            let bytes = response
+                .bytes()
                 .await
-                .map_err(|_| error("Could not get text from response"))
-                .map(|text| Expression::String(text))?;
-            result.insert(Expression::Keyword(":text".to_string()), text);
+                .map_err(|e| error(&format!("Could not get body from response: {}", e)))?;
+            if !bytes.is_empty() || as_.is_some() {
+                result.insert(
+                    Expression::Keyword(":body".to_string()),
+                    Expression::Bytes(bytes),
+                );
+            }
         }
-        Some(t) => Err(error(&format!("Unsupported content type: {}", t)))?,
-        None => {}
     }
     Ok(Expression::Map(result))
 }
 
+#[derive(Clone)]
+struct Retry {
+    attempts: u32,
+    statuses: Vec<u16>,
+    methods: Vec<Method>,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Retry {
+    fn delay(&self, attempt: u32, response: Option<&Response>) -> Duration {
+        let retry_after = response
+            .and_then(|response| response.headers().get(RETRY_AFTER))
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .and_then(|value| match value.parse::<u64>() {
+                Ok(seconds) => Some(Duration::from_secs(seconds)),
+                Err(_) => httpdate::parse_http_date(value).ok().map(|date| {
+                    date.duration_since(std::time::SystemTime::now())
+                        .unwrap_or(Duration::ZERO)
+                }),
+            });
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_backoff);
+        }
+        let exponential = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        let half = exponential / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+}
+
+#[derive(Default)]
+struct Circuit {
+    failures: u32,
+    opened: Option<Instant>,
+    last_failure: String,
+}
+
+struct Breaker {
+    threshold: u32,
+    reset: Duration,
+    circuit: Mutex<Circuit>,
+}
+
+impl Breaker {
+    async fn check(&self, url: &str) -> Result<()> {
+        let circuit = self.circuit.lock().await;
+        match circuit.opened {
+            Some(opened) if opened.elapsed() < self.reset => Err(error(&format!(
+                "Circuit open for {} after {} consecutive failures, last was {}",
+                url, circuit.failures, circuit.last_failure
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn record(&self, failure: Option<String>) {
+        let mut circuit = self.circuit.lock().await;
+        match failure {
+            None => *circuit = Circuit::default(),
+            Some(failure) => {
+                circuit.failures += 1;
+                circuit.last_failure = failure;
+                if circuit.failures >= self.threshold {
+                    circuit.opened = Some(Instant::now());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Client {
+    client: reqwest::Client,
+    base_url: Option<String>,
+    retry: Option<Retry>,
+    breaker: Option<Arc<Breaker>>,
+}
+
+impl Client {
+    fn resolve(&self, url: &str) -> String {
+        match &self.base_url {
+            Some(base) if !url.starts_with("http://") && !url.starts_with("https://") => format!(
+                "{}/{}",
+                base.trim_end_matches('/'),
+                url.trim_start_matches('/')
+            ),
+            _ => url.to_string(),
+        }
+    }
+}
+
+fn milliseconds(options: &OrdMap<Expression, Expression>, key: &str) -> Result<Option<Duration>> {
+    match options.get(&Expression::Keyword(key.to_string())) {
+        Some(ms) => {
+            let ms = extract::integer(ms.clone())?
+                .to_u64()
+                .ok_or_else(|| error(&format!("Expected {} to be a positive integer", key)))?;
+            Ok(Some(Duration::from_millis(ms)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn count(options: &OrdMap<Expression, Expression>, key: &str) -> Result<Option<u32>> {
+    match options.get(&Expression::Keyword(key.to_string())) {
+        Some(n) => {
+            let n = extract::integer(n.clone())?
+                .to_u32()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| error(&format!("Expected {} to be a positive integer", key)))?;
+            Ok(Some(n))
+        }
+        None => Ok(None),
+    }
+}
+
+fn retry_policy(options: &OrdMap<Expression, Expression>) -> Result<Option<Retry>> {
+    let retry = match options.get(&Expression::Keyword(":retry".to_string())) {
+        None | Some(Expression::Nil) | Some(Expression::Bool(false)) => return Ok(None),
+        Some(Expression::Integer(attempts)) => ordmap! {
+            Expression::Keyword(":attempts".to_string()) => Expression::Integer(attempts.clone())
+        },
+        Some(Expression::Bool(true)) => OrdMap::new(),
+        Some(retry) => extract::map(retry.clone())?,
+    };
+    let statuses = match retry.get(&Expression::Keyword(":statuses".to_string())) {
+        Some(statuses) => extract::array(statuses.clone())?
+            .iter()
+            .map(|status| {
+                extract::integer(status.clone())?
+                    .to_u16()
+                    .ok_or_else(|| error(&format!("Invalid status code {}", status)))
+            })
+            .collect::<Result<Vec<u16>>>()?,
+        None => vec![429, 502, 503, 504],
+    };
+    let methods = match retry.get(&Expression::Keyword(":methods".to_string())) {
+        Some(methods) => extract::array(methods.clone())?
+            .iter()
+            .map(|method| parse_method(&extract::keyword(method.clone())?))
+            .collect::<Result<Vec<Method>>>()?,
+        None => vec![
+            Method::GET,
+            Method::HEAD,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
+        ],
+    };
+    Ok(Some(Retry {
+        attempts: count(&retry, ":attempts")?.unwrap_or(3),
+        statuses,
+        methods,
+        backoff: milliseconds(&retry, ":backoff-ms")?.unwrap_or(Duration::from_millis(100)),
+        max_backoff: milliseconds(&retry, ":max-backoff-ms")?.unwrap_or(Duration::from_secs(10)),
+    }))
+}
+
+fn circuit_breaker(options: &OrdMap<Expression, Expression>) -> Result<Option<Arc<Breaker>>> {
+    let breaker = match options.get(&Expression::Keyword(":circuit-breaker".to_string())) {
+        None | Some(Expression::Nil) | Some(Expression::Bool(false)) => return Ok(None),
+        Some(Expression::Bool(true)) => OrdMap::new(),
+        Some(breaker) => extract::map(breaker.clone())?,
+    };
+    Ok(Some(Arc::new(Breaker {
+        threshold: count(&breaker, ":failures")?.unwrap_or(5),
+        reset: milliseconds(&breaker, ":reset-ms")?.unwrap_or(Duration::from_secs(30)),
+        circuit: Mutex::new(Circuit::default()),
+    })))
+}
+
+fn header_map(headers: &OrdMap<Expression, Expression>) -> Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+    for (key, value) in headers.iter() {
+        let key = extract::keyword(key.clone())?;
+        let name = HeaderName::from_bytes(key[1..].as_bytes())
+            .map_err(|_| error(&format!("Invalid header name {}", key)))?;
+        let value = extract::string(value.clone())?;
+        let value = HeaderValue::from_str(&value)
+            .map_err(|_| error(&format!("Invalid value for header {}", key)))?;
+        header_map.insert(name, value);
+    }
+    Ok(header_map)
+}
+
+fn build_client(options: &OrdMap<Expression, Expression>) -> Result<Client> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(headers) = options.get(&Expression::Keyword(":headers".to_string())) {
+        builder = builder.default_headers(header_map(&extract::map(headers.clone())?)?);
+    }
+    if let Some(timeout) = milliseconds(options, ":timeout-ms")? {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(timeout) = milliseconds(options, ":connect-timeout-ms")? {
+        builder = builder.connect_timeout(timeout);
+    }
+    builder = match options.get(&Expression::Keyword(":redirect".to_string())) {
+        None => builder,
+        Some(Expression::Keyword(k)) if k == ":follow" => builder,
+        Some(Expression::Keyword(k)) if k == ":none" => builder.redirect(Policy::none()),
+        Some(Expression::Integer(max)) => {
+            let max = max
+                .to_usize()
+                .ok_or_else(|| error("Expected redirect limit to be a positive integer"))?;
+            builder.redirect(Policy::limited(max))
+        }
+        Some(other) => return Err(error(&format!("Unsupported redirect policy {}", other))),
+    };
+    if let Some(cookies) = options.get(&Expression::Keyword(":cookies".to_string())) {
+        builder = builder.cookie_store(base::truthy(cookies));
+    }
+    let client = builder
+        .build()
+        .map_err(|e| error(&format!("Could not create http client: {}", e)))?;
+    let base_url = match options.get(&Expression::Keyword(":base-url".to_string())) {
+        Some(url) => Some(extract::string(url.clone())?),
+        None => None,
+    };
+    Ok(Client {
+        client,
+        base_url,
+        retry: retry_policy(options)?,
+        breaker: circuit_breaker(options)?,
+    })
+}
+
+async fn client(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
+    let (env, args) = evaluate_expressions(env, args).await?;
+    let options = match args.get(0) {
+        Some(options) => extract::map(options.clone())?,
+        None => OrdMap::new(),
+    };
+    let client = build_client(&options)?;
+    let client = NativeType::new(client, "http/client".to_string());
+    Ok((env, Expression::NativeType(client)))
+}
+
+async fn shared_client(native: &NativeType) -> Result<Client> {
+    let value = native.value.lock().await;
+    value
+        .downcast_ref::<Client>()
+        .cloned()
+        .ok_or_else(|| error("Expected http client"))
+}
+
+async fn default_client(env: &Environment) -> Result<Client> {
+    match env.get("http") {
+        Some(Expression::Module(http)) => match http.get("*client*") {
+            Some(Expression::NativeType(native)) => shared_client(native).await,
+            _ => build_client(&OrdMap::new()),
+        },
+        _ => build_client(&OrdMap::new()),
+    }
+}
+
+fn method(params: &OrdMap<Expression, Expression>) -> Result<Method> {
+    let method = match params.get(&Expression::Keyword(":method".to_string())) {
+        Some(method) => extract::keyword(method.clone())?,
+        None => ":get".to_string(),
+    };
+    parse_method(&method)
+}
+
+fn parse_method(method: &str) -> Result<Method> {
+    match method {
+        ":get" => Ok(Method::GET),
+        ":post" => Ok(Method::POST),
+        ":put" => Ok(Method::PUT),
+        ":delete" => Ok(Method::DELETE),
+        ":patch" => Ok(Method::PATCH),
+        ":head" => Ok(Method::HEAD),
+        ":options" => Ok(Method::OPTIONS),
+        ":trace" => Ok(Method::TRACE),
+        _ => Err(error(&format!("Unsupported http method {}", method))),
+    }
+}
+
 fn extend_builder(
     mut builder: RequestBuilder,
     params: OrdMap<Expression, Expression>,
-) -> RequestBuilder {
+    numbers: Numbers,
+) -> Result<RequestBuilder> {
     if let Some(e) = params.get(&Expression::Keyword(":form".to_string())) {
         builder = builder.form(e);
     }
     if let Some(e) = params.get(&Expression::Keyword(":json".to_string())) {
-        builder = builder.json(e);
+        builder = builder.json(&json::to_value(e, numbers)?);
+    }
+    if let Some(e) = params.get(&Expression::Keyword(":body".to_string())) {
+        builder = match e {
+            Expression::String(s) => builder.body(s.clone()),
+            Expression::Bytes(b) => builder.body(b.clone()),
+            _ => {
+                return Err(error(&format!(
+                    "Expected :body to be a string or bytes, got {}",
+                    e
+                )))
+            }
+        };
     }
     if let Some(e) = params.get(&Expression::Keyword(":query".to_string())) {
         builder = builder.query(e);
     }
     if let Some(e) = params.get(&Expression::Keyword(":headers".to_string())) {
-        if let Expression::Map(headers) = e {
-            for (key, value) in headers.iter() {
-                let keyword = extract::keyword(key.clone()).unwrap_or_default();
-                let keyword = &keyword[1..];
-                let value = extract::string(value.clone()).unwrap_or_default();
-                builder = builder.header(keyword, value);
+        builder = builder.headers(header_map(&extract::map(e.clone())?)?);
+    }
+    if let Some(timeout) = milliseconds(&params, ":timeout-ms")? {
+        builder = builder.timeout(timeout);
+    }
+    Ok(builder)
+}
+
+async fn send(
+    builder: RequestBuilder,
+    method: &Method,
+    url: &str,
+    retry: Option<&Retry>,
+) -> Result<Response> {
+    let retry = retry.filter(|retry| retry.methods.contains(method));
+    let attempts = retry.map_or(1, |retry| retry.attempts);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let request = builder
+            .try_clone()
+            .ok_or_else(|| error(&format!("Could not retry {} request to {}", method, url)))?;
+        let tried = match attempt {
+            1 => String::new(),
+            _ => format!(" after {} attempts", attempt),
+        };
+        let delay = match (request.send().await, retry) {
+            (Ok(response), Some(retry)) if retry.statuses.contains(&response.status().as_u16()) => {
+                if attempt >= attempts {
+                    return Err(error(&format!(
+                        "{} request to {} failed with status {}{}",
+                        method,
+                        url,
+                        response.status().as_u16(),
+                        tried
+                    )));
+                }
+                retry.delay(attempt, Some(&response))
             }
-        }
+            (Ok(response), _) => return Ok(response),
+            (Err(e), Some(retry))
+                if attempt < attempts && (e.is_timeout() || e.is_connect() || e.is_request()) =>
+            {
+                retry.delay(attempt, None)
+            }
+            (Err(e), _) => {
+                return Err(error(&format!(
+                    "Could not make {} request to {}{}: {}",
+                    method, url, tried, e
+                )))
+            }
+        };
+        tokio::time::sleep(delay).await;
     }
-    builder
 }
 
 async fn request(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
     let (env, args) = evaluate_expressions(env, args).await?;
-    let map = extract::map(args[0].clone())?;
-    let url = extract::string(extract::key(map.clone(), ":url")?)?;
-    let default_method = Expression::Keyword(":get".to_string());
-    let method = map
-        .get(&Expression::Keyword(":method".to_string()))
-        .unwrap_or(&default_method);
-    let method = extract::keyword(method.clone())?;
-    let client = reqwest::Client::new();
-    let builder = match &method[..] {
-        ":post" => client.post(url),
-        _ => client.get(url),
-    };
-    let builder = extend_builder(builder, map);
-    let response = builder
-        .send()
-        .await
-        .map_err(|_| error("Could not make get request"))?;
-    let response = encode_response(response).await?;
+    let (client, options) = match &args[0] {
+        Expression::NativeType(native) => (shared_client(native).await?, args[1].clone()),
+        _ => (default_client(&env).await?, args[0].clone()),
+    };
+    let map = extract::map(options.clone())?;
+    let url = client.resolve(&extract::string(extract::key(map.clone(), ":url")?)?);
+    let method = method(&map)?;
+    let numbers = Numbers::from_options(&env, Some(&options))?;
+    let builder = client.client.request(method.clone(), &url);
+    let builder = extend_builder(builder, map.clone(), numbers)?;
+    let retry = match map.contains_key(&Expression::Keyword(":retry".to_string())) {
+        true => retry_policy(&map)?,
+        false => client.retry.clone(),
+    };
+    if let Some(breaker) = &client.breaker {
+        breaker.check(&url).await?;
+    }
+    let response = send(builder, &method, &url, retry.as_ref()).await;
+    if let Some(breaker) = &client.breaker {
+        let failure = match &response {
+            Ok(response) if response.status().is_server_error() => {
+                Some(format!("status {}", response.status().as_u16()))
+            }
+            Ok(_) => None,
+            Err(Effect::Error(message) | Effect::Limit(message)) => Some(message.clone()),
+        };
+        breaker.record(failure).await;
+    }
+    let response = response?;
+    let as_ = match map.get(&Expression::Keyword(":as".to_string())) {
+        Some(as_) => Some(extract::keyword(as_.clone())?),
+        None => None,
+    };
+    let response = encode_response(response, as_.as_deref()).await?;
     Ok((env, response))
 }
 
+fn route_method(method: &Expression) -> Option<(MethodFilter, &'static str)> {
+    let Expression::Keyword(method) = method else {
+        return None;
+    };
+    match &method[..] {
+        ":get" => Some((MethodFilter::GET, "GET")),
+        ":post" => Some((MethodFilter::POST, "POST")),
+        ":put" => Some((MethodFilter::PUT, "PUT")),
+        ":delete" => Some((MethodFilter::DELETE, "DELETE")),
+        ":patch" => Some((MethodFilter::PATCH, "PATCH")),
+        ":head" => Some((MethodFilter::HEAD, "HEAD")),
+        ":options" => Some((MethodFilter::OPTIONS, "OPTIONS")),
+        ":trace" => Some((MethodFilter::TRACE, "TRACE")),
+        _ => None,
+    }
+}
+
+fn method_table(value: &Expression) -> Option<Vec<(MethodFilter, &'static str, Expression)>> {
+    let Expression::Map(map) = value else {
+        return None;
+    };
+    if map.is_empty() {
+        return None;
+    }
+    map.iter()
+        .map(|(method, handler)| {
+            let (filter, name) = route_method(method)?;
+            Some((filter, name, handler.clone()))
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct Context {
+    env: Environment,
+    numbers: Numbers,
+    on_error: Option<Expression>,
+    builtins: Arc<Vec<Builtin>>,
+}
+
+async fn call(env: &Environment, function: Expression, argument: Expression) -> Result<Expression> {
+    let (_, expr) = evaluate(
+        env.clone(),
+        Expression::Call(Call {
+            function: Box::new(function),
+            arguments: vector![argument],
+        }),
+    )
+    .await?;
+    Ok(expr)
+}
+
+async fn error_response(
+    context: &Context,
+    status: StatusCode,
+    effect: Effect,
+    method: String,
+    path: String,
+) -> axum::response::Response {
+    let (Effect::Error(message) | Effect::Limit(message)) = effect;
+    let code = status.as_u16();
+    middleware::log(format_args!("{} {} {} {}", method, path, code, message));
+    if let Some(on_error) = &context.on_error {
+        let error = ordmap! {
+            Expression::Keyword(":status".to_string()) => Expression::Integer(code.into()),
+            Expression::Keyword(":error".to_string()) => Expression::String(message.clone()),
+            Expression::Keyword(":method".to_string()) => Expression::String(method.clone()),
+            Expression::Keyword(":path".to_string()) => Expression::String(path.clone())
+        };
+        let response = call(&context.env, on_error.clone(), Expression::Map(error))
+            .await
+            .and_then(|expr| create_response(expr, context.numbers));
+        match response {
+            Ok(response) => return response,
+            Err(Effect::Error(e) | Effect::Limit(e)) => middleware::log(format_args!(
+                "{} {} {} on-error failed: {}",
+                method, path, code, e
+            )),
+        }
+    }
+    let message = match status.is_server_error() {
+        true => status
+            .canonical_reason()
+            .unwrap_or("Server error")
+            .to_string(),
+        false => message,
+    };
+    let body = ordmap! { Expression::Keyword(":error".to_string()) => Expression::String(message.clone()) };
+    match body_response(Expression::Map(body), context.numbers) {
+        Ok(mut response) => {
+            *response.status_mut() = status;
+            response
+        }
+        Err(_) => (status, message).into_response(),
+    }
+}
+
+async fn handle(
+    context: &Context,
+    path: String,
+    value: Expression,
+    req: Request<Body>,
+) -> axum::response::Response {
+    let method = req.method().to_string();
+    let actual_path = req.uri().path().to_string();
+    let result = match value {
+        function @ (Expression::Function(_) | Expression::Closure(_) | NativeFunction(_)) => {
+            match request_map(&path, req).await {
+                Ok(request) => call(&context.env, function, request)
+                    .await
+                    .and_then(|expr| create_response(expr, context.numbers))
+                    .map_err(|effect| (StatusCode::INTERNAL_SERVER_ERROR, effect)),
+                Err(effect) => Err((StatusCode::BAD_REQUEST, effect)),
+            }
+        }
+        mount @ Expression::Map(_) if files::mounted(&mount) => files::serve(&mount, &path, req)
+            .await
+            .map_err(|effect| (StatusCode::INTERNAL_SERVER_ERROR, effect)),
+        value => create_response(value, context.numbers)
+            .map_err(|effect| (StatusCode::INTERNAL_SERVER_ERROR, effect)),
+    };
+    match result {
+        Ok(response) => response,
+        Err((status, effect)) => error_response(context, status, effect, method, actual_path).await,
+    }
+}
+
+async fn pipeline<F, R>(
+    context: &Context,
+    mut req: Request<Body>,
+    inner: F,
+) -> axum::response::Response
+where
+    F: FnOnce(Request<Body>) -> R,
+    R: core::future::Future<Output = axum::response::Response>,
+{
+    for (index, builtin) in context.builtins.iter().enumerate() {
+        match middleware::before(builtin, &context.env, &mut req).await {
+            Ok(None) => {}
+            Ok(Some(response)) => {
+                let exchange = Exchange::new(&req);
+                return after(&context.builtins[..index], &exchange, response).await;
+            }
+            Err(effect) => {
+                let method = req.method().to_string();
+                let path = req.uri().path().to_string();
+                let status = StatusCode::INTERNAL_SERVER_ERROR;
+                return error_response(context, status, effect, method, path).await;
+            }
+        }
+    }
+    let exchange = Exchange::new(&req);
+    let response = inner(req).await;
+    after(&context.builtins, &exchange, response).await
+}
+
+async fn respond(
+    context: Context,
+    path: String,
+    value: Expression,
+    req: Request<Body>,
+) -> axum::response::Response {
+    pipeline(&context, req, |req| handle(&context, path, value, req)).await
+}
+
+async fn after(
+    builtins: &[Builtin],
+    exchange: &Exchange,
+    mut response: axum::response::Response,
+) -> axum::response::Response {
+    for builtin in builtins.iter().rev() {
+        response = middleware::after(builtin, exchange, response).await;
+    }
+    response
+}
+
+fn route(context: &Context, path: &str, value: Expression) -> MethodRouter {
+    let handler = |value: Expression| {
+        let context = context.clone();
+        let path = path.to_string();
+        move |req: Request<Body>| respond(context.clone(), path.clone(), value.clone(), req)
+    };
+    let fixed = |status: StatusCode, allow: String| {
+        let context = context.clone();
+        move |req: Request<Body>| {
+            let (context, allow) = (context.clone(), allow.clone());
+            async move {
+                let response =
+                    |_: Request<Body>| async move { (status, [(ALLOW, allow)]).into_response() };
+                pipeline(&context, req, response).await
+            }
+        }
+    };
+    let Some(table) = method_table(&value) else {
+        return any(handler(value));
+    };
+    let mut allowed: Vec<&str> = table.iter().map(|(_, name, _)| *name).collect();
+    if allowed.contains(&"GET") && !allowed.contains(&"HEAD") {
+        allowed.push("HEAD");
+    }
+    let options = allowed.contains(&"OPTIONS");
+    if !options {
+        allowed.push("OPTIONS");
+    }
+    let allow = allowed.join(", ");
+    let mut router = MethodRouter::new();
+    for (filter, _, value) in table {
+        router = router.on(filter, handler(value));
+    }
+    if !options {
+        router = router.on(
+            MethodFilter::OPTIONS,
+            fixed(StatusCode::NO_CONTENT, allow.clone()),
+        );
+    }
+    router.fallback(fixed(StatusCode::METHOD_NOT_ALLOWED, allow))
+}
+
+fn join(prefix: &str, path: &str) -> String {
+    match prefix.trim_end_matches('/') {
+        "" => path.to_string(),
+        prefix if path == "/" => prefix.to_string(),
+        prefix => format!("{}{}", prefix, path),
+    }
+}
+
+fn collect_routes(
+    prefix: &str,
+    routes: &Expression,
+    middleware: &Vector<Expression>,
+    out: &mut Vec<(String, Expression, Vector<Expression>)>,
+) -> Result<()> {
+    let routes_key = Expression::Keyword(":routes".to_string());
+    for (k, v) in extract::map(routes.clone())?.iter() {
+        let path = join(prefix, &extract::string(k.clone())?);
+        match v {
+            Expression::Map(group) if group.contains_key(&routes_key) => {
+                let mut middleware = middleware.clone();
+                if let Some(extra) = group.get(&Expression::Keyword(":middleware".to_string())) {
+                    middleware.append(extract::array(extra.clone())?);
+                }
+                collect_routes(&path, &group[&routes_key], &middleware, out)?;
+            }
+            value => {
+                if out.iter().any(|(existing, _, _)| existing == &path) {
+                    return Err(error(&format!("Duplicate route {}", path)));
+                }
+                out.push((path, value.clone(), middleware.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn constant(value: Expression) -> Expression {
+    Expression::Function(Function {
+        env: Environment::new(),
+        patterns: vector![Pattern {
+            parameters: vector![Expression::Symbol("request".to_string())],
+            body: vector![Expression::Quote(Box::new(value))],
+        }],
+    })
+}
+
+async fn wrap_handler(
+    env: &Environment,
+    handler: Expression,
+    middleware: &[Expression],
+) -> Result<Expression> {
+    let mut handler = match handler {
+        function @ (Expression::Function(_) | Expression::Closure(_) | NativeFunction(_)) => {
+            function
+        }
+        value => constant(value),
+    };
+    for middleware in middleware.iter().rev() {
+        handler = call(env, middleware.clone(), handler).await?;
+    }
+    Ok(handler)
+}
+
+async fn wrap(
+    env: &Environment,
+    value: Expression,
+    middleware: &[Expression],
+) -> Result<Expression> {
+    if middleware.is_empty() || files::mounted(&value) {
+        return Ok(value);
+    }
+    if let (Expression::Map(table), Some(_)) = (&value, method_table(&value)) {
+        let mut wrapped = OrdMap::new();
+        for (method, handler) in table.iter() {
+            let handler = wrap_handler(env, handler.clone(), middleware).await?;
+            wrapped.insert(method.clone(), handler);
+        }
+        return Ok(Expression::Map(wrapped));
+    }
+    wrap_handler(env, value, middleware).await
+}
+
 async fn server(env: Environment, args: Vector<Expression>) -> Result<(Environment, Expression)> {
     let (env, arg) = crate::evaluate(env, args[0].clone()).await?;
     let m = extract::map(arg)?;
-    let default_port = Expression::Integer(Integer::from(3000));
-    let port = m
-        .get(&Expression::Keyword(":port".to_string()))
-        .unwrap_or(&default_port);
-    stop_server_by_port(env.clone(), port.clone()).await?;
-    let port = extract::integer(port.clone())?;
-    let port = port
-        .to_u16()
-        .ok_or_else(|| error("Port number out of range"))?;
+    let addr = address(&m)?;
+    let socket = match m.get(&Expression::Keyword(":socket".to_string())) {
+        Some(socket) => Some(extract::string(socket.clone())?),
+        None => None,
+    };
+    let numbers = Numbers::from_options(&env, Some(&Expression::Map(m.clone())))?;
+    let context = Context {
+        env: env.clone(),
+        numbers,
+        on_error: m
+            .get(&Expression::Keyword(":on-error".to_string()))
+            .cloned(),
+        builtins: Arc::new(Vec::new()),
+    };
+    let middleware = match m.get(&Expression::Keyword(":middleware".to_string())) {
+        Some(middleware) => extract::array(middleware.clone())?,
+        None => Vector::new(),
+    };
+    let mut routes = Vec::new();
+    if let Some(r) = m.get(&Expression::Keyword(":routes".to_string())) {
+        collect_routes("", r, &middleware, &mut routes)?;
+    }
     let mut app = Router::new();
-    if let Some(routes) = m.get(&Expression::Keyword(":routes".to_string())) {
-        let m = extract::map(routes.clone())?;
-        for (k, v) in m.iter() {
-            let path = extract::string(k.clone())?;
-            match v.clone() {
-                Expression::Function(patterns) => {
-                    let env = env.clone();
-                    let cloned_path = path.clone();
-                    let handler = async move |req: Request<Body>| {
-                        let (_, expr) = evaluate(
-                            env,
-                            Expression::Call(Call {
-                                function: Box::new(Expression::Function(patterns.clone())),
-                                arguments: vector![request_map(&cloned_path, req).await.unwrap()],
-                            }),
-                        )
-                        .await
-                        .unwrap();
-                        create_handler(expr).await
-                    };
-                    app = app.route(&path, get(handler.clone()));
-                    app = app.route(&path, post(handler.clone()));
-                    app = app.route(&path, delete(handler.clone()));
-                    app = app.route(&path, put(handler));
-                }
-                _ => {
-                    let v = v.clone();
-                    let handler = async move |_req: Request<Body>| create_handler(v).await;
-                    app = app.route(&path, get(handler.clone()));
-                    app = app.route(&path, post(handler.clone()));
-                    app = app.route(&path, delete(handler.clone()));
-                    app = app.route(&path, put(handler));
+    for (path, value, layers) in routes {
+        let mut functions = Vec::new();
+        let mut builtins = Vec::new();
+        for layer in layers.iter() {
+            match layer {
+                Expression::Function(_) | Expression::Closure(_) | NativeFunction(_) => {
+                    functions.push(layer.clone())
                 }
+                other => builtins.push(middleware::builtin(other)?),
             }
         }
+        let value = wrap(&env, value, &functions).await?;
+        let context = Context {
+            builtins: Arc::new(builtins),
+            ..context.clone()
+        };
+        if files::mounted(&value) {
+            let base = path.trim_end_matches('/');
+            let mut paths = vec![format!("{}/", base), format!("{}/*path", base)];
+            if !base.is_empty() {
+                paths.push(base.to_string());
+            }
+            for mount in paths {
+                app = app.route(&mount, route(&context, &path, value.clone()));
+            }
+        } else {
+            app = app.route(&path, route(&context, &path, value));
+        }
     }
-    let (tx, mut rx) = broadcast::channel(1);
-    tokio::spawn(async move {
-        let socket = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
-        axum::Server::bind(&socket)
-            .serve(app.into_make_service())
-            .with_graceful_shutdown(async {
-                rx.recv().await.ok();
-            })
-            .await
-            .unwrap();
-    });
-    let server = NativeType::new(Server { tx, port }, "server".to_string());
+    let (tx, rx) = broadcast::channel(1);
+    let tls = tls_config(&m).await?;
+    if socket.is_some() && tls.is_some() {
+        return Err(error("Unix sockets do not support :cert and :key"));
+    }
+    // only replace a running server once the new one is known to be valid
+    let previous = match &socket {
+        Some(socket) => Expression::String(socket.clone()),
+        None => Expression::String(addr.to_string()),
+    };
+    stop_server(env.clone(), previous).await?;
+    let listener = match socket {
+        Some(socket) => serve_unix(socket, app, rx).await?,
+        None => serve_tcp(addr, tls, app, rx).await?,
+    };
+    let key = listener.key();
+    let server = NativeType::new(Server { tx, listener }, "server".to_string());
     let server = Expression::NativeType(server);
     let http = extract::module(env.get("http").unwrap().clone())?;
     let servers = extract::atom(http.get("*servers*").unwrap().clone())?;
     let mut guard = servers.0.lock().await;
     let mut servers = extract::map(guard.clone())?;
-    servers.insert(Expression::Integer(Integer::from(port)), server.clone());
+    servers.insert(key, server.clone());
     *guard = Expression::Map(servers);
     Ok((env, server))
 }
 
-async fn stop_server_by_port(
+/// The address a server binds to, which together with the actual port is
+/// also how servers are found again to stop them.
+fn address(options: &OrdMap<Expression, Expression>) -> Result<SocketAddr> {
+    let port = match options.get(&Expression::Keyword(":port".to_string())) {
+        Some(port) => extract::integer(port.clone())?
+            .to_u16()
+            .ok_or_else(|| error("Port number out of range"))?,
+        None => 3000,
+    };
+    Ok(SocketAddr::new(host(options)?, port))
+}
+
+fn host(options: &OrdMap<Expression, Expression>) -> Result<IpAddr> {
+    let Some(host) = options.get(&Expression::Keyword(":host".to_string())) else {
+        return Ok(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    };
+    match &extract::string(host.clone())?[..] {
+        "localhost" => Ok(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        host => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .map_err(|_| error(&format!("Invalid host {}", host))),
+    }
+}
+
+async fn tls_config(options: &OrdMap<Expression, Expression>) -> Result<Option<RustlsConfig>> {
+    let cert = options.get(&Expression::Keyword(":cert".to_string()));
+    let key = options.get(&Expression::Keyword(":key".to_string()));
+    match (cert, key) {
+        (None, None) => Ok(None),
+        (Some(cert), Some(key)) => {
+            let cert = extract::string(cert.clone())?;
+            let key = extract::string(key.clone())?;
+            RustlsConfig::from_pem_file(&cert, &key)
+                .await
+                .map(Some)
+                .map_err(|e| error(&format!("Could not load {} and {}: {}", cert, key, e)))
+        }
+        _ => Err(error("Expected both :cert and :key")),
+    }
+}
+
+async fn bind(addr: SocketAddr) -> Result<std::net::TcpListener> {
+    let mut attempts = 0;
+    loop {
+        match std::net::TcpListener::bind(addr) {
+            Ok(listener) => {
+                listener
+                    .set_nonblocking(true)
+                    .map_err(|e| error(&format!("Could not bind {}: {}", addr, e)))?;
+                return Ok(listener);
+            }
+            // a server we just stopped on this port may not have released it yet
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && attempts < 50 => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Err(e) => return Err(error(&format!("Could not bind {}: {}", addr, e))),
+        }
+    }
+}
+
+async fn serve_tcp(
+    addr: SocketAddr,
+    tls: Option<RustlsConfig>,
+    app: Router,
+    mut rx: broadcast::Receiver<()>,
+) -> Result<Listener> {
+    let listener = bind(addr).await?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| error(&format!("Could not bind {}: {}", addr, e)))?;
+    let secure = tls.is_some();
+    match tls {
+        None => {
+            let server = axum::Server::from_tcp(listener)
+                .map_err(|e| error(&format!("Could not bind {}: {}", addr, e)))?;
+            tokio::spawn(async move {
+                let served = server
+                    .serve(app.into_make_service())
+                    .with_graceful_shutdown(async {
+                        rx.recv().await.ok();
+                    })
+                    .await;
+                if let Err(e) = served {
+                    middleware::log(format_args!("Server on {} failed: {}", addr, e));
+                }
+            });
+        }
+        Some(config) => {
+            let handle = axum_server::Handle::new();
+            let shutdown = handle.clone();
+            tokio::spawn(async move {
+                rx.recv().await.ok();
+                shutdown.graceful_shutdown(None);
+            });
+            tokio::spawn(async move {
+                let served = axum_server::from_tcp_rustls(listener, config)
+                    .handle(handle)
+                    .serve(app.into_make_service())
+                    .await;
+                if let Err(e) = served {
+                    middleware::log(format_args!("Server on {} failed: {}", addr, e));
+                }
+            });
+        }
+    }
+    Ok(Listener::Tcp(addr, secure))
+}
+
+#[cfg(unix)]
+async fn serve_unix(
+    path: String,
+    app: Router,
+    mut rx: broadcast::Receiver<()>,
+) -> Result<Listener> {
+    use std::io::ErrorKind;
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    let mut attempts = 0;
+    while std::fs::metadata(&path).map_or(false, |m| m.file_type().is_socket()) {
+        match tokio::net::UnixStream::connect(&path).await {
+            // nothing is listening, so a server that crashed left the socket behind
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                std::fs::remove_file(&path)
+                    .map_err(|e| error(&format!("Could not remove {}: {}", path, e)))?;
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            // a server we just stopped on this socket may not have shut down yet
+            Ok(_) if attempts < 50 => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(_) => return Err(error(&format!("Could not bind {}: already in use", path))),
+            Err(e) => return Err(error(&format!("Could not connect to {}: {}", path, e))),
+        }
+    }
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| error(&format!("Could not bind {}: {}", path, e)))?;
+    let inode = std::fs::metadata(&path).map_or(0, |m| m.ino());
+    let incoming = stream::unfold(listener, |listener| async move {
+        let connection = listener.accept().await.map(|(connection, _)| connection);
+        Some((connection, listener))
+    });
+    let socket = path.clone();
+    tokio::spawn(async move {
+        let served = axum::Server::builder(hyper::server::accept::from_stream(incoming))
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(async {
+                rx.recv().await.ok();
+            })
+            .await;
+        if let Err(e) = served {
+            middleware::log(format_args!("Server on {} failed: {}", socket, e));
+        }
+        // leave the path alone if a new server has bound it since
+        if std::fs::metadata(&socket).map_or(false, |m| m.ino() == inode) {
+            std::fs::remove_file(&socket).ok();
+        }
+    });
+    Ok(Listener::Unix(path))
+}
+
+#[cfg(not(unix))]
+async fn serve_unix(path: String, _app: Router, _rx: broadcast::Receiver<()>) -> Result<Listener> {
+    Err(error(&format!(
+        "Cannot listen on {}, unix sockets are not supported",
+        path
+    )))
+}
+
+async fn server_info(
     env: Environment,
-    port: Expression,
+    args: Vector<Expression>,
 ) -> Result<(Environment, Expression)> {
+    let (env, arg) = crate::evaluate(env, args[0].clone()).await?;
+    let Expression::NativeType(server) = arg else {
+        return Err(error("Expected server"));
+    };
+    let server = server.value.lock().await;
+    let server = server
+        .downcast_ref::<Server>()
+        .ok_or_else(|| error("Expected server"))?;
+    Ok((env, server.listener.info()))
+}
+
+async fn stop_server(env: Environment, key: Expression) -> Result<(Environment, Expression)> {
     let http = extract::module(env.get("http").unwrap().clone())?;
     let servers = extract::atom(http.get("*servers*").unwrap().clone())?;
     let mut guard = servers.0.lock().await;
     let mut servers = extract::map(guard.clone())?;
-    if let Some(Expression::NativeType(server)) = servers.remove(&port) {
+    if let Some(Expression::NativeType(server)) = servers.remove(&key) {
         let server = server.value.lock().await;
         let server = server
             .downcast_ref::<Server>()
             .ok_or_else(|| error("Expected server"))?;
-        server.tx.send(()).unwrap();
+        let _ = server.tx.send(());
     }
     *guard = Expression::Map(servers);
     Ok((env, Expression::Nil))
@@ -419,26 +1564,26 @@ async fn server_stop(
     let (env, arg) = crate::evaluate(env, args[0].clone()).await?;
     match arg {
         Expression::NativeType(server) => {
-            let port = {
+            let key = {
                 let server = server.value.lock().await;
                 let server = server
                     .downcast_ref::<Server>()
                     .ok_or_else(|| error("Expected server"))?;
-                server.tx.send(()).unwrap();
-                server.port
+                let _ = server.tx.send(());
+                server.listener.key()
             };
             let http = extract::module(env.get("http").unwrap().clone())?;
             let servers = extract::atom(http.get("*servers*").unwrap().clone())?;
             let mut guard = servers.0.lock().await;
             let mut servers = extract::map(guard.clone())?;
-            servers.remove(&Expression::Integer(Integer::from(port)));
+            servers.remove(&key);
             *guard = Expression::Map(servers);
             Ok((env, Expression::Nil))
         }
-        Expression::Map(map) => {
-            let port = extract::key(map.clone(), ":port")?;
-            stop_server_by_port(env, port).await
-        }
+        Expression::Map(map) => match map.get(&Expression::Keyword(":socket".to_string())) {
+            Some(socket) => stop_server(env, socket.clone()).await,
+            None => stop_server(env, Expression::String(address(&map)?.to_string())).await,
+        },
         _ => return Err(error("Expected server")),
     }
 }
@@ -447,8 +1592,14 @@ pub fn environment() -> Environment {
     ordmap! {
         "*name*".to_string() => Expression::String("http".to_string()),
         "*servers*".to_string() => Expression::Atom(Atom::new(Expression::Map(ordmap! {}))),
+        "*client*".to_string() => Expression::NativeType(NativeType::new(
+            Client { client: reqwest::Client::new(), base_url: None, retry: None, breaker: None },
+            "http/client".to_string(),
+        )),
+        "client".to_string() => NativeFunction(|env, args| Box::pin(client(env, args))),
         "request".to_string() => NativeFunction(|env, args| Box::pin(request(env, args))),
         "server".to_string() => NativeFunction(|env, args| Box::pin(server(env, args))),
-        "server-stop".to_string() => NativeFunction(|env, args| Box::pin(server_stop(env, args)))
+        "server-stop".to_string() => NativeFunction(|env, args| Box::pin(server_stop(env, args))),
+        "server-info".to_string() => NativeFunction(|env, args| Box::pin(server_info(env, args)))
     }
 }
@@ -0,0 +1,530 @@
+extern crate alloc;
+extern crate std;
+
+use crate::{call, Result};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use axum::body::boxed;
+use axum::http::Request;
+use axum::response::{IntoResponse, Response};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use compiler::effect::error;
+use compiler::expression::Environment;
+use compiler::extract;
+use compiler::Expression;
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use hyper::body::HttpBody;
+use hyper::header::{
+    ACCEPT_ENCODING, ACCEPT_RANGES, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+    ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+    ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD,
+    AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, ORIGIN, VARY,
+    WWW_AUTHENTICATE,
+};
+use hyper::{Body, Method, StatusCode};
+use im::{ordmap, OrdMap};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::eprintln;
+use std::io::{self, Write};
+use tokio::time::Instant;
+
+pub struct Cors {
+    origins: Option<Vec<String>>,
+    methods: Option<String>,
+    headers: Option<String>,
+    expose: Option<String>,
+    credentials: bool,
+    max_age: Option<u64>,
+}
+
+pub struct Auth {
+    realm: String,
+    credentials: Vec<String>,
+    verify: Option<Expression>,
+}
+
+pub enum Builtin {
+    Cors(Cors),
+    Compress(usize),
+    RequestId(HeaderName),
+    BasicAuth(Auth),
+    BearerAuth(Auth),
+    AccessLog,
+    BodyLimit(usize),
+}
+
+pub struct Exchange {
+    method: Method,
+    path: String,
+    headers: HeaderMap,
+    started: Instant,
+}
+
+impl Exchange {
+    pub fn new(req: &Request<Body>) -> Exchange {
+        Exchange {
+            method: req.method().clone(),
+            path: req.uri().path().to_string(),
+            headers: req.headers().clone(),
+            started: Instant::now(),
+        }
+    }
+}
+
+/// Writes a line to the server log on stderr, shared by :access-log and
+/// handler errors so they interleave in one place.
+pub fn log(line: core::fmt::Arguments) {
+    eprintln!("{}", line);
+}
+
+fn names(options: &OrdMap<Expression, Expression>, key: &str) -> Result<Option<Vec<String>>> {
+    match options.get(&Expression::Keyword(key.to_string())) {
+        None => Ok(None),
+        Some(Expression::String(s)) if s == "*" => Ok(None),
+        Some(values) => extract::array(values.clone())?
+            .iter()
+            .map(|value| match value {
+                Expression::String(s) => Ok(s.clone()),
+                Expression::Keyword(k) => Ok(k[1..].to_string()),
+                _ => Err(error(&format!("Expected {} to contain strings", key))),
+            })
+            .collect::<Result<Vec<String>>>()
+            .map(Some),
+    }
+}
+
+fn size(options: &Expression, key: &str, default: usize) -> Result<usize> {
+    let value = match options {
+        Expression::Map(map) => match map.get(&Expression::Keyword(key.to_string())) {
+            Some(value) => value.clone(),
+            None => return Ok(default),
+        },
+        Expression::Nil => return Ok(default),
+        value => value.clone(),
+    };
+    extract::integer(value.clone())?
+        .to_usize()
+        .ok_or_else(|| error(&format!("Expected {} to be a positive integer", key)))
+}
+
+fn auth(options: &OrdMap<Expression, Expression>, scheme: &str) -> Result<Auth> {
+    let realm = match options.get(&Expression::Keyword(":realm".to_string())) {
+        Some(realm) => extract::string(realm.clone())?,
+        None => "yeti".to_string(),
+    };
+    let credentials = match scheme {
+        "Basic" => match options.get(&Expression::Keyword(":users".to_string())) {
+            Some(users) => extract::map(users.clone())?
+                .iter()
+                .map(|(user, password)| -> Result<String> {
+                    let user = match user {
+                        Expression::Keyword(k) => k[1..].to_string(),
+                        user => extract::string(user.clone())?,
+                    };
+                    Ok(format!("{}:{}", user, extract::string(password.clone())?))
+                })
+                .collect::<Result<Vec<String>>>()?,
+            None => Vec::new(),
+        },
+        _ => names(options, ":tokens")?.unwrap_or_default(),
+    };
+    Ok(Auth {
+        realm,
+        credentials,
+        verify: options
+            .get(&Expression::Keyword(":verify".to_string()))
+            .cloned(),
+    })
+}
+
+pub fn builtin(middleware: &Expression) -> Result<Builtin> {
+    let (name, options) = match middleware {
+        Expression::Keyword(name) => (name.clone(), Expression::Nil),
+        Expression::Array(a) if a.len() == 2 => (extract::keyword(a[0].clone())?, a[1].clone()),
+        other => return Err(error(&format!("Unsupported middleware {}", other))),
+    };
+    let map = match &options {
+        Expression::Map(map) => map.clone(),
+        _ => OrdMap::new(),
+    };
+    let header = |key: &str| -> Result<Option<String>> {
+        match map.get(&Expression::Keyword(key.to_string())) {
+            Some(value) => Ok(Some(extract::string(value.clone())?)),
+            None => Ok(None),
+        }
+    };
+    match &name[..] {
+        ":cors" => Ok(Builtin::Cors(Cors {
+            origins: names(&map, ":origins")?,
+            methods: names(&map, ":methods")?.map(|m| m.join(", ").to_uppercase()),
+            headers: names(&map, ":headers")?.map(|h| h.join(", ")),
+            expose: names(&map, ":expose")?.map(|h| h.join(", ")),
+            credentials: map
+                .get(&Expression::Keyword(":credentials".to_string()))
+                .map_or(false, base::truthy),
+            max_age: match map.get(&Expression::Keyword(":max-age".to_string())) {
+                Some(age) => Some(size(age, ":max-age", 0)? as u64),
+                None => None,
+            },
+        })),
+        ":compress" => Ok(Builtin::Compress(size(&options, ":min-bytes", 1024)?)),
+        ":request-id" => {
+            let name = header(":header")?.unwrap_or_else(|| "x-request-id".to_string());
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| error(&format!("Invalid header name {}", name)))?;
+            Ok(Builtin::RequestId(name))
+        }
+        ":basic-auth" => Ok(Builtin::BasicAuth(auth(&map, "Basic")?)),
+        ":bearer-auth" => Ok(Builtin::BearerAuth(auth(&map, "Bearer")?)),
+        ":access-log" => Ok(Builtin::AccessLog),
+        ":body-limit" => Ok(Builtin::BodyLimit(size(&options, ":bytes", 1024 * 1024)?)),
+        _ => Err(error(&format!("Unknown middleware {}", name))),
+    }
+}
+
+impl Cors {
+    fn allowed(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        match &self.origins {
+            None if !self.credentials => Some(HeaderValue::from_static("*")),
+            None => Some(origin.clone()),
+            Some(origins) => origins
+                .iter()
+                .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+                .then(|| origin.clone()),
+        }
+    }
+
+    fn preflight(&self, req: &Request<Body>) -> Option<Response> {
+        let origin = req.headers().get(ORIGIN)?;
+        let method = req.headers().get(ACCESS_CONTROL_REQUEST_METHOD)?;
+        if req.method() != Method::OPTIONS {
+            return None;
+        }
+        let Some(allowed) = self.allowed(origin) else {
+            return Some(StatusCode::FORBIDDEN.into_response());
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+        headers.insert(VARY, HeaderValue::from_static("Origin"));
+        let methods = match &self.methods {
+            Some(methods) => HeaderValue::from_str(methods).ok(),
+            None => Some(method.clone()),
+        };
+        if let Some(methods) = methods {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        let allow_headers = match &self.headers {
+            Some(allowed) => HeaderValue::from_str(allowed).ok(),
+            None => req.headers().get(ACCESS_CONTROL_REQUEST_HEADERS).cloned(),
+        };
+        if let Some(allow_headers) = allow_headers {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allow_headers);
+        }
+        if self.credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        if let Some(age) = self.max_age {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(age));
+        }
+        Some((StatusCode::NO_CONTENT, headers).into_response())
+    }
+
+    fn decorate(&self, exchange: &Exchange, response: &mut Response) {
+        let Some(allowed) = exchange.headers.get(ORIGIN).and_then(|o| self.allowed(o)) else {
+            return;
+        };
+        let headers = response.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+        headers.append(VARY, HeaderValue::from_static("Origin"));
+        if self.credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        if let Some(expose) = self
+            .expose
+            .as_ref()
+            .and_then(|e| HeaderValue::from_str(e).ok())
+        {
+            headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, expose);
+        }
+    }
+}
+
+/// Compares without stopping at the first differing byte, so response times
+/// don't reveal how much of a secret was guessed.
+fn equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl Auth {
+    async fn check(&self, env: &Environment, scheme: &str, headers: &HeaderMap) -> Result<bool> {
+        let credentials = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(given, _)| given.eq_ignore_ascii_case(scheme))
+            .map(|(_, credentials)| credentials.trim().to_string());
+        let Some(credentials) = credentials else {
+            return Ok(false);
+        };
+        let credentials = match scheme {
+            "Basic" => match STANDARD.decode(credentials).ok().map(String::from_utf8) {
+                Some(Ok(decoded)) => decoded,
+                _ => return Ok(false),
+            },
+            _ => credentials,
+        };
+        let known = self.credentials.iter().fold(false, |found, known| {
+            equal(known.as_bytes(), credentials.as_bytes()) | found
+        });
+        if known {
+            return Ok(true);
+        }
+        let Some(verify) = &self.verify else {
+            return Ok(false);
+        };
+        let argument = match (scheme, credentials.split_once(':')) {
+            ("Basic", Some((user, password))) => Expression::Map(ordmap! {
+                Expression::Keyword(":user".to_string()) => Expression::String(user.to_string()),
+                Expression::Keyword(":password".to_string()) => Expression::String(password.to_string())
+            }),
+            ("Basic", None) => return Ok(false),
+            _ => Expression::String(credentials),
+        };
+        Ok(base::truthy(&call(env, verify.clone(), argument).await?))
+    }
+
+    fn challenge(&self, scheme: &str) -> Response {
+        let challenge = format!("{} realm=\"{}\"", scheme, self.realm);
+        let mut response = StatusCode::UNAUTHORIZED.into_response();
+        if let Ok(challenge) = HeaderValue::from_str(&challenge) {
+            response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+        }
+        response
+    }
+}
+
+async fn limit(req: &mut Request<Body>, limit: usize) -> Result<Option<Response>> {
+    let too_large = || Ok(Some(StatusCode::PAYLOAD_TOO_LARGE.into_response()));
+    let declared = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok());
+    if declared.map_or(false, |length| length > limit) {
+        return too_large();
+    }
+    let mut body = core::mem::take(req.body_mut());
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| error(&format!("Failed to read body: {}", e)))?;
+        if buffer.len() + chunk.len() > limit {
+            return too_large();
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    *req.body_mut() = Body::from(buffer);
+    Ok(None)
+}
+
+pub async fn before(
+    builtin: &Builtin,
+    env: &Environment,
+    req: &mut Request<Body>,
+) -> Result<Option<Response>> {
+    match builtin {
+        Builtin::Cors(cors) => Ok(cors.preflight(req)),
+        Builtin::RequestId(name) => {
+            if !req.headers().contains_key(name) {
+                let id = uuid::Uuid::new_v4().to_string();
+                let id = HeaderValue::from_str(&id).map_err(|_| error("Invalid request id"))?;
+                req.headers_mut().insert(name.clone(), id);
+            }
+            Ok(None)
+        }
+        Builtin::BasicAuth(auth) => match auth.check(env, "Basic", req.headers()).await? {
+            true => Ok(None),
+            false => Ok(Some(auth.challenge("Basic"))),
+        },
+        Builtin::BearerAuth(auth) => match auth.check(env, "Bearer", req.headers()).await? {
+            true => Ok(None),
+            false => Ok(Some(auth.challenge("Bearer"))),
+        },
+        Builtin::BodyLimit(bytes) => limit(req, *bytes).await,
+        Builtin::Compress(_) | Builtin::AccessLog => Ok(None),
+    }
+}
+
+fn encoding(headers: &HeaderMap) -> Option<&'static str> {
+    let accepted: Vec<(String, bool)> = headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|coding| {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or("").to_ascii_lowercase();
+            let refused = parts.any(|p| p.replace(' ', "") == "q=0" || p == "q=0.0");
+            (name, !refused)
+        })
+        .collect();
+    ["gzip", "deflate"].into_iter().find(|coding| {
+        accepted
+            .iter()
+            .any(|(name, ok)| name.as_str() == *coding && *ok)
+    })
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(DeflateEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(coding: &str) -> Encoder {
+        match coding {
+            "gzip" => Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::default())),
+            _ => Encoder::Deflate(DeflateEncoder::new(Vec::new(), Compression::default())),
+        }
+    }
+
+    fn write(&mut self, chunk: &[u8]) -> io::Result<Bytes> {
+        let output = match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.get_mut()
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.get_mut()
+            }
+        };
+        Ok(Bytes::from(core::mem::take(output)))
+    }
+
+    fn finish(self) -> io::Result<Bytes> {
+        let output = match self {
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Deflate(encoder) => encoder.finish()?,
+        };
+        Ok(Bytes::from(output))
+    }
+}
+
+fn compressible(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers.get(CONTENT_TYPE).and_then(|t| t.to_str().ok()) else {
+        return true;
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    match essence.split_once('/') {
+        Some(("text", "event-stream")) => false,
+        Some(("text", _) | ("image", "svg+xml")) => true,
+        Some(("image" | "audio" | "video" | "font", _)) => false,
+        Some((_, subtype)) => !matches!(
+            subtype,
+            "zip"
+                | "gzip"
+                | "x-gzip"
+                | "x-bzip2"
+                | "x-xz"
+                | "x-7z-compressed"
+                | "x-rar-compressed"
+                | "zstd"
+                | "octet-stream"
+                | "pdf"
+        ),
+        None => true,
+    }
+}
+
+/// Compresses the body as it streams. Bodies that are already encoded,
+/// can't shrink, or carry no content are passed through untouched.
+async fn compress(exchange: &Exchange, response: Response, min_bytes: usize) -> Response {
+    let Some(coding) = encoding(&exchange.headers) else {
+        return response;
+    };
+    let status = response.status();
+    let skipped = exchange.method == Method::HEAD
+        || status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::PARTIAL_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || response.headers().contains_key(CONTENT_ENCODING)
+        || !compressible(response.headers());
+    let small = response
+        .body()
+        .size_hint()
+        .exact()
+        .map_or(false, |length| length < min_bytes as u64);
+    if skipped || small {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let state = Some((body, Encoder::new(coding)));
+    let stream = futures::stream::unfold(state, |state| async move {
+        let (mut body, mut encoder) = state?;
+        match body.data().await {
+            Some(Ok(chunk)) => Some((encoder.write(&chunk), Some((body, encoder)))),
+            Some(Err(e)) => Some((Err(io::Error::new(io::ErrorKind::Other, e)), None)),
+            None => Some((encoder.finish(), None)),
+        }
+    });
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.remove(ACCEPT_RANGES);
+    let weak = parts
+        .headers
+        .get(ETAG)
+        .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+        .and_then(|etag| HeaderValue::from_bytes(&[b"W/", etag.as_bytes()].concat()).ok());
+    if let Some(weak) = weak {
+        parts.headers.insert(ETAG, weak);
+    }
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static(coding));
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+    Response::from_parts(parts, boxed(Body::wrap_stream(stream)))
+}
+
+pub async fn after(builtin: &Builtin, exchange: &Exchange, mut response: Response) -> Response {
+    match builtin {
+        Builtin::Cors(cors) => {
+            cors.decorate(exchange, &mut response);
+            response
+        }
+        Builtin::Compress(min_bytes) => compress(exchange, response, *min_bytes).await,
+        Builtin::RequestId(name) => {
+            if let Some(id) = exchange.headers.get(name) {
+                response.headers_mut().insert(name.clone(), id.clone());
+            }
+            response
+        }
+        Builtin::AccessLog => {
+            log(format_args!(
+                "{} {} {} {}ms",
+                exchange.method,
+                exchange.path,
+                response.status().as_u16(),
+                exchange.started.elapsed().as_millis()
+            ));
+            response
+        }
+        Builtin::BasicAuth(_) | Builtin::BearerAuth(_) | Builtin::BodyLimit(_) => response,
+    }
+}
@@ -1,6 +1,3 @@
-use compiler;
-use http;
-
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
 fn without_date(
@@ -788,7 +785,7 @@ async fn streaming_response_from_endpoint() -> Result {
         (spawn
           (let [_ (put! c "Hello")
                 _ (put! c "Goodbye")
-                _ (put! c nil)]
+                _ (close! c)]
             nil))
         c))
     "#,
@@ -830,11 +827,886 @@ async fn streaming_response_from_endpoint() -> Result {
     assert_eq!(actual, compiler::Expression::String("Hello".to_string()));
     let (env, actual) = compiler::evaluate_source(env, "(take! c)").await?;
     assert_eq!(actual, compiler::Expression::String("Goodbye".to_string()));
+    let closed = compiler::channel::closed();
     let (env, actual) = compiler::evaluate_source(env, "(take! c)").await?;
-    assert_eq!(actual, compiler::Expression::Nil);
+    assert_eq!(actual, closed);
     let (env, actual) = compiler::evaluate_source(env, "(take! c)").await?;
-    assert_eq!(actual, compiler::Expression::Nil);
+    assert_eq!(actual, closed);
     let (_, actual) = compiler::evaluate_source(env, "(take! c)").await?;
-    assert_eq!(actual, compiler::Expression::Nil);
+    assert_eq!(actual, closed);
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_with_every_method() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"(http/server {:port 3020 :routes {"/" (fn [req] (:method req))}})"#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        [(:text (http/request {:url "http://localhost:3020" :method :put}))
+         (:text (http/request {:url "http://localhost:3020" :method :delete}))
+         (:status (http/request {:url "http://localhost:3020" :method :head}))]
+        "#,
+    )
+    .await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"["PUT" "DELETE" 200]"#).await?;
+    assert_eq!(actual, expected);
+    let result = compiler::evaluate_source(
+        env,
+        r#"(http/request {:url "http://localhost:3020" :method :fetch})"#,
+    )
+    .await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_with_client() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"(http/server {:port 3021 :routes {"/token" (fn [req] (:x-token (:headers req)))}})"#,
+    )
+    .await?;
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (def client (http/client {:base-url "http://localhost:3021"
+                                  :headers {:x-token "secret"}
+                                  :timeout-ms 5000}))
+        "#,
+    )
+    .await?;
+    let (env, actual) =
+        compiler::evaluate_source(env, r#"(:text (http/request client {:url "/token"}))"#).await?;
+    assert_eq!(actual, compiler::Expression::String("secret".to_string()));
+    let (_, actual) = compiler::evaluate_source(
+        env,
+        r#"(:text (http/request client {:url "/token" :headers {:x-token "override"}}))"#,
+    )
+    .await?;
+    assert_eq!(actual, compiler::Expression::String("override".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_with_raw_body() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"(http/server {:port 3022 :routes {"/" (fn [req] (:json req))}})"#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (:json (http/request {:url "http://localhost:3022"
+                              :method :post
+                              :headers {:content-type "application/json"}
+                              :body "{\"total\": 3}"}))
+        "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, "{:total 3}").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_with_response_format() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) =
+        compiler::evaluate_source(env, r#"(http/server {:port 3023 :routes {"/" "[1, 2]"}})"#)
+            .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"(:json (http/request {:url "http://localhost:3023" :as :json}))"#,
+    )
+    .await?;
+    let (env, expected) = compiler::evaluate_source(env, "[1 2]").await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"(:body (http/request {:url "http://localhost:3023" :as :bytes}))"#,
+    )
+    .await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::Bytes(bytes::Bytes::from_static(b"[1, 2]"))
+    );
+    let result = compiler::evaluate_source(
+        env,
+        r#"(http/request {:url "http://localhost:3023" :as :xml})"#,
+    )
+    .await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_and_respond_with_bytes() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"(http/server {:port 3024 :routes {"/" (fn [req] (concat (:body req) #x"ff"))}})"#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (http/request {:url "http://localhost:3024"
+                       :method :post
+                       :headers {:content-type "application/octet-stream"}
+                       :body #x"00010203"})
+        "#,
+    )
+    .await?;
+    let actual = compiler::extract::map(actual)?;
+    let (_, expected) = compiler::evaluate_source(env, r#"#x"00010203ff""#).await?;
+    assert_eq!(
+        actual.get(&compiler::Expression::Keyword(":body".to_string())),
+        Some(&expected)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_timeout() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"(http/server {:port 3025 :routes {"/" (fn [req] (take! (timeout 1000)) "late")}})"#,
+    )
+    .await?;
+    let result = compiler::evaluate_source(
+        env,
+        r#"(http/request {:url "http://localhost:3025" :timeout-ms 50})"#,
+    )
+    .await;
+    let message = format!("{}", result.unwrap_err());
+    assert!(message.contains("GET request to http://localhost:3025"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_retries_connection_errors() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let result = compiler::evaluate_source(
+        env,
+        r#"(http/request {:url "http://localhost:3026" :retry {:attempts 3 :backoff-ms 10}})"#,
+    )
+    .await;
+    let message = format!("{}", result.unwrap_err());
+    assert!(message.contains("http://localhost:3026 after 3 attempts"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_retries_only_idempotent_methods_by_default() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let result = compiler::evaluate_source(
+        env.clone(),
+        r#"(http/request {:url "http://localhost:3026" :method :post :retry {:attempts 3 :backoff-ms 10}})"#,
+    )
+    .await;
+    let message = format!("{}", result.unwrap_err());
+    assert!(!message.contains("attempts"));
+    let result = compiler::evaluate_source(
+        env,
+        r#"(http/request {:url "http://localhost:3026" :method :post :retry {:attempts 3 :backoff-ms 10 :methods [:post]}})"#,
+    )
+    .await;
+    let message = format!("{}", result.unwrap_err());
+    assert!(message.contains("http://localhost:3026 after 3 attempts"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn circuit_breaker_fails_fast() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"(def client (http/client {:circuit-breaker {:failures 2 :reset-ms 60000}}))"#,
+    )
+    .await?;
+    for _ in 0..2 {
+        let result = compiler::evaluate_source(
+            env.clone(),
+            r#"(http/request client {:url "http://localhost:3026"})"#,
+        )
+        .await;
+        assert!(!format!("{}", result.unwrap_err()).contains("Circuit open"));
+    }
+    let result = compiler::evaluate_source(
+        env,
+        r#"(http/request client {:url "http://localhost:3026"})"#,
+    )
+    .await;
+    assert!(format!("{}", result.unwrap_err()).contains("Circuit open for http://localhost:3026"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_with_method_routes() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (http/server {:port 3027
+                      :routes {"/users" {:get (fn [req] "list users")
+                                         :post (fn [req] "create user")}
+                               "/any" (fn [req] (:method req))}})
+        "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        [(:text (http/request {:url "http://localhost:3027/users"}))
+         (:text (http/request {:url "http://localhost:3027/users" :method :post}))
+         (:status (http/request {:url "http://localhost:3027/users" :method :head}))
+         (:text (http/request {:url "http://localhost:3027/any" :method :patch}))]
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"["list users" "create user" 200 "PATCH"]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3027/users" :method :delete})]
+          [(:status response) (:allow (:headers response))])
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"[405 "GET, POST, HEAD, OPTIONS"]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3027/users" :method :options})]
+          [(:status response) (:allow (:headers response))])
+        "#,
+    )
+    .await?;
+    let (_, expected) =
+        compiler::evaluate_source(env, r#"[204 "GET, POST, HEAD, OPTIONS"]"#).await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_with_response_maps() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (http/server {:port 3028
+                      :routes {"/created" (fn [req]
+                                            {:status 201
+                                             :headers {:x-id "7"}
+                                             :cookies {:session {:value "abc"
+                                                                 :path "/"
+                                                                 :http-only true}}
+                                             :body {:id 7}})
+                               "/moved" {:redirect "/created" :status 303}
+                               "/cookies" (fn [req] (:cookies req))
+                               "/broken" (fn [req] (fn [x] x))}})
+        "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3028/created"})]
+          [(:status response)
+           (:x-id (:headers response))
+           (:set-cookie (:headers response))
+           (:json response)])
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"[201 "7" "session=abc; HttpOnly; Path=/" {:id 7}]"#)
+            .await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [client (http/client {:redirect :none})
+              response (http/request client {:url "http://localhost:3028/moved"})]
+          [(:status response) (:location (:headers response))])
+        "#,
+    )
+    .await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"[303 "/created"]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"(:json (http/request {:url "http://localhost:3028/cookies" :headers {:cookie "a=1; b=2"}}))"#,
+    )
+    .await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"{:a "1" :b "2"}"#).await?;
+    assert_eq!(actual, expected);
+    let (_, actual) = compiler::evaluate_source(
+        env,
+        r#"(:status (http/request {:url "http://localhost:3028/broken"}))"#,
+    )
+    .await?;
+    assert_eq!(actual, compiler::Expression::Integer(500.into()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_redirect_statuses() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (http/server {:port 3035
+                      :routes {"/found" {:redirect "/other"}
+                               "/see-other" {:redirect "/other" :status 303}
+                               "/temporary" {:redirect "/other" :status 307}}})
+        "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [client (http/client {:redirect :none})
+              status (fn [path]
+                       (:status (http/request client {:url (str "http://localhost:3035" path)})))]
+          [(status "/found") (status "/see-other") (status "/temporary")])
+        "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, "[302 303 307]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn handler_errors_become_responses() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (http/server {:port 3029
+                      :routes {"/fail" (fn [req] (nth [] 1))
+                               "/echo" (fn [req] (:json req))}})
+        "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3029/fail"})]
+          [(:status response) (:json response)])
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"[500 {:error "Internal Server Error"}]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (:status (http/request {:url "http://localhost:3029/echo"
+                                :method :post
+                                :headers {:content-type "application/json"}
+                                :body "{not json"}))
+        "#,
+    )
+    .await?;
+    assert_eq!(actual, compiler::Expression::Integer(400.into()));
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (http/server {:port 3030
+                      :on-error (fn [e] {:status (:status e)
+                                         :body (str "oops " (:method e) " " (:path e))})
+                      :routes {"/fail" (fn [req] (nth [] 1))}})
+        "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3030/fail"})]
+          [(:status response) (:text response)])
+        "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, r#"[500 "oops GET /fail"]"#).await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_with_middleware() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (http/server {:port 3031
+                      :middleware [:request-id
+                                   [:compress {:min-bytes 0}]
+                                   (fn [handler] (fn [req] (str "wrapped " (handler req))))]
+                      :routes {"/hello" "hello"
+                               "/admin" {:middleware [[:bearer-auth {:tokens ["secret"]}]]
+                                         :routes {"/" (fn [req] "admin")}}}})
+        "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3031/hello"})]
+          [(:status response) (:text response) (count (bytes (:x-request-id (:headers response))))])
+        "#,
+    )
+    .await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"[200 "wrapped hello" 36]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3031/hello"
+                                      :headers {:accept-encoding "gzip"}
+                                      :as :bytes})]
+          (:content-encoding (:headers response)))
+        "#,
+    )
+    .await?;
+    assert_eq!(actual, compiler::Expression::String("gzip".to_string()));
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3031/admin"})]
+          [(:status response) (:www-authenticate (:headers response))])
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"[401 "Bearer realm=\"yeti\""]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3031/admin"
+                                      :headers {:authorization "Bearer secret"}})]
+          [(:status response) (:text response)])
+        "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, r#"[200 "wrapped admin"]"#).await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_with_static_files() -> Result {
+    let root = std::env::temp_dir().join("yeti-static-files");
+    std::fs::create_dir_all(root.join("public/docs")).unwrap();
+    std::fs::write(root.join("public/hello.txt"), "hello world").unwrap();
+    std::fs::write(root.join("public/docs/index.html"), "<h1>docs</h1>").unwrap();
+    std::fs::write(root.join("secret.txt"), "secret").unwrap();
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let source = format!(
+        r#"(http/server {{:port 3032 :routes {{"/static" {{:dir "{}"}}}}}})"#,
+        root.join("public").display()
+    );
+    let (env, _) = compiler::evaluate_source(env, &source).await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3032/static/hello.txt"})]
+          [(:status response) (:text response) (:content-type (:headers response))])
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"[200 "hello world" "text/plain"]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [url "http://localhost:3032/static/hello.txt"
+              etag (:etag (:headers (http/request {:url url})))]
+          (:status (http/request {:url url :headers {:if-none-match etag}})))
+        "#,
+    )
+    .await?;
+    assert_eq!(actual, compiler::Expression::Integer(304.into()));
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3032/static/hello.txt"
+                                      :headers {:range "bytes=6-"}})]
+          [(:status response) (:text response) (:content-range (:headers response))])
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"[206 "world" "bytes 6-10/11"]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"(:text (http/request {:url "http://localhost:3032/static/docs/"}))"#,
+    )
+    .await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::String("<h1>docs</h1>".to_string())
+    );
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        [(:status (http/request {:url "http://localhost:3032/static/..%2fsecret.txt"}))
+         (:status (http/request {:url "http://localhost:3032/static/missing.txt"}))]
+        "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, "[404 404]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn compress_skips_ranges_and_binary_files() -> Result {
+    let root = std::env::temp_dir().join("yeti-compressed-files");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("page.html"), "hello ".repeat(100)).unwrap();
+    std::fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G']).unwrap();
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let source = format!(
+        r#"(http/server {{:port 3034
+                         :middleware [[:compress {{:min-bytes 0}}]]
+                         :routes {{"/static" {{:dir "{}"}}}}}})"#,
+        root.display()
+    );
+    let (env, _) = compiler::evaluate_source(env, &source).await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [url "http://localhost:3034/static/page.html"
+              plain (:headers (http/request {:url url}))
+              compressed (:headers (http/request {:url url
+                                                  :headers {:accept-encoding "gzip"}
+                                                  :as :bytes}))]
+          [(:content-encoding plain)
+           (:content-encoding compressed)
+           (= (:etag compressed) (str "W/" (:etag plain)))])
+        "#,
+    )
+    .await?;
+    let (env, expected) = compiler::evaluate_source(env, r#"[nil "gzip" true]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [gzip {:accept-encoding "gzip"}
+              partial (http/request {:url "http://localhost:3034/static/page.html"
+                                     :headers (assoc gzip :range "bytes=0-4")})
+              image (http/request {:url "http://localhost:3034/static/logo.png"
+                                   :headers gzip
+                                   :as :bytes})]
+          [(:status partial) (:text partial) (:content-encoding (:headers partial))
+           (:content-encoding (:headers image))])
+        "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, r#"[206 "hello" nil nil]"#).await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_with_host_and_any_port() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"(def s (http/server {:host "0.0.0.0" :port 0 :routes {"/" "hello"}}))"#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [{:port port :host host :scheme scheme} (http/server-info s)]
+          [host scheme (> port 0)
+           (:text (http/request {:url (str "http://localhost:" port "/")}))])
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"["0.0.0.0" "http" true "hello"]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, _) = compiler::evaluate_source(env, "(http/server-stop s)").await?;
+    assert!(
+        compiler::evaluate_source(env, r#"(http/server {:host "nowhere"})"#)
+            .await
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_over_tls() -> Result {
+    let root = std::env::temp_dir().join("yeti-server-tls");
+    std::fs::create_dir_all(&root).unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    std::fs::write(root.join("cert.pem"), cert.serialize_pem().unwrap()).unwrap();
+    std::fs::write(root.join("key.pem"), cert.serialize_private_key_pem()).unwrap();
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let source = format!(
+        r#"(def s (http/server {{:port 0 :cert "{}" :key "{}" :routes {{"/" "secure"}}}}))"#,
+        root.join("cert.pem").display(),
+        root.join("key.pem").display()
+    );
+    let (env, _) = compiler::evaluate_source(env, &source).await?;
+    let (env, url) = compiler::evaluate_source(env, "(:url (http/server-info s))").await?;
+    let url = compiler::extract::string(url)?.replace("127.0.0.1", "localhost");
+    assert!(url.starts_with("https://"));
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let text = client.get(&url).send().await.unwrap().text().await.unwrap();
+    assert_eq!(text, "secure");
+    compiler::evaluate_source(env, "(http/server-stop s)").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_redefinition_keeps_the_running_server() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) =
+        compiler::evaluate_source(env, r#"(http/server {:port 3036 :routes {"/" "old"}})"#).await?;
+    let redefined = compiler::evaluate_source(
+        env.clone(),
+        r#"(http/server {:port 3036 :cert "missing.pem" :key "missing.pem" :routes {"/" "new"}})"#,
+    )
+    .await;
+    assert!(redefined.is_err());
+    let (_, actual) = compiler::evaluate_source(
+        env,
+        r#"(:text (http/request {:url "http://localhost:3036"}))"#,
+    )
+    .await?;
+    assert_eq!(actual, compiler::Expression::String("old".to_string()));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn server_on_unix_socket() -> Result {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let path = std::env::temp_dir().join("yeti-server.sock");
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let source = format!(
+        r#"(http/server {{:socket "{}" :routes {{"/" "over a socket"}}}})"#,
+        path.display()
+    );
+    let (env, _) = compiler::evaluate_source(env, &source).await?;
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("over a socket"));
+    let source = format!(r#"(http/server-stop {{:socket "{}"}})"#, path.display());
+    compiler::evaluate_source(env, &source).await?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn server_keeps_a_socket_in_use() -> Result {
+    let path = std::env::temp_dir().join("yeti-busy.sock");
+    std::fs::remove_file(&path).ok();
+    let other = tokio::net::UnixListener::bind(&path).unwrap();
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let source = format!(
+        r#"(http/server {{:socket "{}" :routes {{"/" "hello"}}}})"#,
+        path.display()
+    );
+    assert!(compiler::evaluate_source(env.clone(), &source)
+        .await
+        .is_err());
+    assert!(path.exists());
+    drop(other);
+    let (env, _) = compiler::evaluate_source(env, &source).await?;
+    let source = format!(r#"(http/server-stop {{:socket "{}"}})"#, path.display());
+    compiler::evaluate_source(env, &source).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_stops_by_its_address() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (def s (http/server {:port 0 :routes {"/" "hello"}}))
+        (def url (:url (http/server-info s)))
+        (http/server-stop (http/server-info s))
+        (http/server-stop s)
+        "#,
+    )
+    .await?;
+    assert!(compiler::evaluate_source(
+        env,
+        "(http/request {:url url :retry false :timeout-ms 1000})"
+    )
+    .await
+    .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_runs_middleware_for_automatic_responses() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "http".to_string(),
+        compiler::Expression::Module(http::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+        (http/server {:port 3033
+                      :middleware [:request-id [:cors {:origins ["http://example.com"]}]]
+                      :routes {"/users" {:get (fn [req] "list users")}}})
+        "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3033/users"
+                                      :method :options
+                                      :headers {:origin "http://example.com"
+                                                :access-control-request-method "GET"}})]
+          [(:status response)
+           (:access-control-allow-origin (:headers response))
+           (:access-control-allow-methods (:headers response))
+           (count (bytes (:x-request-id (:headers response))))])
+        "#,
+    )
+    .await?;
+    let (env, expected) =
+        compiler::evaluate_source(env, r#"[204 "http://example.com" "GET" 36]"#).await?;
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [response (http/request {:url "http://localhost:3033/users"
+                                      :method :delete
+                                      :headers {:origin "http://example.com"}})]
+          [(:status response)
+           (:access-control-allow-origin (:headers response))
+           (count (bytes (:x-request-id (:headers response))))])
+        "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, r#"[405 "http://example.com" 36]"#).await?;
+    assert_eq!(actual, expected);
     Ok(())
 }
@@ -17,18 +17,44 @@ pub fn environment() -> Environment {
                 Box::pin(async move {
                     let (env, args) = compiler::evaluate_expressions(env, args).await?;
                     let path = compiler::extract::string(args[0].clone())?;
-                    let contents = tokio::fs::read_to_string(path).await
-                        .map_err(|_| compiler::effect::error("Could not read file"))?;
+                    let contents = match args.get(1) {
+                        Some(encoding) => {
+                            let encoding = compiler::extract::keyword(encoding.clone())?;
+                            let bytes = tokio::fs::read(path).await
+                                .map_err(|_| compiler::effect::error("Could not read file"))?;
+                            compiler::binary::decode(&bytes, &encoding)?
+                        }
+                        None => tokio::fs::read_to_string(path).await
+                            .map_err(|_| compiler::effect::error("Could not read file"))?,
+                    };
                     Ok((env, compiler::Expression::String(contents)))
                 })
             }
         ),
+        "read-bytes".to_string() => compiler::Expression::NativeFunction(
+            |env, args| {
+                Box::pin(async move {
+                    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+                    let path = compiler::extract::string(args[0].clone())?;
+                    let contents = tokio::fs::read(path).await
+                        .map_err(|_| compiler::effect::error("Could not read file"))?;
+                    Ok((env, compiler::Expression::Bytes(contents.into())))
+                })
+            }
+        ),
         "write-file".to_string() => compiler::Expression::NativeFunction(
             |env, args| {
                 Box::pin(async move {
                     let (env, args) = compiler::evaluate_expressions(env, args).await?;
                     let path = compiler::extract::string(args[0].clone())?;
-                    let contents = compiler::extract::string(args[1].clone())?;
+                    let contents = match &args[1] {
+                        compiler::Expression::Bytes(bytes) => bytes.clone(),
+                        compiler::Expression::String(text) => match args.get(2) {
+                            Some(encoding) => compiler::binary::encode(text, &compiler::extract::keyword(encoding.clone())?)?,
+                            None => text.clone().into(),
+                        },
+                        _ => return Err(compiler::effect::error("Expected string or bytes")),
+                    };
                     tokio::fs::write(path, contents).await
                         .map_err(|_| compiler::effect::error("Could not write file"))?;
                     Ok((env, compiler::Expression::Nil))
@@ -3,14 +3,84 @@
 
 extern crate alloc;
 
-use compiler::effect::error;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use compiler::effect::{error, Effect};
 use compiler::evaluate_expressions;
-use compiler::expression::Environment;
+use compiler::expression::{exact_number, map_key, Environment, Numbers, Serialized};
 use compiler::extract;
 use compiler::Expression::{self, NativeFunction};
-use alloc::boxed::Box;
-use alloc::string::ToString;
-use im::ordmap;
+use compiler::Float;
+use im::{ordmap, OrdMap, Vector};
+use rug::Integer;
+use serde_json::{Map, Number, Value};
+
+/// Converts an expression to JSON. Numbers that don't fit an `i64`, `u64` or
+/// `f64` are written exactly as JSON numbers under `Numbers::Exact`, and bytes
+/// become an array of integers.
+pub fn to_value(expression: &Expression, numbers: Numbers) -> Result<Value, Effect> {
+    if let Some(text) = exact_number(expression, numbers) {
+        return text
+            .parse::<Number>()
+            .map(Value::Number)
+            .map_err(|e| error(&format!("Could not convert {} to json: {}", text, e)));
+    }
+    match expression {
+        Expression::Array(expressions) => expressions
+            .iter()
+            .map(|e| to_value(e, numbers))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Expression::Map(map) => {
+            let mut object = Map::new();
+            for (k, v) in map {
+                let key =
+                    map_key(k).ok_or_else(|| error(&format!("Cannot use {} as a map key", k)))?;
+                object.insert(key, to_value(v, numbers)?);
+            }
+            Ok(Value::Object(object))
+        }
+        expression => serde_json::to_value(Serialized {
+            expression,
+            numbers,
+        })
+        .map_err(|e| error(&format!("Could not convert to json: {}", e))),
+    }
+}
+
+/// Converts JSON to an expression, keeping every digit of its numbers. Object
+/// keys become keywords.
+pub fn from_value(value: Value) -> Result<Expression, Effect> {
+    match value {
+        Value::Null => Ok(Expression::Nil),
+        Value::Bool(b) => Ok(Expression::Bool(b)),
+        Value::String(s) => Ok(Expression::String(s)),
+        Value::Number(n) => {
+            let text = n.to_string();
+            let number = match text.contains(['.', 'e', 'E']) {
+                true => rug::Float::parse(&text)
+                    .ok()
+                    .map(|_| Expression::Float(Float::from_str(&text))),
+                false => text.parse::<Integer>().ok().map(Expression::Integer),
+            };
+            number.ok_or_else(|| error(&format!("Invalid number {}", text)))
+        }
+        Value::Array(values) => values
+            .into_iter()
+            .map(from_value)
+            .collect::<Result<Vector<_>, _>>()
+            .map(Expression::Array),
+        Value::Object(object) => {
+            let mut map = OrdMap::new();
+            for (k, v) in object {
+                map.insert(Expression::Keyword(format!(":{}", k)), from_value(v)?);
+            }
+            Ok(Expression::Map(map))
+        }
+    }
+}
 
 pub fn environment() -> Environment {
     ordmap! {
@@ -19,8 +89,10 @@ pub fn environment() -> Environment {
             |env, args| {
                 Box::pin(async move {
                     let (env, args) = evaluate_expressions(env, args).await?;
-                    let json = serde_json::to_string_pretty(&args[0])
-                        .map_err(|_| error("Could not convert to json"))?;
+                    let numbers = Numbers::from_options(&env, args.get(1))?;
+                    let value = to_value(&args[0], numbers)?;
+                    let json = serde_json::to_string_pretty(&value)
+                        .map_err(|e| error(&format!("Could not convert to json: {}", e)))?;
                     Ok((env, Expression::String(json)))
                 })
             }
@@ -30,9 +102,9 @@ pub fn environment() -> Environment {
                 Box::pin(async move {
                     let (env, args) = evaluate_expressions(env, args).await?;
                     let string = extract::string(args[0].clone())?;
-                    let json = serde_json::from_str::<Expression>(&string)
+                    let json = serde_json::from_str::<Value>(&string)
                         .map_err(|_| error("Could not parse json"))?;
-                    Ok((env, json))
+                    Ok((env, from_value(json)?))
                 })
             }
         )
@@ -1,7 +1,3 @@
-use base;
-use compiler;
-use json;
-
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
 #[tokio::test]
@@ -68,3 +64,158 @@ async fn json_from_string_for_map_with_int() -> Result {
     assert_eq!(actual, expected);
     Ok(())
 }
+
+#[tokio::test]
+async fn json_to_string_for_decimal() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "json".to_string(),
+        compiler::Expression::Module(json::environment()),
+    );
+    let (_, actual) =
+        compiler::evaluate_source(env, r#"(json/to-string {:total 12345678901234567890.10M})"#)
+            .await?;
+    let expected =
+        compiler::Expression::String("{\n  \"total\": \"12345678901234567890.10\"\n}".to_string());
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn json_to_string_for_big_integer() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "json".to_string(),
+        compiler::Expression::Module(json::environment()),
+    );
+    let (env, actual) =
+        compiler::evaluate_source(env, r#"(json/to-string [123456789012345678901234567890])"#)
+            .await?;
+    let expected =
+        compiler::Expression::String("[\n  123456789012345678901234567890\n]".to_string());
+    assert_eq!(actual, expected);
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"(json/to-string [1234567890123456789012345678901234567890])"#,
+    )
+    .await?;
+    let expected = compiler::Expression::String(
+        "[\n  1234567890123456789012345678901234567890\n]".to_string(),
+    );
+    assert_eq!(actual, expected);
+    let (_, actual) = compiler::evaluate_source(
+        env,
+        r#"(json/to-string [1234567890123456789012345678901234567890] {:numbers :string})"#,
+    )
+    .await?;
+    let expected = compiler::Expression::String(
+        "[\n  \"1234567890123456789012345678901234567890\"\n]".to_string(),
+    );
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn json_to_string_for_ratio() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "json".to_string(),
+        compiler::Expression::Module(json::environment()),
+    );
+    let (env, actual) = compiler::evaluate_source(env, "(json/to-string [1/3])").await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::String("[\n  \"1/3\"\n]".to_string())
+    );
+    let (env, actual) =
+        compiler::evaluate_source(env, "(json/to-string [1/3] {:numbers :string})").await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::String("[\n  \"1/3\"\n]".to_string())
+    );
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"(rational (nth (json/from-string (json/to-string [1/3] {:numbers :string})) 0))"#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, "1/3").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn json_to_string_for_float() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "json".to_string(),
+        compiler::Expression::Module(json::environment()),
+    );
+    let (env, actual) = compiler::evaluate_source(env, "(json/to-string [0.5])").await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::String("[\n  0.5\n]".to_string())
+    );
+    let (env, actual) =
+        compiler::evaluate_source(env, "(json/to-string [(with-precision 100 (/ 1.0 3))])").await?;
+    let compiler::Expression::String(json) = actual else {
+        panic!("Expected string");
+    };
+    assert!(json.starts_with("[\n  0.333333333333333333333333333333"));
+    let (_, actual) = compiler::evaluate_source(
+        env,
+        "(let [*numbers* :float] (json/to-string [(with-precision 100 (/ 1.0 3))]))",
+    )
+    .await?;
+    assert_eq!(
+        actual,
+        compiler::Expression::String("[\n  0.3333333333333333\n]".to_string())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn json_round_trips_big_numbers() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "json".to_string(),
+        compiler::Expression::Module(json::environment()),
+    );
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [x (* 99999999999999999999 99999999999999999999 99999999999999999999 -7)]
+          (= x (json/from-string (json/to-string x))))
+        "#,
+    )
+    .await?;
+    assert_eq!(actual, compiler::Expression::Bool(true));
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+        (let [x (with-precision 200 (/ 1.0 3))]
+          (= x (json/from-string (json/to-string x))))
+        "#,
+    )
+    .await?;
+    assert_eq!(actual, compiler::Expression::Bool(true));
+    let (env, actual) =
+        compiler::evaluate_source(env, r#"(json/from-string "[1, 0.5, 1e3]")"#).await?;
+    let (_, expected) = compiler::evaluate_source(env, "[1 0.5 1000.0]").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn json_to_string_for_bytes() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "json".to_string(),
+        compiler::Expression::Module(json::environment()),
+    );
+    let (_, actual) =
+        compiler::evaluate_source(env, r#"(json/to-string {:data #x"01ff"})"#).await?;
+    let expected =
+        compiler::Expression::String("{\n  \"data\": [\n    1,\n    255\n  ]\n}".to_string());
+    assert_eq!(actual, expected);
+    Ok(())
+}
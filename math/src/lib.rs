@@ -0,0 +1,86 @@
+#![no_std]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::ToString;
+use compiler::effect::Effect;
+use compiler::expression::Environment;
+use compiler::Expression::{self, NativeFunction};
+use im::{ordmap, Vector};
+use rug::float::Constant;
+use rug::ops::Pow;
+
+type Result = core::result::Result<(Environment, Expression), Effect>;
+
+fn precision(env: &Environment, args: &Vector<Expression>) -> core::result::Result<u32, Effect> {
+    let precision = compiler::precision_from(env)?;
+    let widest = args
+        .iter()
+        .filter_map(|arg| match arg {
+            Expression::Float(f) => Some(f.as_float().prec()),
+            _ => None,
+        })
+        .max();
+    let widest = widest.unwrap_or_default().max(compiler::DEFAULT_PRECISION);
+    Ok(precision.unwrap_or(widest))
+}
+
+fn float(f: rug::Float) -> Expression {
+    Expression::Float(compiler::Float::from_float(f))
+}
+
+async fn unary(
+    env: Environment,
+    args: Vector<Expression>,
+    f: fn(rug::Float) -> rug::Float,
+) -> Result {
+    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+    let bits = precision(&env, &args)?;
+    let x = compiler::as_float(&args[0], Some(bits))?;
+    Ok((env, float(f(x))))
+}
+
+async fn binary(
+    env: Environment,
+    args: Vector<Expression>,
+    f: fn(rug::Float, &rug::Float) -> rug::Float,
+) -> Result {
+    let (env, args) = compiler::evaluate_expressions(env, args).await?;
+    let bits = precision(&env, &args)?;
+    let x = compiler::as_float(&args[0], Some(bits))?;
+    let y = compiler::as_float(&args[1], Some(bits))?;
+    Ok((env, float(f(x, &y))))
+}
+
+async fn constant(env: Environment, args: Vector<Expression>, f: fn(u32) -> rug::Float) -> Result {
+    let bits = precision(&env, &args)?;
+    Ok((env, float(f(bits))))
+}
+
+pub fn environment() -> Environment {
+    ordmap! {
+        "*name*".to_string() => Expression::String("math".to_string()),
+        "sqrt".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::sqrt))),
+        "cbrt".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::cbrt))),
+        "exp".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::exp))),
+        "log".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::ln))),
+        "log2".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::log2))),
+        "log10".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::log10))),
+        "sin".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::sin))),
+        "cos".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::cos))),
+        "tan".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::tan))),
+        "asin".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::asin))),
+        "acos".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::acos))),
+        "atan".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::atan))),
+        "sinh".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::sinh))),
+        "cosh".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::cosh))),
+        "tanh".to_string() => NativeFunction(|env, args| Box::pin(unary(env, args, rug::Float::tanh))),
+        "pow".to_string() => NativeFunction(|env, args| Box::pin(binary(env, args, |x, y| x.pow(y)))),
+        "atan2".to_string() => NativeFunction(|env, args| Box::pin(binary(env, args, rug::Float::atan2))),
+        "hypot".to_string() => NativeFunction(|env, args| Box::pin(binary(env, args, rug::Float::hypot))),
+        "pi".to_string() => NativeFunction(|env, args| Box::pin(constant(env, args, |bits| rug::Float::with_val(bits, Constant::Pi)))),
+        "e".to_string() => NativeFunction(|env, args| Box::pin(constant(env, args, |bits| rug::Float::with_val(bits, 1).exp())))
+    }
+}
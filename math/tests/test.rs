@@ -0,0 +1,50 @@
+use compiler::Expression;
+use rug::Integer;
+
+type Result = std::result::Result<(), compiler::effect::Effect>;
+
+fn environment() -> compiler::Environment {
+    let mut env = base::environment();
+    env.insert("math".to_string(), Expression::Module(math::environment()));
+    env
+}
+
+#[tokio::test]
+async fn sqrt_of_integer() -> Result {
+    let (_, actual) = compiler::evaluate_source(environment(), "(math/sqrt 16)").await?;
+    assert_eq!(actual, Expression::Float(compiler::Float::from_f64(4.0)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn pow_of_floats() -> Result {
+    let (_, actual) = compiler::evaluate_source(environment(), "(round (math/pow 2 10))").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(1024)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn exp_and_log_are_inverse() -> Result {
+    let (_, actual) =
+        compiler::evaluate_source(environment(), "(round (math/log (math/exp 3)))").await?;
+    assert_eq!(actual, Expression::Integer(Integer::from(3)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn pi_uses_precision() -> Result {
+    let (_, actual) =
+        compiler::evaluate_source(environment(), "(with-precision 200 (math/pi))").await?;
+    let Expression::Float(pi) = actual else {
+        panic!("Expected float");
+    };
+    assert_eq!(pi.as_float().prec(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn sin_of_zero() -> Result {
+    let (_, actual) = compiler::evaluate_source(environment(), "(math/sin 0)").await?;
+    assert_eq!(actual, Expression::Float(compiler::Float::from_f64(0.0)));
+    Ok(())
+}
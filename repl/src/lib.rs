@@ -6,7 +6,6 @@ extern crate alloc;
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
-use compiler;
 use compiler::effect::error;
 use im::ordmap;
 use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt};
@@ -73,7 +72,7 @@ pub async fn read() -> Result<Vec<compiler::Expression>> {
         .await
         .map_err(|_| error("Could not write to stdout"))?;
     let input = read_from_stdin().await;
-    let tokens = compiler::tokenize(&input);
+    let tokens = compiler::tokenize(&input)?;
     let expressions = compiler::parse_all(&tokens);
     Ok(expressions)
 }
@@ -85,7 +84,19 @@ pub async fn evaluate(
     let mut env = env;
     let mut result = compiler::Expression::Nil;
     for expr in exprs {
-        let (new_env, new_result) = compiler::evaluate(env, expr.clone()).await?;
+        let (expr, changes) = compiler::optimize(&env, expr.clone());
+        if let Some(debug) = env.get("*debug*") {
+            if !matches!(
+                debug,
+                compiler::Expression::Nil | compiler::Expression::Bool(false)
+            ) {
+                for change in changes {
+                    print_change(change).await?;
+                }
+            }
+        }
+        let prototype = compiler::bytecode::compile(&env, expr)?;
+        let (new_env, new_result) = compiler::vm::run(env, prototype).await?;
         env = new_env;
         result = new_result;
     }
@@ -105,6 +116,19 @@ pub async fn print(expression: compiler::Expression) -> Result<()> {
     Ok(())
 }
 
+async fn print_change(change: String) -> Result<()> {
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(format!("{}{}{}\n", BLUE, change, RESET).as_bytes())
+        .await
+        .map_err(|_| error("Could not write to stdout"))?;
+    stdout
+        .flush()
+        .await
+        .map_err(|_| error("Could not write to stdout"))?;
+    Ok(())
+}
+
 pub async fn print_effect(effect: compiler::effect::Effect) -> Result<()> {
     let mut stdout = io::stdout();
     stdout
@@ -205,7 +205,9 @@ impl ToSql for ExpressionWrapper {
             Expression::Integer(i) => Ok(ToSqlOutput::Owned(Value::Integer(i.to_i64().unwrap()))),
             Expression::Float(f) => Ok(ToSqlOutput::Owned(Value::Real(f.to_f64()))),
             Expression::Ratio(r) => Ok(ToSqlOutput::Owned(Value::Real(r.to_f64()))),
+            Expression::Decimal(d) => Ok(ToSqlOutput::Owned(Value::Text(d.to_string()))),
             Expression::String(s) => Ok(ToSqlOutput::Owned(Value::Text(s.clone()))),
+            Expression::Bytes(b) => Ok(ToSqlOutput::Borrowed(ValueRef::Blob(b))),
             Expression::Nil => Ok(ToSqlOutput::Owned(Value::Null)),
             Expression::Bool(b) => Ok(ToSqlOutput::Owned(Value::Integer(if *b { 1 } else { 0 }))),
             _ => {
@@ -229,9 +231,7 @@ impl FromSql for ExpressionWrapper {
             ValueRef::Text(text) => Ok(ExpressionWrapper(Expression::String(
                 String::from_utf8_lossy(text).into_owned(),
             ))),
-            ValueRef::Blob(blob) => Ok(ExpressionWrapper(Expression::String(
-                String::from_utf8_lossy(blob).into_owned(),
-            ))),
+            ValueRef::Blob(blob) => Ok(ExpressionWrapper(Expression::Bytes(blob.to_vec().into()))),
         }
     }
 }
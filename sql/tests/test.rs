@@ -1,7 +1,3 @@
-use base;
-use compiler;
-use sql;
-
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
 #[tokio::test]
@@ -213,3 +209,91 @@ async fn insert_and_select() -> Result {
     assert_eq!(actual, expected);
     Ok(())
 }
+
+#[tokio::test]
+async fn decimals_are_stored_as_text() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "sql".to_string(),
+        compiler::Expression::Module(sql::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(env, "(def conn (sql/connect))").await?;
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+    (sql/execute! conn
+     {:create-table :invoices
+      :with-columns [[:id :int [:not nil]]
+                     [:total :text [:not nil]]]})
+    "#,
+    )
+    .await?;
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+    (sql/execute! conn
+     {:insert-into :invoices
+      :columns [:id :total]
+      :values [[1 12345678901234567890.01M]]})
+    "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+    (decimal (:total (nth (sql/query conn
+                           {:select [:total]
+                            :from :invoices
+                            :where [:= :id 1]})
+                          0)))
+    "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, "12345678901234567890.01M").await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn bytes_round_trip_as_blobs() -> Result {
+    let mut env = base::environment();
+    env.insert(
+        "sql".to_string(),
+        compiler::Expression::Module(sql::environment()),
+    );
+    let (env, _) = compiler::evaluate_source(env, "(def conn (sql/connect))").await?;
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+    (sql/execute! conn
+     {:create-table :images
+      :with-columns [[:id :int [:not nil]]
+                     [:data :blob [:not nil]]]})
+    "#,
+    )
+    .await?;
+    let (env, _) = compiler::evaluate_source(
+        env,
+        r#"
+    (sql/execute! conn
+     {:insert-into :images
+      :columns [:id :data]
+      :values [[1 #x"89504e47ff00"]]})
+    "#,
+    )
+    .await?;
+    let (env, actual) = compiler::evaluate_source(
+        env,
+        r#"
+    (:data (nth (sql/query conn
+                 {:select [:data]
+                  :from :images
+                  :where [:= :id 1]})
+                0))
+    "#,
+    )
+    .await?;
+    let (_, expected) = compiler::evaluate_source(env, r#"#x"89504e47ff00""#).await?;
+    assert_eq!(actual, expected);
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use compiler::effect::{error, Effect};
+use compiler::expression::Call;
+use compiler::{extract, Environment, Expression};
+use im::{ordmap, OrdMap};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"YETIBNDL";
+
+type Result<T> = core::result::Result<T, Effect>;
+
+pub struct Bundle {
+    pub main: String,
+    pub modules: OrdMap<Expression, Expression>,
+}
+
+fn imports(expression: &Expression, names: &mut Vec<String>) -> Result<()> {
+    match expression {
+        Expression::Call(Call {
+            function,
+            arguments,
+        }) if matches!(function.as_ref(), Expression::Symbol(f) if f == "import") => {
+            match (arguments.len(), arguments.front()) {
+                (1, Some(Expression::Symbol(name))) => {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                    Ok(())
+                }
+                _ => Err(error(&format!(
+                    "Cannot bundle {}, imports must name a module",
+                    expression
+                ))),
+            }
+        }
+        Expression::Call(Call {
+            function,
+            arguments,
+        }) => {
+            imports(function, names)?;
+            arguments.iter().try_for_each(|e| imports(e, names))
+        }
+        Expression::Symbol(s) if s == "import" => Err(error(
+            "Cannot bundle import used as a value, imports must name a module",
+        )),
+        Expression::Array(expressions) => expressions.iter().try_for_each(|e| imports(e, names)),
+        Expression::Map(map) => map.iter().try_for_each(|(k, v)| {
+            imports(k, names)?;
+            imports(v, names)
+        }),
+        Expression::Quote(e) | Expression::Deref(e) => imports(e, names),
+        _ => Ok(()),
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|_| error(&format!("Could not read file {}", path.display())))
+}
+
+fn collect(
+    directory: &Path,
+    source: &str,
+    modules: &mut OrdMap<Expression, Expression>,
+) -> Result<()> {
+    let tokens = compiler::tokenize(source)?;
+    let mut names = vec![];
+    for expression in compiler::parse_all(&tokens) {
+        imports(&expression, &mut names)?;
+    }
+    for name in names {
+        let key = Expression::String(name.clone());
+        if modules.contains_key(&key) {
+            continue;
+        }
+        let path = directory.join(format!("{}.yeti", name));
+        let source = std::fs::read_to_string(&path).map_err(|_| {
+            error(&format!(
+                "Could not resolve import {}, {} not found",
+                name,
+                path.display()
+            ))
+        })?;
+        modules.insert(key, Expression::String(source.clone()));
+        collect(directory, &source, modules)?;
+    }
+    Ok(())
+}
+
+pub fn build(args: &[String]) -> Result<()> {
+    let (input, output) = match args {
+        [input, flag, output] if flag == "-o" => (PathBuf::from(input), PathBuf::from(output)),
+        [input] => {
+            let input = PathBuf::from(input);
+            let output = input.with_extension("");
+            (input, output)
+        }
+        _ => return Err(error("Usage: yeti build <file.yeti> -o <output>")),
+    };
+    let directory = input.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let main = read(&input)?;
+    let mut modules = OrdMap::new();
+    collect(&directory, &main, &mut modules)?;
+    let payload = format!(
+        "{}",
+        Expression::Map(ordmap! {
+            Expression::Keyword(":main".to_string()) => Expression::String(main),
+            Expression::Keyword(":modules".to_string()) => Expression::Map(modules)
+        })
+    );
+    let executable =
+        std::env::current_exe().map_err(|_| error("Could not locate the yeti executable"))?;
+    let mut binary =
+        std::fs::read(executable).map_err(|_| error("Could not read the yeti executable"))?;
+    binary.extend_from_slice(payload.as_bytes());
+    binary.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    binary.extend_from_slice(MAGIC);
+    std::fs::write(&output, binary)
+        .map_err(|_| error(&format!("Could not write file {}", output.display())))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&output, std::fs::Permissions::from_mode(0o755))
+            .map_err(|_| error(&format!("Could not make {} executable", output.display())))?;
+    }
+    Ok(())
+}
+
+pub fn embedded() -> Result<Option<Bundle>> {
+    let Some(mut file) = std::env::current_exe()
+        .ok()
+        .and_then(|e| File::open(e).ok())
+    else {
+        return Ok(None);
+    };
+    let mut trailer = [0u8; 16];
+    if file.seek(SeekFrom::End(-16)).is_err() || file.read_exact(&mut trailer).is_err() {
+        return Ok(None);
+    }
+    if &trailer[8..] != MAGIC {
+        return Ok(None);
+    }
+    let length = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    let size = file
+        .metadata()
+        .map_err(|_| error("Could not read the yeti executable"))?
+        .len();
+    if length > size - 16 {
+        return Err(error("Bundled program is truncated"));
+    }
+    let mut payload = vec![0u8; length as usize];
+    file.seek(SeekFrom::End(-16 - length as i64))
+        .and_then(|_| file.read_exact(&mut payload))
+        .map_err(|_| error("Could not read the bundled program"))?;
+    let payload =
+        String::from_utf8(payload).map_err(|_| error("Bundled program is not valid UTF-8"))?;
+    let tokens = compiler::tokenize(&payload)?;
+    let (_, bundle) = compiler::parse(&tokens);
+    let bundle = extract::map(bundle)?;
+    let main = extract::string(extract::key(bundle.clone(), ":main")?)?;
+    let modules = extract::map(extract::key(bundle, ":modules")?)?;
+    Ok(Some(Bundle { main, modules }))
+}
+
+pub fn script(args: &[String]) -> Result<Bundle> {
+    match args {
+        [input] => Ok(Bundle {
+            main: read(Path::new(input))?,
+            modules: OrdMap::new(),
+        }),
+        _ => Err(error("Usage: yeti run <file.yeti>")),
+    }
+}
+
+async fn serving(env: &Environment) -> bool {
+    let Some(Expression::Module(http)) = env.get("http") else {
+        return false;
+    };
+    let Some(Expression::Atom(servers)) = http.get("*servers*") else {
+        return false;
+    };
+    let servers = servers.0.lock().await;
+    matches!(&*servers, Expression::Map(m) if !m.is_empty())
+}
+
+pub async fn run(mut env: Environment, bundle: Bundle) -> Result<()> {
+    env.insert("*modules*".to_string(), Expression::Map(bundle.modules));
+    let (env, _) = compiler::vm::evaluate_source(env, &bundle.main).await?;
+    if serving(&env).await {
+        tokio::signal::ctrl_c()
+            .await
+            .map_err(|_| error("Could not listen for ctrl-c"))?;
+    }
+    Ok(())
+}
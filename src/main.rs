@@ -1,15 +1,6 @@
-use base;
-use compiler;
 use compiler::Expression::Module;
-use html;
-use http;
-use io;
-use json;
-use sql;
-use toml;
-use yaml;
-use repl;
 
+mod bundle;
 
 fn repl_environment() -> compiler::Environment {
     let mut env = base::environment();
@@ -21,6 +12,7 @@ fn repl_environment() -> compiler::Environment {
     env.insert("http".to_string(), Module(http::environment()));
     env.insert("io".to_string(), Module(io::environment()));
     env.insert("json".to_string(), Module(json::environment()));
+    env.insert("math".to_string(), Module(math::environment()));
     env.insert("sql".to_string(), Module(sql::environment()));
     env.insert("toml".to_string(), Module(toml::environment()));
     env.insert("yaml".to_string(), Module(yaml::environment()));
@@ -29,21 +21,44 @@ fn repl_environment() -> compiler::Environment {
 
 type Result<T> = core::result::Result<T, compiler::effect::Effect>;
 
-async fn read_evaluate_print(env: compiler::Environment) -> Result<compiler::Environment> {
-    let expressions = repl::read().await?;
-    let (env, expression) = repl::evaluate(env, &expressions).await?;
+async fn read_evaluate_print(env: compiler::Environment) -> Result<Option<compiler::Environment>> {
+    // Once the first evaluation listens for Ctrl-C the default handler is gone,
+    // so an interrupt at the prompt has to end the session here.
+    let expressions = tokio::select! {
+        expressions = repl::read() => expressions?,
+        _ = tokio::signal::ctrl_c() => return Ok(None),
+    };
+    let (scoped, tasks) = compiler::task::tracked(env);
+    let (mut env, expression) = tokio::select! {
+        result = repl::evaluate(scoped, &expressions) => result?,
+        _ = tokio::signal::ctrl_c() => {
+            compiler::task::cancel_tracked(&tasks).await;
+            return Err(compiler::effect::error("Evaluation cancelled"));
+        }
+    };
+    env.remove(compiler::task::TRACKED);
     repl::print(expression).await?;
-    Ok(env)
+    Ok(Some(env))
 }
 
 #[tokio::main]
 async fn main() -> core::result::Result<(), compiler::effect::Effect> {
+    if let Some(bundle) = bundle::embedded()? {
+        return bundle::run(repl_environment(), bundle).await;
+    }
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("build") => return bundle::build(&args[2..]),
+        Some("run") => return bundle::run(repl_environment(), bundle::script(&args[2..])?).await,
+        _ => {}
+    }
     let mut env = repl_environment();
     loop {
         match read_evaluate_print(env.clone()).await {
-            Ok(next_env) => {
+            Ok(Some(next_env)) => {
                 env = next_env;
             }
+            Ok(None) => return Ok(()),
             Err(effect) => repl::print_effect(effect).await?,
         }
     }
@@ -1,7 +1,4 @@
-use base;
-use compiler;
 use compiler::expression::Expression::Module;
-use toml;
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
@@ -3,13 +3,14 @@
 
 extern crate alloc;
 
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
 use compiler::effect::error;
 use compiler::evaluate_expressions;
-use compiler::expression::Environment;
+use compiler::expression::{Environment, Numbers, Serialized};
 use compiler::extract;
 use compiler::Expression::{self, NativeFunction};
-use alloc::boxed::Box;
-use alloc::string::ToString;
 use im::ordmap;
 
 pub fn environment() -> Environment {
@@ -19,8 +20,13 @@ pub fn environment() -> Environment {
             |env, args| {
                 Box::pin(async move {
                     let (env, args) = evaluate_expressions(env, args).await?;
-                    let yaml = serde_yaml::to_string(&args[0])
-                        .map_err(|_| error("Could not convert to yaml"))?;
+                    let numbers = Numbers::from_options(&env, args.get(1))?;
+                    let serialized = Serialized {
+                        expression: &args[0],
+                        numbers,
+                    };
+                    let yaml = serde_yaml::to_string(&serialized)
+                        .map_err(|e| error(&format!("Could not convert to yaml: {}", e)))?;
                     Ok((env, Expression::String(yaml)))
                 })
             }
@@ -1,7 +1,4 @@
-use base;
-use compiler;
 use compiler::Expression::Module;
-use yaml;
 
 type Result = std::result::Result<(), compiler::effect::Effect>;
 
@@ -49,3 +46,15 @@ async fn yaml_from_string_for_map_with_int() -> Result {
     assert_eq!(actual, expected);
     Ok(())
 }
+
+#[tokio::test]
+async fn yaml_to_string_with_string_numbers() -> Result {
+    let mut env = base::environment();
+    env.insert("yaml".to_string(), Module(yaml::environment()));
+    let (_, actual) =
+        compiler::evaluate_source(env, r#"(yaml/to-string {:ratio 1/3} {:numbers :string})"#)
+            .await?;
+    let expected = compiler::Expression::String("ratio: 1/3\n".to_string());
+    assert_eq!(actual, expected);
+    Ok(())
+}